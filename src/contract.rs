@@ -1,10 +1,13 @@
-use cosmwasm_std::{Uint128, Decimal, DepsMut, Env, MessageInfo, Response, StdError, Addr};
-use cw2::set_contract_version;
+use cosmwasm_std::{Uint128, Decimal, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Addr, Order, Timestamp, Storage};
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::{Bound, Item, Map};
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 use crate::error::ContractError;
+use crate::execute::record_claimable;
 use crate::msg::{InstantiateMsg, MigrateMsg};
-use crate::state::{Config, Stats, CONFIG, STATS, REENTRANCY_LOCK};
+use crate::state::{BetAsset, ClockAnchor, Config, Stats, PhaseSchedule, ScheduleMode, RandomnessSource, TierPrize, FeeRecipient, LotteryPhase, LotterySession, LotteryResult, Participant, PrizeStructure, PrizeTier, TicketPrices, TierSummary, NonRevealOutcome, Winner, CONFIG, STATS, REENTRANCY_LOCK, CLOCK_ANCHOR, JACKPOT, CURRENT_SESSION, SESSION_DETAILS, LOTTERY_HISTORY, SETTLED_SESSIONS, WINNINGS, ACCRUED_FEES, UNCLAIMED_PRIZES, PENDING_REFUNDS, REFERRAL_PAYABLE, DEFAULT_CLAIM_EXPIRY_BLOCKS, participants};
 
 // 版本信息
 const CONTRACT_NAME: &str = "dd-3d-lottery";
@@ -32,20 +35,124 @@ pub fn instantiate(
         return Err(ContractError::invalid_bet_amount(msg.min_bet_amount));
     }
 
-    // 验证代币类型
-    if msg.bet_denom.is_empty() {
-        return Err(ContractError::InvalidBetDenom);
+    // 验证投注资产
+    let bet_asset = match msg.cw20_token {
+        Some(token) => BetAsset::Cw20 { address: deps.api.addr_validate(&token)? },
+        None => {
+            if msg.bet_denom.is_empty() {
+                return Err(ContractError::InvalidBetDenom);
+            }
+            BetAsset::Native { denom: msg.bet_denom }
+        }
+    };
+
+    // 验证奖金领取有效期
+    let claim_expiry_blocks = msg.claim_expiry_blocks.unwrap_or(DEFAULT_CLAIM_EXPIRY_BLOCKS);
+    if claim_expiry_blocks == 0 {
+        return Err(ContractError::InvalidConfiguration);
+    }
+
+    // 验证阶段时间表，长度字段的单位必须与调度模式一致
+    let schedule = match msg.schedule_mode.unwrap_or(ScheduleMode::BlockHeight) {
+        ScheduleMode::BlockHeight => {
+            if msg.commitment_seconds.is_some()
+                || msg.reveal_seconds.is_some()
+                || msg.settlement_seconds.is_some()
+                || msg.genesis_time.is_some()
+            {
+                return Err(ContractError::InvalidConfiguration);
+            }
+            let default_schedule = PhaseSchedule::default();
+            PhaseSchedule {
+                genesis: msg.genesis_height.unwrap_or(default_schedule.genesis),
+                commitment_length: msg.commitment_blocks.unwrap_or(default_schedule.commitment_length),
+                reveal_length: msg.reveal_blocks.unwrap_or(default_schedule.reveal_length),
+                settlement_length: msg.settlement_blocks.unwrap_or(default_schedule.settlement_length),
+                ..default_schedule
+            }
+        }
+        ScheduleMode::Timestamp => {
+            if msg.commitment_blocks.is_some()
+                || msg.reveal_blocks.is_some()
+                || msg.settlement_blocks.is_some()
+                || msg.genesis_height.is_some()
+            {
+                return Err(ContractError::InvalidConfiguration);
+            }
+            let default_schedule = PhaseSchedule::daily(env.block.time.seconds());
+            PhaseSchedule {
+                genesis: msg.genesis_time.unwrap_or(default_schedule.genesis),
+                commitment_length: msg.commitment_seconds.unwrap_or(default_schedule.commitment_length),
+                reveal_length: msg.reveal_seconds.unwrap_or(default_schedule.reveal_length),
+                settlement_length: msg.settlement_seconds.unwrap_or(default_schedule.settlement_length),
+                ..default_schedule
+            }
+        }
+    };
+    if !schedule.is_valid() {
+        return Err(ContractError::InvalidConfiguration);
+    }
+
+    // 验证未揭秘处理策略
+    let non_reveal_policy = msg.non_reveal_policy.unwrap_or_default();
+    if !non_reveal_policy.is_valid() {
+        return Err(ContractError::InvalidConfiguration);
+    }
+
+    // 验证随机数来源
+    let randomness_source = msg.randomness_source.unwrap_or_default();
+    validate_randomness_source(deps.as_ref(), &randomness_source)?;
+
+    // 验证奖金结构
+    let prize_structure = msg.prize_structure.unwrap_or_default();
+    if !prize_structure.is_valid() {
+        return Err(ContractError::InvalidConfiguration);
+    }
+
+    // 验证奖级配置
+    let prize_tiers = msg.prize_tiers.unwrap_or_else(TierPrize::default_tiers);
+    if !TierPrize::validate_all(&prize_tiers) {
+        return Err(ContractError::InvalidConfiguration);
+    }
+
+    // 验证投注类型单价
+    let ticket_prices = msg.ticket_prices.unwrap_or_default();
+    if !ticket_prices.is_valid() {
+        return Err(ContractError::InvalidConfiguration);
+    }
+
+    // 验证服务费分成表
+    let fee_recipients = msg.fee_recipients.unwrap_or_default();
+    validate_fee_recipients(deps.as_ref(), &fee_recipients)?;
+
+    // 验证推荐奖励比例
+    let referral_fee_share = msg.referral_fee_share.unwrap_or_default();
+    if referral_fee_share > Decimal::one() {
+        return Err(ContractError::InvalidConfiguration);
     }
 
     // 创建配置
     let config = Config {
-        admin,
+        admin: Some(admin.clone()),
         service_fee_rate: msg.service_fee_rate,
         min_bet_amount: msg.min_bet_amount,
         max_bet_amount: msg.max_bet_amount,
-        bet_denom: msg.bet_denom,
+        bet_asset,
         paused: false,
         pause_requested: msg.pause_requested.unwrap_or(false),
+        claim_expiry_blocks,
+        schedule,
+        pending_schedule: None,
+        non_reveal_policy,
+        commitment_deposit: msg.commitment_deposit.unwrap_or_default(),
+        randomness_source,
+        prize_structure,
+        prize_tiers,
+        ticket_prices,
+        extra_funds_policy: msg.extra_funds_policy.unwrap_or_default(),
+        fee_recipients,
+        fee_distribution: msg.fee_distribution.unwrap_or_default(),
+        referral_fee_share,
     };
 
     CONFIG.save(deps.storage, &config)?;
@@ -65,42 +172,427 @@ pub fn instantiate(
     // 初始化防重入锁
     REENTRANCY_LOCK.save(deps.storage, &false)?;
 
+    // 记录区块时钟锚点，用于估算平均出块时间
+    CLOCK_ANCHOR.save(deps.storage, &ClockAnchor::new(&env.block))?;
+
+    // 初始化累积奖池
+    JACKPOT.save(deps.storage, &Uint128::zero())?;
+
+    // 初始化资金台账
+    ACCRUED_FEES.save(deps.storage, &Uint128::zero())?;
+    UNCLAIMED_PRIZES.save(deps.storage, &Uint128::zero())?;
+    PENDING_REFUNDS.save(deps.storage, &Uint128::zero())?;
+    REFERRAL_PAYABLE.save(deps.storage, &Uint128::zero())?;
+
     Ok(Response::new()
         .add_attribute("method", "instantiate")
-        .add_attribute("admin", config.admin)
+        .add_attribute("admin", admin)
         .add_attribute("service_fee_rate", config.service_fee_rate.to_string())
         .add_attribute("min_bet_amount", config.min_bet_amount.to_string())
         .add_attribute("max_bet_amount", config.max_bet_amount.to_string()))
 }
 
+/// 验证随机数来源：信标代理合约地址必须有效且轮次间隔大于0
+pub(crate) fn validate_randomness_source(
+    deps: Deps,
+    source: &RandomnessSource,
+) -> Result<(), ContractError> {
+    if let Some(beacon) = source.beacon() {
+        deps.api.addr_validate(beacon.address.as_str())?;
+        if !beacon.is_valid() {
+            return Err(ContractError::InvalidConfiguration);
+        }
+    }
+    Ok(())
+}
+
+/// 验证服务费分成表：收款地址必须有效，收款人不重复且权重大于0
+pub(crate) fn validate_fee_recipients(
+    deps: Deps,
+    recipients: &[FeeRecipient],
+) -> Result<(), ContractError> {
+    for recipient in recipients {
+        deps.api.addr_validate(recipient.address.as_str())?;
+    }
+    if !FeeRecipient::validate_all(recipients) {
+        return Err(ContractError::InvalidConfiguration);
+    }
+    Ok(())
+}
+
 /// 合约迁移
 /// 
-/// 注意：当前系统是初始化版本，暂时不需要处理复杂的迁移逻辑
-/// 此函数仅提供基本的版本更新和管理员更新功能
+/// 迁移权限由链上的合约管理员控制；通过 cw2 版本检查拒绝其他合约的迁移和版本降级，
+/// 并按存储的版本依次执行状态迁移
 pub fn migrate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     msg: MigrateMsg,
 ) -> Result<Response, ContractError> {
-    // 检查管理员权限
-    let config = CONFIG.load(deps.storage)?;
-    if env.contract.address != config.admin {
-        return Err(ContractError::Unauthorized);
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::ContractUpgradeNotAllowed);
+    }
+    let from_version = parse_version(&stored.version)?;
+    if from_version > parse_version(CONTRACT_VERSION)? {
+        return Err(ContractError::ContractUpgradeNotAllowed);
+    }
+
+    let mut response = Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("contract_name", CONTRACT_NAME)
+        .add_attribute("from_version", stored.version)
+        .add_attribute("contract_version", CONTRACT_VERSION);
+
+    if from_version < (0, 2, 0) {
+        let legacy = migrate_from_v0_1(deps.branch(), &env)?;
+        response = response.add_attributes(legacy.attributes);
+    }
+    if LEGACY_RESULTS_CURSOR.exists(deps.storage) {
+        let legacy = migrate_legacy_results(deps.branch(), &env, msg.limit)?;
+        response = response.add_attributes(legacy.attributes);
+    }
+    if !CLOCK_ANCHOR.exists(deps.storage) {
+        CLOCK_ANCHOR.save(deps.storage, &ClockAnchor::new(&env.block))?;
     }
 
     // 更新版本
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    // 更新管理员（如果提供）
-    if let Some(new_admin) = msg.new_admin {
-        let admin = deps.api.addr_validate(&new_admin)?;
-        let mut new_config = config;
-        new_config.admin = admin;
-        CONFIG.save(deps.storage, &new_config)?;
+    Ok(response)
+}
+
+/// 解析 `主版本.次版本.修订号` 形式的版本号，忽略预发布和构建后缀
+fn parse_version(version: &str) -> Result<(u64, u64, u64), ContractError> {
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    let parts = core.split('.')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ContractError::ContractUpgradeNotAllowed)?;
+    match parts[..] {
+        [major, minor, patch] => Ok((major, minor, patch)),
+        _ => Err(ContractError::ContractUpgradeNotAllowed),
     }
+}
 
-    Ok(Response::new()
-        .add_attribute("method", "migrate")
-        .add_attribute("contract_name", CONTRACT_NAME)
-        .add_attribute("contract_version", CONTRACT_VERSION))
-}
\ No newline at end of file
+/// 0.1.x 版本的系统配置
+#[derive(Serialize, Deserialize)]
+struct LegacyConfig {
+    admin: Addr,
+    service_fee_rate: Decimal,
+    min_bet_amount: Uint128,
+    max_bet_amount: Uint128,
+    bet_denom: String,
+    paused: bool,
+    pause_requested: bool,
+}
+
+/// 0.1.x 版本的当前会话，只读取迁移需要的字段
+#[derive(Serialize, Deserialize)]
+struct LegacySession {
+    session_id: String,
+    total_pool: Uint128,
+    participants: Vec<LegacyParticipant>,
+    created_height: u64,
+    settled: bool,
+}
+
+/// 0.1.x 版本的参与者，只读取退款需要的字段
+#[derive(Serialize, Deserialize)]
+struct LegacyParticipant {
+    address: Addr,
+    bet_amount: Uint128,
+    bet_time: Timestamp,
+}
+
+/// 0.1.x 版本的开奖记录，只读取转换需要的字段
+#[derive(Serialize, Deserialize)]
+struct LegacyResult {
+    session_id: String,
+    winning_number: u16,
+    total_pool: Uint128,
+    service_fee: Uint128,
+    reward_pool: Uint128,
+    winners: Vec<LegacyWinner>,
+    settled_at: Timestamp,
+    settled_height: u64,
+}
+
+/// 0.1.x 版本的中奖记录，每个中奖号码一条，均为直选
+#[derive(Serialize, Deserialize)]
+struct LegacyWinner {
+    address: Addr,
+    reward_amount: Uint128,
+}
+
+/// 0.1.x 版本按区块高度划分周期的周期长度
+const LEGACY_CYCLE_BLOCKS: u64 = 10000;
+
+/// 0.1.x 版本的会话ID前缀
+const LEGACY_SESSION_PREFIX: &str = "global_session_";
+
+/// 单次迁移默认和最多转换的开奖记录数
+const DEFAULT_LEGACY_RESULTS_LIMIT: u32 = 100;
+const MAX_LEGACY_RESULTS_LIMIT: u32 = 1000;
+
+const LEGACY_CONFIG: Item<LegacyConfig> = Item::new("config");
+const LEGACY_SESSION: Item<LegacySession> = Item::new("current_session");
+const LEGACY_HISTORY: Map<String, LegacyResult> = Map::new("lottery_history");
+
+/// 开奖记录转换进度：存在时尚未转换完，值为最后转换的会话ID
+const LEGACY_RESULTS_CURSOR: Item<Option<String>> = Item::new("legacy_results_cursor");
+
+/// 从 0.1.x 迁移
+/// 
+/// 配置转换为当前结构，新增配置项使用默认值；0.1.x 的会话结构不兼容，
+/// 迁移时移除当前会话（未结算的会话取消，投注转为待退款）；初始化资金台账。
+/// 开奖记录由 `migrate_legacy_results` 分批转换，已结算会话数随转换重新计数。返回记录迁移结果的属性
+fn migrate_from_v0_1(deps: DepsMut, env: &Env) -> Result<Response, ContractError> {
+    let mut response = Response::new();
+    let storage = deps.storage;
+    if CONFIG.load(storage).is_err() {
+        let legacy = LEGACY_CONFIG.load(storage)?;
+        let config = Config {
+            admin: Some(legacy.admin),
+            service_fee_rate: legacy.service_fee_rate,
+            min_bet_amount: legacy.min_bet_amount,
+            max_bet_amount: legacy.max_bet_amount,
+            bet_asset: BetAsset::Native { denom: legacy.bet_denom },
+            paused: legacy.paused,
+            pause_requested: legacy.pause_requested,
+            claim_expiry_blocks: DEFAULT_CLAIM_EXPIRY_BLOCKS,
+            schedule: PhaseSchedule::default(),
+            pending_schedule: None,
+            non_reveal_policy: Default::default(),
+            commitment_deposit: Uint128::zero(),
+            randomness_source: RandomnessSource::default(),
+            prize_structure: Default::default(),
+            prize_tiers: TierPrize::default_tiers(),
+            ticket_prices: Default::default(),
+            extra_funds_policy: Default::default(),
+            fee_recipients: vec![],
+            fee_distribution: Default::default(),
+            referral_fee_share: Decimal::zero(),
+        };
+        CONFIG.save(storage, &config)?;
+    }
+
+    if CURRENT_SESSION.may_load(storage).is_err() {
+        // 旧版承诺存放在旧命名空间，不再读取
+        let legacy = LEGACY_SESSION.load(storage)?;
+        CURRENT_SESSION.remove(storage);
+        if !legacy.settled {
+            let config = CONFIG.load(storage)?;
+            let refunds = cancel_legacy_session(storage, &config, legacy)?;
+            response = response.add_attribute("legacy_refunds", refunds.to_string());
+        }
+    }
+
+    // 已结算会话数与彩票历史的分页索引保持一致，转换开奖记录时重新计数
+    let mut stats = STATS.load(storage)?;
+    stats.total_sessions = 0;
+    stats.last_updated = env.block.time;
+    STATS.save(storage, &stats)?;
+
+    for ledger in LEDGERS {
+        if !ledger.exists(storage) {
+            ledger.save(storage, &Uint128::zero())?;
+        }
+    }
+    LEGACY_RESULTS_CURSOR.save(storage, &None)?;
+    Ok(response)
+}
+
+/// 资金台账，合约余额中台账之外的部分为 0.1.x 遗留的服务费
+const LEDGERS: [&Item<Uint128>; 5] = [&JACKPOT, &ACCRUED_FEES, &UNCLAIMED_PRIZES, &PENDING_REFUNDS, &REFERRAL_PAYABLE];
+
+/// 分批转换 0.1.x 的开奖记录
+/// 
+/// 按会话ID顺序每次最多转换 `limit` 条，开奖记录按当前结构原地保存并加入历史索引。
+/// 0.1.x 结算时不发放奖金，中奖者的奖金按地址合并为直选中奖记录并记入待领取余额，
+/// 领取截止高度从转换时起算。全部转换后，台账之外的余额计入已计提服务费，
+/// 之后按台账通过 `WithdrawServiceFee` 提取
+fn migrate_legacy_results(
+    deps: DepsMut,
+    env: &Env,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LEGACY_RESULTS_LIMIT).clamp(1, MAX_LEGACY_RESULTS_LIMIT) as usize;
+    let config = CONFIG.load(deps.storage)?;
+    let start = match LEGACY_RESULTS_CURSOR.load(deps.storage)? {
+        Some(cursor) => Bound::exclusive(cursor),
+        None => Bound::inclusive(LEGACY_SESSION_PREFIX.to_string()),
+    };
+    // 多读取一条，判断是否还有未转换的记录
+    let mut session_ids = LEGACY_HISTORY
+        .keys(deps.storage, Some(start), None, Order::Ascending)
+        .take_while(|key| key.as_ref().map_or(true, |key| key.starts_with(LEGACY_SESSION_PREFIX)))
+        .take(limit + 1)
+        .collect::<StdResult<Vec<String>>>()?;
+    let has_more = session_ids.len() > limit;
+    session_ids.truncate(limit);
+
+    let mut stats = STATS.load(deps.storage)?;
+    let mut prizes = Uint128::zero();
+    for session_id in &session_ids {
+        let legacy = LEGACY_HISTORY.load(deps.storage, session_id.clone())?;
+        let total_pending = convert_legacy_result(deps.storage, env, &config, legacy)?;
+        prizes += total_pending;
+        stats.total_sessions += 1;
+    }
+    STATS.save(deps.storage, &stats)?;
+    let unclaimed = UNCLAIMED_PRIZES.load(deps.storage)?;
+    UNCLAIMED_PRIZES.save(deps.storage, &(unclaimed + prizes))?;
+
+    let response = Response::new()
+        .add_attribute("legacy_results", session_ids.len().to_string())
+        .add_attribute("legacy_prizes", prizes.to_string());
+    if has_more {
+        LEGACY_RESULTS_CURSOR.save(deps.storage, &session_ids.last().cloned())?;
+        return Ok(response.add_attribute("legacy_migration", "in_progress"));
+    }
+
+    LEGACY_RESULTS_CURSOR.remove(deps.storage);
+    let mut tracked = Uint128::zero();
+    for ledger in LEDGERS {
+        tracked += ledger.load(deps.storage)?;
+    }
+    let balance = config.bet_asset.query_balance(&deps.querier, &env.contract.address)?;
+    let legacy_fees = balance.saturating_sub(tracked);
+    let accrued = ACCRUED_FEES.load(deps.storage)?;
+    ACCRUED_FEES.save(deps.storage, &(accrued + legacy_fees))?;
+    Ok(response
+        .add_attribute("legacy_fees", legacy_fees.to_string())
+        .add_attribute("legacy_migration", "completed"))
+}
+
+/// 转换一条 0.1.x 开奖记录，返回记入待领取余额的奖金总额
+fn convert_legacy_result(
+    storage: &mut dyn Storage,
+    env: &Env,
+    config: &Config,
+    legacy: LegacyResult,
+) -> Result<Uint128, ContractError> {
+    let level = PrizeTier::Straight.level();
+    let mut summary = TierSummary::new(PrizeTier::Straight);
+    let mut winners_count = 0;
+    for winner in &legacy.winners {
+        let key = (legacy.session_id.as_str(), &winner.address, level);
+        let record = match WINNINGS.may_load(storage, key)? {
+            Some(mut record) => {
+                record.count += 1;
+                record.stake += Uint128::one();
+                record.reward_amount += winner.reward_amount;
+                record
+            }
+            None => {
+                winners_count += 1;
+                Winner {
+                    address: winner.address.clone(),
+                    level,
+                    count: 1,
+                    stake: Uint128::one(),
+                    reward_amount: winner.reward_amount,
+                }
+            }
+        };
+        WINNINGS.save(storage, key, &record)?;
+        record_claimable(storage, &winner.address, &legacy.session_id, winner.reward_amount)?;
+        summary.count += 1;
+        summary.stake += Uint128::one();
+        summary.distributed += winner.reward_amount;
+    }
+    summary.budget = summary.distributed;
+
+    let cycle = legacy.settled_height / LEGACY_CYCLE_BLOCKS;
+    let result = LotteryResult {
+        session_id: legacy.session_id.clone(),
+        cycle,
+        winning_number: legacy.winning_number,
+        total_pool: legacy.total_pool,
+        service_fee: legacy.service_fee,
+        reward_pool: legacy.reward_pool,
+        jackpot_in: Uint128::zero(),
+        jackpot_out: Uint128::zero(),
+        tiers: vec![summary.clone()],
+        winners_count,
+        settled_at: legacy.settled_at,
+        settled_height: legacy.settled_height,
+        total_paid: Uint128::zero(),
+        total_pending: summary.distributed,
+        total_expired: Uint128::zero(),
+        claim_deadline_height: env.block.height.saturating_add(config.claim_expiry_blocks),
+        non_reveal: NonRevealOutcome {
+            policy: config.non_reveal_policy.clone(),
+            non_revealed_count: 0,
+            non_revealed_amount: Uint128::zero(),
+            forfeited_to_pool: Uint128::zero(),
+            forfeited_to_treasury: Uint128::zero(),
+            refunded: Uint128::zero(),
+            slashed_deposits: Uint128::zero(),
+        },
+        post_reveal_entropy: String::new(),
+        randomness_source: RandomnessSource::ParticipantSeeds,
+        beacon_round: None,
+        prize_structure: PrizeStructure::default(),
+        prize_tiers: TierPrize::default_tiers(),
+        ticket_prices: TicketPrices::default(),
+    };
+    LOTTERY_HISTORY.save(storage, legacy.session_id.clone(), &result)?;
+    SETTLED_SESSIONS.save(storage, cycle, &legacy.session_id)?;
+    Ok(result.total_pending)
+}
+
+/// 取消 0.1.x 未结算的会话
+/// 
+/// 会话归档为已取消的会话，参与者按投注金额写入（不含号码和种子），投注计入待退款，
+/// 投注者通过 `Refund` 取回投注。返回待退款总额
+fn cancel_legacy_session(
+    storage: &mut dyn Storage,
+    config: &Config,
+    legacy: LegacySession,
+) -> Result<Uint128, ContractError> {
+    let mut refunds = Uint128::zero();
+    for participant in &legacy.participants {
+        participants().save(storage, (legacy.session_id.as_str(), &participant.address), &Participant {
+            address: participant.address.clone(),
+            bet_amount: participant.bet_amount,
+            lucky_numbers: vec![],
+            tickets: vec![],
+            random_seed: None,
+            revealed: false,
+            commitment_hash: None,
+            bet_time: participant.bet_time,
+            reveal_time: None,
+            deposit: Uint128::zero(),
+        })?;
+        refunds += participant.bet_amount;
+    }
+
+    let session = LotterySession {
+        session_id: legacy.session_id,
+        cycle: legacy.created_height / LEGACY_CYCLE_BLOCKS,
+        phase: LotteryPhase::Settlement,
+        total_pool: legacy.total_pool,
+        service_fee: Uint128::zero(),
+        participant_count: legacy.participants.len() as u32,
+        revealed_count: 0,
+        pending_deposits: Uint128::zero(),
+        referral_fees: Uint128::zero(),
+        created_height: legacy.created_height,
+        winning_number: None,
+        settled: false,
+        non_reveal_policy: config.non_reveal_policy.clone(),
+        randomness_source: config.randomness_source.clone(),
+        prize_structure: config.prize_structure.clone(),
+        prize_tiers: config.prize_tiers.clone(),
+        ticket_prices: config.ticket_prices.clone(),
+        beacon_request: None,
+        cancelled: true,
+    };
+    SESSION_DETAILS.save(storage, session.session_id.clone(), &session)?;
+
+    let pending = PENDING_REFUNDS.may_load(storage)?.unwrap_or_default();
+    PENDING_REFUNDS.save(storage, &(pending + refunds))?;
+    Ok(refunds)
+}
@@ -45,6 +45,12 @@ pub enum ContractError {
     #[error("Insufficient funds")]
     InsufficientFunds,
 
+    #[error("Invalid referrer")]
+    InvalidReferrer,
+
+    #[error("Insufficient accrued fees: available {available}")]
+    InsufficientAccruedFees { available: Uint128 },
+
     #[error("Reentrancy detected")]
     ReentrancyDetected,
 
@@ -85,14 +91,53 @@ pub enum ContractError {
     #[error("Invalid bet denomination")]
     InvalidBetDenom,
 
+    #[error("Bet denomination cannot change while balances are outstanding")]
+    OutstandingBalances,
+
+    #[error("Unexpected funds: {denom}")]
+    UnexpectedFunds { denom: String },
+
     #[error("Lucky numbers count mismatch: expected {expected}, got {actual}")]
     LuckyNumbersCountMismatch {
         expected: u32,
         actual: u32,
     },
 
+    #[error("Nothing to claim")]
+    NothingToClaim,
+
+    #[error("Claim expired at height {deadline}")]
+    ClaimExpired { deadline: u64 },
+
+    #[error("Claim not expired until height {deadline}")]
+    ClaimNotExpired { deadline: u64 },
+
+    #[error("Insufficient commitment deposit: required {required}")]
+    InsufficientDeposit { required: Uint128 },
+
+    #[error("Beacon randomness for round {round} not received yet")]
+    RandomnessNotReady { round: u64 },
+
+    #[error("Unexpected beacon round: expected {expected}, got {actual}")]
+    UnexpectedBeaconRound { expected: u64, actual: u64 },
+
+    #[error("Session cancelled")]
+    SessionCancelled,
+
+    #[error("Session not cancelled")]
+    SessionNotCancelled,
+
+    #[error("Already refunded")]
+    AlreadyRefunded,
+
     #[error("Contract upgrade not allowed")]
     ContractUpgradeNotAllowed,
+
+    #[error("No pending admin proposal")]
+    NoAdminProposal,
+
+    #[error("Fee recipients required before renouncing admin")]
+    FeeRecipientsRequired,
 }
 
 impl ContractError {
@@ -1,18 +1,24 @@
-use cosmwasm_std::{Uint128, Decimal, DepsMut, Env, MessageInfo, Response, StdError, Fraction};
-use cw2::set_contract_version;
+use cosmwasm_std::{Uint128, Decimal, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Fraction, BankMsg, Coin, Addr, Order, Storage, HexBinary, WasmMsg, CosmosMsg, to_json_binary, from_json};
+use cw20::Cw20ReceiveMsg;
 use std::str::FromStr;
 
 use crate::error::ContractError;
-use crate::msg::{InstantiateMsg, ExecuteMsg};
-use crate::state::{Config, LotteryPhase, Participant, LotteryResult, Commitment, Stats, LotterySession, CONFIG, CURRENT_SESSION, COMMITMENTS, LOTTERY_HISTORY, STATS, REENTRANCY_LOCK};
+use crate::msg::{InstantiateMsg, ExecuteMsg, BeaconExecuteMsg, Cw20HookMsg};
+use crate::state::{LotteryPhase, PhaseSchedule, ScheduleMode, RandomnessSource, BeaconRequest, TierPrize, Ticket, Participant, LotteryResult, Commitment, LotterySession, CONFIG, CURRENT_SESSION, SESSION_DETAILS, COMMITMENTS, LOTTERY_HISTORY, STATS, REENTRANCY_LOCK, CLAIMABLE, JACKPOT, REFUNDS, ACCRUED_FEES, UNCLAIMED_PRIZES, PENDING_REFUNDS, REFERRAL_EARNINGS, REFERRAL_PAYABLE, REFERRER_STATS, PENDING_ADMIN, load_session, session_id_for_cycle, participants, SettlementProgress, SettlementStage, NonRevealOutcome, TierSummary, Winner, WINNINGS, PRIZES, Config, SETTLEMENT_PROGRESS, SETTLED_SESSIONS, USER_SESSIONS, BetAsset, ExtraFundsPolicy, FeeRecipient, FeeDistribution};
+use cw_storage_plus::{Bound, Item};
 use crate::phase_manager::PhaseManager;
 use crate::lottery_logic::LotteryLogic;
 use crate::reward_system::RewardSystem;
 
 
-// 版本信息
-const CONTRACT_NAME: &str = "dd-3d-lottery";
-const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// 一键领取时单笔交易最多处理的会话数量
+const MAX_CLAIMS_PER_TX: usize = 30;
+
+/// 结算时单笔交易默认处理的记录数（参与者或号码索引记录）
+const DEFAULT_SETTLEMENT_LIMIT: u32 = 100;
+
+/// 结算时单笔交易最多处理的记录数
+const MAX_SETTLEMENT_LIMIT: u32 = 1000;
 
 /// 检查会话阶段兼容性
 /// 确定会话的当前阶段是否与区块链的当前阶段兼容
@@ -34,58 +40,10 @@ fn is_session_phase_compatible(session_phase: &LotteryPhase, current_phase: &Lot
 pub fn instantiate(
     deps: DepsMut,
     env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
-    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-
-    // 验证管理员地址
-    let admin = deps.api.addr_validate(&msg.admin)?;
-
-    // 验证服务费率
-    if msg.service_fee_rate > Decimal::from_str("1.0").map_err(|_| StdError::generic_err("Invalid decimal"))? {
-        return Err(ContractError::invalid_service_fee_rate(msg.service_fee_rate));
-    }
-
-    // 验证投注金额范围
-    if msg.min_bet_amount >= msg.max_bet_amount {
-        return Err(ContractError::invalid_bet_amount(msg.min_bet_amount));
-    }
-
-    // 创建配置
-    let config = Config {
-        admin,
-        service_fee_rate: msg.service_fee_rate,
-        min_bet_amount: msg.min_bet_amount,
-        max_bet_amount: msg.max_bet_amount,
-        bet_denom: msg.bet_denom,
-        paused: false,
-        pause_requested: msg.pause_requested.unwrap_or(false),
-    };
-
-    CONFIG.save(deps.storage, &config)?;
-
-    // 初始化统计信息
-    let stats = Stats {
-        total_sessions: 0,
-        total_participants: 0,
-        total_pool: Uint128::zero(),
-        total_service_fee: Uint128::zero(),
-        total_rewards: Uint128::zero(),
-        last_updated: env.block.time,
-    };
-
-    STATS.save(deps.storage, &stats)?;
-
-    // 初始化防重入锁
-    REENTRANCY_LOCK.save(deps.storage, &false)?;
-
-    Ok(Response::new()
-        .add_attribute("method", "instantiate")
-        .add_attribute("admin", config.admin)
-        .add_attribute("service_fee_rate", config.service_fee_rate.to_string())
-        .add_attribute("min_bet_amount", config.min_bet_amount.to_string())
-        .add_attribute("max_bet_amount", config.max_bet_amount.to_string()))
+    crate::contract::instantiate(deps, env, info, msg)
 }
 
 /// 合约执行
@@ -96,17 +54,35 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::PlaceBet { commitment_hash } => {
-            execute_place_bet(deps, env, info, commitment_hash)
+        ExecuteMsg::PlaceBet { commitment_hash, referrer } => {
+            let (paid_amount, extra_funds) = native_payment(deps.as_ref(), &info)?;
+            let referrer = validate_referrer(deps.as_ref(), &env, &info.sender, referrer)?;
+            let response = execute_place_bet(deps, env, info.sender.clone(), paid_amount, commitment_hash, referrer)?;
+            Ok(refund_extra_funds(response, &info.sender, extra_funds))
+        }
+        ExecuteMsg::Receive(wrapper) => {
+            execute_receive(deps, env, info, wrapper)
+        }
+        ExecuteMsg::RevealRandom { lucky_numbers, random_seed, tickets, lucky_number_counts } => {
+            execute_reveal_random(deps, env, info, lucky_numbers, lucky_number_counts, tickets, random_seed)
+        }
+        ExecuteMsg::SettleLottery { limit } => {
+            execute_settle_lottery(deps, env, info, limit)
+        }
+        ExecuteMsg::ClaimReward { session_id } => {
+            execute_claim_reward(deps, env, info, session_id)
         }
-        ExecuteMsg::RevealRandom { lucky_numbers, random_seed } => {
-            execute_reveal_random(deps, env, info, lucky_numbers, random_seed)
+        ExecuteMsg::ExpireRewards { session_id } => {
+            execute_expire_rewards(deps, env, info, session_id)
         }
-        ExecuteMsg::SettleLottery {} => {
-            execute_settle_lottery(deps, env, info)
+        ExecuteMsg::Refund { session_id } => {
+            execute_refund(deps, env, info, session_id)
         }
-        ExecuteMsg::UpdateConfig { service_fee_rate, min_bet_amount, max_bet_amount, bet_denom, pause_requested } => {
-            execute_update_config(deps, env, info, service_fee_rate, min_bet_amount, max_bet_amount, bet_denom, pause_requested)
+        ExecuteMsg::BeaconCallback { job_id, round, randomness } => {
+            execute_beacon_callback(deps, env, info, job_id, round, randomness)
+        }
+        msg @ ExecuteMsg::UpdateConfig { .. } => {
+            execute_update_config(deps, env, info, msg)
         }
         ExecuteMsg::EmergencyPause { paused } => {
             execute_emergency_pause(deps, env, info, paused)
@@ -114,6 +90,102 @@ pub fn execute(
         ExecuteMsg::WithdrawServiceFee { amount } => {
             execute_withdraw_service_fee(deps, env, info, amount)
         }
+        ExecuteMsg::ClaimReferralRewards {} => {
+            execute_claim_referral_rewards(deps, env, info)
+        }
+        ExecuteMsg::ProposeAdmin { new_admin } => {
+            execute_propose_admin(deps, env, info, new_admin)
+        }
+        ExecuteMsg::AcceptAdmin {} => {
+            execute_accept_admin(deps, env, info)
+        }
+        ExecuteMsg::CancelAdminProposal {} => {
+            execute_cancel_admin_proposal(deps, env, info)
+        }
+        ExecuteMsg::RenounceAdmin {} => {
+            execute_renounce_admin(deps, env, info)
+        }
+    }
+}
+
+/// 原生代币投注的支付金额和需要退还的多余代币
+/// 投注资产为 CW20 时不接受原生代币投注，需通过代币合约的 Send 调用
+/// 附带其他面额的代币时按配置的策略拒绝投注或退还
+fn native_payment(deps: Deps, info: &MessageInfo) -> Result<(Uint128, Vec<Coin>), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let BetAsset::Native { denom } = &config.bet_asset else {
+        return Err(ContractError::InvalidBetDenom);
+    };
+
+    let mut paid_amount = Uint128::zero();
+    let mut extra_funds = vec![];
+    for coin in &info.funds {
+        if &coin.denom == denom {
+            paid_amount += coin.amount;
+        } else if config.extra_funds_policy == ExtraFundsPolicy::Reject {
+            return Err(ContractError::UnexpectedFunds { denom: coin.denom.clone() });
+        } else {
+            extra_funds.push(coin.clone());
+        }
+    }
+    Ok((paid_amount, extra_funds))
+}
+
+/// 在响应中退还投注时附带的多余代币
+fn refund_extra_funds(response: Response, recipient: &Addr, extra_funds: Vec<Coin>) -> Response {
+    if extra_funds.is_empty() {
+        return response;
+    }
+    let refunded = extra_funds.iter()
+        .map(|coin| coin.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    response
+        .add_message(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: extra_funds,
+        })
+        .add_attribute("refunded_funds", refunded)
+}
+
+/// 验证推荐人：地址必须有效，不能是投注人自己或合约本身
+fn validate_referrer(
+    deps: Deps,
+    env: &Env,
+    bettor: &Addr,
+    referrer: Option<String>,
+) -> Result<Option<Addr>, ContractError> {
+    let Some(referrer) = referrer else {
+        return Ok(None);
+    };
+    let referrer = deps.api.addr_validate(&referrer)
+        .map_err(|_| ContractError::InvalidReferrer)?;
+    if referrer == *bettor || referrer == env.contract.address {
+        return Err(ContractError::InvalidReferrer);
+    }
+    Ok(Some(referrer))
+}
+
+/// CW20 代币投注入口
+/// 只接受配置的投注代币合约转入，投注人为代币的发送方
+fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    match &config.bet_asset {
+        BetAsset::Cw20 { address } if *address == info.sender => {}
+        _ => return Err(ContractError::InvalidBetDenom),
+    }
+
+    let bettor = deps.api.addr_validate(&wrapper.sender)?;
+    match from_json(&wrapper.msg)? {
+        Cw20HookMsg::PlaceBet { commitment_hash, referrer } => {
+            let referrer = validate_referrer(deps.as_ref(), &env, &bettor, referrer)?;
+            execute_place_bet(deps, env, bettor, wrapper.amount, commitment_hash, referrer)
+        }
     }
 }
 
@@ -121,8 +193,10 @@ pub fn execute(
 fn execute_place_bet(
     deps: DepsMut,
     env: Env,
-    info: MessageInfo,
+    bettor: Addr,
+    paid_amount: Uint128,
     commitment_hash: String,
+    referrer: Option<Addr>,
 ) -> Result<Response, ContractError> {
     // 检查防重入
     if REENTRANCY_LOCK.load(deps.storage)? {
@@ -132,15 +206,15 @@ fn execute_place_bet(
     // 设置重入锁
     REENTRANCY_LOCK.save(deps.storage, &true)?;
 
+    let config = CONFIG.load(deps.storage)?;
+
     // 检查当前阶段
-    let current_phase = LotteryPhase::from_block_height(env.block.height);
+    let current_phase = PhaseManager::get_current_phase(&config, &env);
     if current_phase != LotteryPhase::Commitment {
         // 释放重入锁
         REENTRANCY_LOCK.save(deps.storage, &false)?;
         return Err(ContractError::invalid_phase("commitment", current_phase.name()));
     }
-
-    let config = CONFIG.load(deps.storage)?;
     
     // 检查暂停状态
     if config.paused {
@@ -150,17 +224,21 @@ fn execute_place_bet(
     }
     
     // 检查是否请求暂停且当前是新周期
-    if config.pause_requested && PhaseManager::is_new_commitment_phase(&env) {
+    if config.pause_requested && PhaseManager::is_new_commitment_phase(&config, &env) {
         // 释放重入锁
         REENTRANCY_LOCK.save(deps.storage, &false)?;
         return Err(ContractError::ContractPaused);
     }
 
-    // 验证投注金额
-    let bet_amount = info.funds.iter()
-        .find(|coin| coin.denom == config.bet_denom)
-        .map(|coin| coin.amount)
-        .unwrap_or_default();
+    // 验证投注金额（转账金额扣除承诺押金）
+    let bet_amount = match paid_amount.checked_sub(config.commitment_deposit) {
+        Ok(amount) => amount,
+        Err(_) => {
+            // 释放重入锁
+            REENTRANCY_LOCK.save(deps.storage, &false)?;
+            return Err(ContractError::InsufficientDeposit { required: config.commitment_deposit });
+        }
+    };
 
     if bet_amount < config.min_bet_amount || bet_amount > config.max_bet_amount {
         // 释放重入锁
@@ -168,33 +246,36 @@ fn execute_place_bet(
         return Err(ContractError::invalid_bet_amount(bet_amount));
     }
 
+    // 获取当前会话（新周期开始时先归档上一期会话并重置承诺）
+    let cycle = PhaseManager::get_current_cycle(&config, &env);
+    let current_session = rollover_session(deps.storage, cycle)?;
+    let session_id = current_session.as_ref()
+        .map(|session| session.session_id.clone())
+        .unwrap_or_else(|| session_id_for_cycle(cycle));
+
     // 检查参与者是否已存在
-    if COMMITMENTS.has(deps.storage, &info.sender) {
+    if COMMITMENTS.has(deps.storage, (session_id.as_str(), &bettor)) {
         // 释放重入锁
         REENTRANCY_LOCK.save(deps.storage, &false)?;
         return Err(ContractError::ParticipantAlreadyExists);
     }
 
-    // 获取当前会话（不限制参与者数量）
-    let current_session = CURRENT_SESSION.may_load(deps.storage)?;
-
     // 使用新的预验证机制验证承诺一致性
     LotteryLogic::validate_commitment_consistency(bet_amount.u128(), &commitment_hash)
-        .map_err(|e| {
+        .inspect_err(|_| {
             // 释放重入锁
             REENTRANCY_LOCK.save(deps.storage, &false).ok();
-            e
         })?;
 
     // 保存承诺（只保存哈希，不保存原始数据）
     let commitment = Commitment {
-        participant: info.sender.clone(),
+        participant: bettor.clone(),
         commitment_hash: commitment_hash.clone(),
         bet_amount,
         submitted_at: env.block.time,
     };
 
-    COMMITMENTS.save(deps.storage, &info.sender, &commitment)?;
+    COMMITMENTS.save(deps.storage, (session_id.as_str(), &bettor), &commitment)?;
     
     // 验证当前阶段必须是承诺阶段才能创建会话
     if current_phase != LotteryPhase::Commitment {
@@ -238,36 +319,75 @@ fn execute_place_bet(
             }
             
             LotterySession {
-                session_id: format!("global_session_{}", env.block.height),
+                session_id,
+                cycle,
                 phase: current_phase.clone(),
                 total_pool: Uint128::zero(),
                 service_fee: Uint128::zero(),
-                participants: vec![],
+                participant_count: 0,
+                revealed_count: 0,
+                pending_deposits: Uint128::zero(),
+                referral_fees: Uint128::zero(),
                 created_height: env.block.height,
                 winning_number: None,
                 settled: false,
+                non_reveal_policy: config.non_reveal_policy.clone(),
+                randomness_source: config.randomness_source.clone(),
+                prize_structure: config.prize_structure.clone(),
+                prize_tiers: config.prize_tiers.clone(),
+                ticket_prices: config.ticket_prices.clone(),
+                beacon_request: None,
+                cancelled: false,
             }
         }
     };
 
     // 添加参与者（在承诺阶段不保存幸运数字和随机种子）
     let participant = Participant {
-        address: info.sender.clone(),
+        address: bettor.clone(),
         bet_amount,
         lucky_numbers: vec![], // 在承诺阶段不保存
+        tickets: vec![], // 在承诺阶段不保存
         random_seed: None, // 在承诺阶段不保存
         revealed: false,
         commitment_hash: Some(commitment_hash.clone()),
         bet_time: env.block.time,
         reveal_time: None,
+        deposit: config.commitment_deposit,
     };
 
-    current_session.participants.push(participant.clone());
+    participants().save(deps.storage, (current_session.session_id.as_str(), &bettor), &participant)?;
+    USER_SESSIONS.save(deps.storage, (&bettor, current_session.cycle), &current_session.session_id)?;
+    current_session.participant_count += 1;
+    current_session.pending_deposits += participant.deposit;
     current_session.total_pool += bet_amount;
     current_session.service_fee = current_session.total_pool.multiply_ratio(
         config.service_fee_rate.numerator(),
         config.service_fee_rate.denominator()
     );
+    let bet_fee = bet_amount.multiply_ratio(
+        config.service_fee_rate.numerator(),
+        config.service_fee_rate.denominator()
+    );
+
+    // 推荐奖励从该笔投注的服务费中分出，会话结算后可领取
+    let referral_fee = bet_fee.multiply_ratio(
+        config.referral_fee_share.numerator(),
+        config.referral_fee_share.denominator()
+    );
+    if let Some(referrer) = &referrer {
+        if !referral_fee.is_zero() {
+            let key = (referrer, current_session.session_id.as_str());
+            let earned = REFERRAL_EARNINGS.may_load(deps.storage, key)?.unwrap_or_default();
+            REFERRAL_EARNINGS.save(deps.storage, key, &(earned + referral_fee))?;
+            current_session.referral_fees += referral_fee;
+        }
+        let mut referrer_stats = REFERRER_STATS.may_load(deps.storage, referrer)?.unwrap_or_default();
+        referrer_stats.referred_bets += 1;
+        referrer_stats.referred_volume += bet_amount;
+        referrer_stats.earned_fees += referral_fee;
+        REFERRER_STATS.save(deps.storage, referrer, &referrer_stats)?;
+    }
 
     // 保存全局会话
     CURRENT_SESSION.save(deps.storage, &current_session)?;
@@ -276,22 +396,126 @@ fn execute_place_bet(
     let mut stats = STATS.load(deps.storage)?;
     stats.total_participants += 1;
     stats.total_pool += bet_amount;
-    stats.total_service_fee += bet_amount.multiply_ratio(
-        config.service_fee_rate.numerator(),
-        config.service_fee_rate.denominator()
-    );
     stats.last_updated = env.block.time;
     STATS.save(deps.storage, &stats)?;
 
     // 释放重入锁
     REENTRANCY_LOCK.save(deps.storage, &false)?;
 
-    Ok(Response::new()
+    let mut response = Response::new()
         .add_attribute("method", "place_bet")
-        .add_attribute("participant", info.sender)
+        .add_attribute("participant", bettor)
         .add_attribute("bet_amount", bet_amount.to_string())
         .add_attribute("commitment_hash", commitment_hash)
-        .add_attribute("phase", LotteryPhase::from_block_height(env.block.height).name()))
+        .add_attribute("phase", current_phase.name());
+    if let Some(referrer) = referrer {
+        response = response
+            .add_attribute("referrer", referrer)
+            .add_attribute("referral_fee", referral_fee.to_string());
+    }
+    Ok(response)
+}
+
+/// 取消会话
+/// 
+/// 取消的会话不收取服务费，全部投注转为待退款；未揭秘者的承诺押金罚没，并入下一次结算的奖金池。
+/// 结算中途取消时已写入的中奖记录和待领取余额作废，数量与已处理的记录数相同，
+/// 不在此处删除，由各投注者退款时（或一键领取时）删除，见 `clear_partial_settlement`
+fn cancel_session(
+    storage: &mut dyn Storage,
+    session: &mut LotterySession,
+) -> Result<(), ContractError> {
+    session.cancelled = true;
+    session.winning_number = None;
+    SETTLEMENT_PROGRESS.remove(storage);
+
+    add_to_jackpot(storage, session.pending_deposits)?;
+    credit_ledger(storage, &PENDING_REFUNDS, session.total_pool)?;
+
+    Ok(())
+}
+
+/// 删除地址在已取消会话结算中途写入的中奖记录、待领取余额和应发金额
+fn clear_partial_settlement(storage: &mut dyn Storage, session_id: &str, address: &Addr) -> StdResult<()> {
+    CLAIMABLE.remove(storage, (address, session_id));
+    PRIZES.remove(storage, (address, session_id));
+    let levels = WINNINGS
+        .prefix((session_id, address))
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<u8>>>()?;
+    for level in levels {
+        WINNINGS.remove(storage, (session_id, address, level));
+    }
+    Ok(())
+}
+
+/// 是否还有以当前投注面额记账的资金：未结算会话的投注和押金，以及各项台账余额
+fn has_outstanding_balances(storage: &dyn Storage) -> StdResult<bool> {
+    if let Some(session) = CURRENT_SESSION.may_load(storage)? {
+        if !session.settled && !session.cancelled && !(session.total_pool + session.pending_deposits).is_zero() {
+            return Ok(true);
+        }
+    }
+    for ledger in [&UNCLAIMED_PRIZES, &PENDING_REFUNDS, &JACKPOT, &ACCRUED_FEES, &REFERRAL_PAYABLE] {
+        if !ledger.may_load(storage)?.unwrap_or_default().is_zero() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// 资金滚入累积奖池，下一次结算时并入奖金池
+fn add_to_jackpot(storage: &mut dyn Storage, amount: Uint128) -> Result<(), ContractError> {
+    credit_ledger(storage, &JACKPOT, amount)
+}
+
+/// 资金台账增加
+fn credit_ledger(storage: &mut dyn Storage, ledger: &Item<Uint128>, amount: Uint128) -> Result<(), ContractError> {
+    if !amount.is_zero() {
+        let balance = ledger.may_load(storage)?.unwrap_or_default();
+        ledger.save(storage, &(balance + amount))?;
+    }
+    Ok(())
+}
+
+/// 资金台账扣减，余额不足说明账目不一致
+fn debit_ledger(storage: &mut dyn Storage, ledger: &Item<Uint128>, amount: Uint128) -> Result<(), ContractError> {
+    if !amount.is_zero() {
+        let balance = ledger.may_load(storage)?.unwrap_or_default();
+        let remaining = balance.checked_sub(amount)
+            .map_err(|_| ContractError::RewardCalculationError)?;
+        ledger.save(storage, &remaining)?;
+    }
+    Ok(())
+}
+
+/// 会话滚动
+/// 
+/// 当前会话属于之前的周期时，将其归档到 SESSION_DETAILS（未结算的会话先取消），承诺按会话存储无需清空；
+/// 返回 None 表示需要为当前周期创建新会话；否则返回当前会话
+fn rollover_session(
+    storage: &mut dyn Storage,
+    cycle: u64,
+) -> Result<Option<LotterySession>, ContractError> {
+    let session = match CURRENT_SESSION.may_load(storage)? {
+        Some(session) => session,
+        None => return Ok(None),
+    };
+
+    if session.cycle >= cycle {
+        return Ok(Some(session));
+    }
+
+    // 错过结算窗口的会话取消，投注者可以退款
+    let mut session = session;
+    if !session.settled && !session.cancelled {
+        cancel_session(storage, &mut session)?;
+    }
+
+    SESSION_DETAILS.save(storage, session.session_id.clone(), &session)?;
+    CURRENT_SESSION.remove(storage);
+
+    Ok(None)
 }
 
 /// 揭秘随机数
@@ -300,10 +524,13 @@ fn execute_reveal_random(
     env: Env,
     info: MessageInfo,
     lucky_numbers: Vec<u16>,
+    lucky_number_counts: Vec<(u16, u32)>,
+    tickets: Vec<Ticket>,
     random_seed: String,
 ) -> Result<Response, ContractError> {
     // 检查当前阶段
-    let current_phase = LotteryPhase::from_block_height(env.block.height);
+    let config = CONFIG.load(deps.storage)?;
+    let current_phase = PhaseManager::get_current_phase(&config, &env);
     if current_phase != LotteryPhase::Reveal {
         return Err(ContractError::invalid_phase("reveal", current_phase.name()));
     }
@@ -315,8 +542,16 @@ fn execute_reveal_random(
         }
     }
     
-    // 验证幸运数字数量不能为空
-    if lucky_numbers.is_empty() {
+    // 投注码列表和直方图只能二选一
+    let use_counts = !lucky_number_counts.is_empty();
+    if use_counts && !lucky_numbers.is_empty() {
+        return Err(ContractError::invalid_lucky_numbers(
+            "Provide either lucky_numbers or lucky_number_counts, not both"
+        ));
+    }
+
+    // 验证幸运数字数量不能为空（只购买投注票时可以为空）
+    if lucky_numbers.is_empty() && !use_counts && tickets.is_empty() {
         return Err(ContractError::invalid_lucky_numbers("Must have at least 1 lucky number"));
     }
     
@@ -328,133 +563,805 @@ fn execute_reveal_random(
         ));
     }
 
-    // 获取参与者承诺
-    let commitment = COMMITMENTS.load(deps.storage, &info.sender)?;
+    // 获取全局会话
+    let mut session = CURRENT_SESSION.load(deps.storage)?;
 
-    // 验证所有投注码的总数必须等于投注金额K
-    // 例如：投注1000个代币，可以获得1000个投注码，每个投注码对应一个幸运数字
-    if commitment.bet_amount.u128() != lucky_numbers.len() as u128 {
-        return Err(ContractError::invalid_lucky_numbers(
-            "Bet amount K must equal total number of betting codes (K tokens = K betting codes)"
-        ));
-    }
+    // 获取参与者在当前会话的承诺
+    let commitment = COMMITMENTS.load(deps.storage, (session.session_id.as_str(), &info.sender))?;
 
     // 验证单个幸运号码的出现次数限制（每个号码最多1000次）
     LotteryLogic::validate_lucky_number_counts(&lucky_numbers)?;
 
-    // 使用新的完整一致性验证
-    LotteryLogic::validate_commitment_full_consistency(
-        commitment.bet_amount.u128(),
-        &lucky_numbers,
-        &random_seed,
-        &commitment.commitment_hash
-    )?;
+    if use_counts {
+        // 直方图承诺，投注码总数加投注票总价须等于投注金额
+        LotteryLogic::validate_histogram_commitment(
+            commitment.bet_amount.u128(),
+            &lucky_number_counts,
+            &tickets,
+            &session.ticket_prices,
+            &random_seed,
+            &commitment.commitment_hash
+        )?;
+    } else if tickets.is_empty() {
+        // 验证所有投注码的总数必须等于投注金额K
+        // 例如：投注1000个代币，可以获得1000个投注码，每个投注码对应一个幸运数字
+        if commitment.bet_amount.u128() != lucky_numbers.len() as u128 {
+            return Err(ContractError::invalid_lucky_numbers(
+                "Bet amount K must equal total number of betting codes (K tokens = K betting codes)"
+            ));
+        }
 
-    // 获取全局会话
-    let mut session = CURRENT_SESSION.load(deps.storage)?;
+        // 使用新的完整一致性验证
+        LotteryLogic::validate_commitment_full_consistency(
+            commitment.bet_amount.u128(),
+            &lucky_numbers,
+            &random_seed,
+            &commitment.commitment_hash
+        )?;
+    } else {
+        // 投注票按本期单价计价
+        LotteryLogic::validate_ticket_commitment(
+            commitment.bet_amount.u128(),
+            &lucky_numbers,
+            &tickets,
+            &session.ticket_prices,
+            &random_seed,
+            &commitment.commitment_hash
+        )?;
+    }
     
     // 会话阶段不应该被更新，应该保持创建时的阶段
     // 只有当前阶段需要验证，会话阶段保持不变
     
+    // 只能揭秘本周期的会话
+    if session.cycle != PhaseManager::get_current_cycle(&config, &env) {
+        return Err(ContractError::SessionNotFound);
+    }
+
     // 检查会话是否已结算
     if session.settled {
         return Err(ContractError::LotteryAlreadySettled);
     }
 
-    // 更新会话中的参与者信息
-    if let Some(participant) = session.participants.iter_mut()
-        .find(|p| p.address == info.sender) {
-        participant.lucky_numbers = lucky_numbers.clone();
-        participant.random_seed = Some(random_seed.clone());
-        participant.revealed = true;
-        participant.reveal_time = Some(env.block.time);
+    // 以直方图形式保存投注码，避免大额投注者的投注码列表占用过多存储
+    let number_counts = if use_counts {
+        lucky_number_counts
     } else {
-        return Err(ContractError::ParticipantNotFound);
+        LotteryLogic::to_number_counts(&lucky_numbers)
+    };
+    let lucky_numbers_count: u128 = number_counts.iter().map(|&(_, count)| count as u128).sum();
+
+    // 更新参与者信息，号码索引随之更新
+    let key = (session.session_id.as_str(), &info.sender);
+    let mut participant = participants().may_load(deps.storage, key)?
+        .ok_or(ContractError::ParticipantNotFound)?;
+    if participant.revealed {
+        return Err(ContractError::RandomSeedAlreadyRevealed);
     }
-
+    participant.lucky_numbers = number_counts;
+    participant.tickets = tickets.clone();
+    participant.random_seed = Some(random_seed.clone());
+    participant.revealed = true;
+    participant.reveal_time = Some(env.block.time);
+    let deposit = participant.deposit;
+    participants().save(deps.storage, key, &participant)?;
+
+    session.revealed_count += 1;
+    session.pending_deposits -= deposit;
     CURRENT_SESSION.save(deps.storage, &session)?;
 
-    Ok(Response::new()
+    // 揭秘后退还承诺押金
+    let mut response = Response::new();
+    if !deposit.is_zero() {
+        response = response.add_message(config.bet_asset.transfer_msg(&info.sender, deposit)?);
+    }
+
+    Ok(response
         .add_attribute("method", "reveal_random")
         .add_attribute("participant", info.sender)
-        .add_attribute("lucky_numbers_count", lucky_numbers.len().to_string())
+        .add_attribute("lucky_numbers_count", lucky_numbers_count.to_string())
+        .add_attribute("tickets_count", tickets.len().to_string())
         .add_attribute("phase", current_phase.name()))
 }
 
 /// 结算彩票
+/// 
+/// 结算分批进行，每次调用从上次的游标处继续，至多处理 `limit` 个参与者、号码索引记录或中奖记录：
+/// 先读取全部参与者折叠随机种子并处理未揭秘者，计算出中奖号码后再读取可能中奖号码下的参与者
+/// 累计中奖记录，最后逐批计算中奖记录的奖金，全部处理完成后标记会话已结算
 fn execute_settle_lottery(
     deps: DepsMut,
     env: Env,
     _info: MessageInfo,
+    limit: Option<u32>,
 ) -> Result<Response, ContractError> {
     // 检查当前阶段
-    let current_phase = LotteryPhase::from_block_height(env.block.height);
+    let config = CONFIG.load(deps.storage)?;
+    let current_phase = PhaseManager::get_current_phase(&config, &env);
     if current_phase != LotteryPhase::Settlement {
         return Err(ContractError::invalid_phase("settlement", current_phase.name()));
     }
 
     let mut session = CURRENT_SESSION.load(deps.storage)?;
+    if session.cycle != PhaseManager::get_current_cycle(&config, &env) {
+        return Err(ContractError::SessionNotFound);
+    }
     if session.settled {
         return Err(ContractError::LotteryAlreadySettled);
     }
+    if session.cancelled {
+        return Err(ContractError::SessionCancelled);
+    }
+
+    // 无人揭秘时无法产生中奖号码，取消会话，投注者通过 Refund 取回投注
+    if session.revealed_count == 0 {
+        cancel_session(deps.storage, &mut session)?;
+        CURRENT_SESSION.save(deps.storage, &session)?;
+
+        return Ok(Response::new()
+            .add_attribute("method", "settle_lottery")
+            .add_attribute("session_id", session.session_id)
+            .add_attribute("status", "cancelled"));
+    }
+
+    let mut progress = match SETTLEMENT_PROGRESS.may_load(deps.storage)? {
+        Some(progress) if progress.session_id == session.session_id => progress,
+        _ => {
+            // 使用外部信标时，首次结算发出随机数请求，收到信标回调后才能完成结算
+            let beacon_randomness = match session.randomness_source.beacon().cloned() {
+                None => None,
+                Some(beacon) => match session.beacon_request.clone() {
+                    None => {
+                        let round = beacon.next_round_after(env.block.time.seconds());
+                        session.beacon_request = Some(BeaconRequest { round, randomness: None });
+                        CURRENT_SESSION.save(deps.storage, &session)?;
+
+                        let request = WasmMsg::Execute {
+                            contract_addr: beacon.address.to_string(),
+                            msg: to_json_binary(&BeaconExecuteMsg::RequestRandomness {
+                                job_id: session.session_id.clone(),
+                                round,
+                            })?,
+                            funds: vec![],
+                        };
+                        return Ok(Response::new()
+                            .add_message(request)
+                            .add_attribute("method", "settle_lottery")
+                            .add_attribute("session_id", session.session_id)
+                            .add_attribute("status", "awaiting_randomness")
+                            .add_attribute("beacon_round", round.to_string()));
+                    }
+                    Some(BeaconRequest { round, randomness: None }) => {
+                        return Err(ContractError::RandomnessNotReady { round });
+                    }
+                    Some(BeaconRequest { round, randomness: Some(randomness) }) => Some((round, randomness.to_hex())),
+                },
+            };
+
+            // 结算熵：使用信标时为信标随机数，否则由首次结算交易所在区块生成并保存在进度中，后续批次不再改变
+            let (post_reveal_entropy, beacon_round) = match beacon_randomness {
+                Some((round, randomness)) => (randomness, Some(round)),
+                None => (LotteryLogic::post_reveal_entropy(&env.block.chain_id, &session.session_id, env.block.height, env.block.time), None),
+            };
+
+            SettlementProgress {
+                session_id: session.session_id.clone(),
+                stage: SettlementStage::Drawing,
+                post_reveal_entropy,
+                beacon_round,
+                seed_digest: String::new(),
+                winning_number: None,
+                non_reveal: NonRevealOutcome {
+                    policy: session.non_reveal_policy.clone(),
+                    non_revealed_count: 0,
+                    non_revealed_amount: Uint128::zero(),
+                    forfeited_to_pool: Uint128::zero(),
+                    forfeited_to_treasury: Uint128::zero(),
+                    refunded: Uint128::zero(),
+                    slashed_deposits: Uint128::zero(),
+                },
+                tiers: session.prize_tiers.iter().map(|prize| TierSummary::new(prize.tier)).collect(),
+                winners_count: 0,
+                jackpot_in: Uint128::zero(),
+                reward_pool: Uint128::zero(),
+                total_pending: Uint128::zero(),
+                number_cursor: 0,
+                participant_cursor: None,
+                payout_cursor: None,
+                processed_participants: 0,
+            }
+        }
+    };
+
+    let mut budget = limit.unwrap_or(DEFAULT_SETTLEMENT_LIMIT).clamp(1, MAX_SETTLEMENT_LIMIT) as usize;
+
+    if progress.stage == SettlementStage::Drawing {
+        let start = progress.participant_cursor.as_ref().map(Bound::exclusive);
+        let batch = participants()
+            .prefix(session.session_id.as_str())
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(budget)
+            .map(|item| item.map(|(_, participant)| participant))
+            .collect::<StdResult<Vec<Participant>>>()?;
+        let exhausted = batch.len() < budget;
+        budget -= batch.len();
+
+        for participant in batch.iter().filter(|p| p.revealed) {
+            if let Some(seed) = &participant.random_seed {
+                progress.seed_digest = LotteryLogic::fold_seed(&progress.seed_digest, seed);
+            }
+        }
+
+        // 按策略处理未揭秘的参与者，退款直接记入待领取余额
+        let (non_reveal, refunds) = RewardSystem::settle_non_revealers(
+            &batch,
+            &session.non_reveal_policy,
+            session.total_pool,
+            session.service_fee,
+        )?;
+        progress.non_reveal.merge(&non_reveal);
+        for refund in refunds {
+            record_claimable(deps.storage, &refund.address, &session.session_id, refund.amount)?;
+            progress.total_pending += refund.amount;
+        }
+        progress.processed_participants += batch.len() as u32;
+        if let Some(last) = batch.last() {
+            progress.participant_cursor = Some(last.address.clone());
+        }
+
+        if exhausted {
+            // 计算中奖号码：参与者种子摘要混入结算熵（信标随机数或首次结算时生成的结算熵）
+            let winning_number = match session.randomness_source {
+                RandomnessSource::Beacon(_) => LotteryLogic::calculate_winning_number_from_beacon_randomness(
+                    &progress.post_reveal_entropy,
+                )?,
+                _ => LotteryLogic::calculate_winning_number_from_digest(
+                    &progress.seed_digest,
+                    Some(&progress.post_reveal_entropy),
+                )?,
+            };
+            progress.winning_number = Some(winning_number);
+            progress.stage = SettlementStage::Winners;
+            progress.participant_cursor = None;
+
+            session.winning_number = Some(winning_number);
+            CURRENT_SESSION.save(deps.storage, &session)?;
+        }
+    }
+
+    if progress.stage == SettlementStage::Winners {
+        // 通过号码索引只读取有人投注的可能中奖号码下的参与者
+        let winning_number = progress.winning_number.ok_or(ContractError::RewardCalculationError)?;
+        let enabled: Vec<_> = session.prize_tiers.iter().map(|prize| prize.tier).collect();
+        let index = participants().idx.number;
+        let mut candidates = vec![];
+        for number in LotteryLogic::winning_candidates(winning_number, &enabled) {
+            if index.bets(deps.storage, &session.session_id, number)? > 0 {
+                candidates.push(number);
+            }
+        }
+
+        while budget > 0 && (progress.number_cursor as usize) < candidates.len() {
+            let number = candidates[progress.number_cursor as usize];
+            let entries = index.page(
+                deps.storage,
+                &session.session_id,
+                number,
+                progress.participant_cursor.as_ref(),
+                budget,
+            )?;
+            let exhausted = entries.len() < budget;
+            budget -= entries.len();
+
+            for (address, _) in entries {
+                let participant = participants().load(deps.storage, (session.session_id.as_str(), &address))?;
+                for winner in RewardSystem::calculate_number_winners(
+                    &participant,
+                    number,
+                    winning_number,
+                    &session.prize_tiers,
+                    &session.ticket_prices,
+                ) {
+                    record_winning(deps.storage, &session.session_id, &mut progress, winner)?;
+                }
+                progress.participant_cursor = Some(address);
+            }
+
+            if exhausted {
+                progress.number_cursor += 1;
+                progress.participant_cursor = None;
+            }
+        }
+
+        if progress.number_cursor as usize == candidates.len() {
+            // 确定奖金池（并入累积奖池，扣除没收至服务费和退还的部分）和各奖级的奖金总额
+            let non_reveal = &progress.non_reveal;
+            progress.jackpot_in = JACKPOT.may_load(deps.storage)?.unwrap_or_default();
+            progress.reward_pool = (session.total_pool - session.service_fee + progress.jackpot_in + non_reveal.slashed_deposits)
+                .checked_sub(non_reveal.forfeited_to_treasury + non_reveal.refunded)
+                .map_err(|_| ContractError::RewardCalculationError)?;
+            for (summary, prize) in progress.tiers.iter_mut().zip(&session.prize_tiers) {
+                summary.budget = RewardSystem::tier_prize_budget(
+                    prize,
+                    summary.stake,
+                    progress.reward_pool,
+                    &session.prize_structure,
+                )?;
+            }
+            let total_budget: Uint128 = progress.tiers.iter().map(|summary| summary.budget).sum();
+            if total_budget > progress.reward_pool {
+                return Err(ContractError::RewardCalculationError);
+            }
+            progress.stage = SettlementStage::Payouts;
+        }
+    }
+
+    if progress.stage == SettlementStage::Payouts {
+        // 逐批按中奖投注额比例计算奖金，记入待领取余额
+        let start = progress.payout_cursor.as_ref().map(|(address, level)| Bound::exclusive((address, *level)));
+        let batch = WINNINGS
+            .sub_prefix(session.session_id.as_str())
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(budget)
+            .collect::<StdResult<Vec<_>>>()?;
+        let exhausted = batch.len() < budget;
+
+        for ((address, level), mut winner) in batch {
+            let summary = progress.tiers.iter_mut()
+                .find(|summary| summary.tier.level() == level)
+                .ok_or(ContractError::RewardCalculationError)?;
+            winner.reward_amount = RewardSystem::stake_reward(summary.budget, winner.stake, summary.stake);
+            summary.distributed += winner.reward_amount;
+            WINNINGS.save(deps.storage, (session.session_id.as_str(), &address, level), &winner)?;
+
+            record_claimable(deps.storage, &address, &session.session_id, winner.reward_amount)?;
+            progress.total_pending += winner.reward_amount;
+            progress.payout_cursor = Some((address, level));
+        }
+
+        if exhausted {
+            return finalize_settlement(deps, env, &config, session, progress, current_phase);
+        }
+    }
+
+    SETTLEMENT_PROGRESS.save(deps.storage, &progress)?;
+
+    let mut response = Response::new()
+        .add_attribute("method", "settle_lottery")
+        .add_attribute("session_id", session.session_id)
+        .add_attribute("status", "in_progress")
+        .add_attribute("stage", progress.stage.name())
+        .add_attribute("processed_participants", progress.processed_participants.to_string())
+        .add_attribute("winners_count", progress.winners_count.to_string());
+    if let Some(winning_number) = progress.winning_number {
+        response = response.add_attribute("winning_number", winning_number.to_string());
+    }
+    Ok(response)
+}
+
+/// 累计中奖记录和奖级汇总，中奖者在本期首次中奖时计入中奖人数
+fn record_winning(
+    storage: &mut dyn Storage,
+    session_id: &str,
+    progress: &mut SettlementProgress,
+    winner: Winner,
+) -> Result<(), ContractError> {
+    let summary = progress.tiers.iter_mut()
+        .find(|summary| summary.tier.level() == winner.level)
+        .ok_or(ContractError::RewardCalculationError)?;
+    summary.count += winner.count as u64;
+    summary.stake += winner.stake;
+
+    let first_win = WINNINGS
+        .prefix((session_id, &winner.address))
+        .keys(storage, None, None, Order::Ascending)
+        .next()
+        .is_none();
+    if first_win {
+        progress.winners_count += 1;
+    }
 
-    // 计算中奖号码
-    let winning_number = LotteryLogic::calculate_winning_number(&session.participants)?;
-    session.winning_number = Some(winning_number);
+    let address = winner.address.clone();
+    let key = (session_id, &address, winner.level);
+    let record = match WINNINGS.may_load(storage, key)? {
+        Some(mut record) => {
+            record.count += winner.count;
+            record.stake += winner.stake;
+            record
+        }
+        None => winner,
+    };
+    WINNINGS.save(storage, key, &record)?;
+    Ok(())
+}
+
+/// 记入待领取余额和本期应发金额
+pub(crate) fn record_claimable(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    session_id: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+    CLAIMABLE.update(storage, (address, session_id), |balance| -> StdResult<_> {
+        Ok(balance.unwrap_or_default() + amount)
+    })?;
+    PRIZES.update(storage, (address, session_id), |prize| -> StdResult<_> {
+        let mut prize = prize.unwrap_or_default();
+        prize.amount += amount;
+        Ok(prize)
+    })?;
+    Ok(())
+}
 
-    // 计算奖金分配
-    let mut winners = RewardSystem::calculate_winners(&session.participants, winning_number)?;
-    let _rewards = RewardSystem::distribute_rewards(&mut winners, session.total_pool - session.service_fee)?;
+/// 完成结算
+/// 奖金余额滚入累积奖池，计入待领取奖金台账和服务费，保存结果并标记会话已结算
+fn finalize_settlement(
+    deps: DepsMut,
+    env: Env,
+    config: &Config,
+    mut session: LotterySession,
+    progress: SettlementProgress,
+    current_phase: LotteryPhase,
+) -> Result<Response, ContractError> {
+    let winning_number = progress.winning_number.ok_or(ContractError::RewardCalculationError)?;
+
+    // 奖金分配后的剩余（无人中奖时为整个奖金池）滚入下一期；
+    // 结算期间回收的过期奖金已计入累积奖池，只扣除本期并入的部分
+    let total_rewards: Uint128 = progress.tiers.iter().map(|summary| summary.distributed).sum();
+    let jackpot_out = progress.reward_pool.checked_sub(total_rewards)
+        .map_err(|_| ContractError::RewardCalculationError)?;
+    let jackpot = JACKPOT.may_load(deps.storage)?.unwrap_or_default()
+        .checked_sub(progress.jackpot_in)
+        .map_err(|_| ContractError::RewardCalculationError)?;
+    JACKPOT.save(deps.storage, &(jackpot + jackpot_out))?;
+
+    // 待领取奖金和退款已在结算过程中记入 CLAIMABLE，由收款人通过 ClaimReward 自行领取
+    credit_ledger(deps.storage, &UNCLAIMED_PRIZES, progress.total_pending)?;
+
+    // 服务费扣除推荐奖励后在结算时计提或按分成表直接发放，取消的会话不收取服务费
+    credit_ledger(deps.storage, &REFERRAL_PAYABLE, session.referral_fees)?;
+    let fees = (session.service_fee + progress.non_reveal.forfeited_to_treasury)
+        .checked_sub(session.referral_fees)
+        .map_err(|_| ContractError::RewardCalculationError)?;
+    let fee_msgs = if config.fee_distribution == FeeDistribution::AtSettlement && !config.fee_recipients.is_empty() {
+        fee_transfer_msgs(config, fees)?
+    } else {
+        credit_ledger(deps.storage, &ACCRUED_FEES, fees)?;
+        vec![]
+    };
+    let distributed_fees = if fee_msgs.is_empty() { Uint128::zero() } else { fees };
 
     // 创建彩票结果
     let result = LotteryResult {
         session_id: session.session_id.clone(),
+        cycle: session.cycle,
         winning_number,
         total_pool: session.total_pool,
         service_fee: session.service_fee,
-        reward_pool: session.total_pool - session.service_fee,
-        winners,
+        reward_pool: progress.reward_pool,
+        jackpot_in: progress.jackpot_in,
+        jackpot_out,
+        tiers: progress.tiers,
+        winners_count: progress.winners_count,
         settled_at: env.block.time,
         settled_height: env.block.height,
+        total_paid: Uint128::zero(),
+        total_pending: progress.total_pending,
+        total_expired: Uint128::zero(),
+        claim_deadline_height: env.block.height.saturating_add(config.claim_expiry_blocks),
+        non_reveal: progress.non_reveal,
+        post_reveal_entropy: progress.post_reveal_entropy,
+        randomness_source: session.randomness_source.clone(),
+        beacon_round: progress.beacon_round,
+        prize_structure: session.prize_structure.clone(),
+        prize_tiers: session.prize_tiers.clone(),
+        ticket_prices: session.ticket_prices.clone(),
     };
 
     // 保存结果
     LOTTERY_HISTORY.save(deps.storage, session.session_id.clone(), &result)?;
+    SETTLED_SESSIONS.save(deps.storage, session.cycle, &session.session_id)?;
 
-    // 标记为已结算
+    // 最后一批处理完成后才标记为已结算
     session.settled = true;
     CURRENT_SESSION.save(deps.storage, &session)?;
+    SETTLEMENT_PROGRESS.remove(deps.storage);
 
     // 更新统计信息
     let mut stats = STATS.load(deps.storage)?;
     stats.total_sessions += 1;
-    stats.total_rewards += result.reward_pool;
+    stats.total_rewards += total_rewards;
+    stats.total_service_fee += fees;
     stats.last_updated = env.block.time;
     STATS.save(deps.storage, &stats)?;
 
     Ok(Response::new()
+        .add_messages(fee_msgs)
         .add_attribute("method", "settle_lottery")
         .add_attribute("session_id", session.session_id)
         .add_attribute("winning_number", winning_number.to_string())
         .add_attribute("total_pool", session.total_pool.to_string())
-        .add_attribute("winners_count", result.winners.len().to_string())
+        .add_attribute("winners_count", result.winners_count.to_string())
+        .add_attribute("total_pending", result.total_pending.to_string())
+        .add_attribute("jackpot_out", result.jackpot_out.to_string())
+        .add_attribute("non_revealed_count", result.non_reveal.non_revealed_count.to_string())
+        .add_attribute("claim_deadline_height", result.claim_deadline_height.to_string())
+        .add_attribute("distributed_fees", distributed_fees.to_string())
         .add_attribute("phase", current_phase.name()))
 }
 
+/// 退款
+/// 会话取消后，投注者可以取回投注金额；结算阶段结束仍未结算的当前会话在此时取消
+fn execute_refund(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    session_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let cycle = PhaseManager::get_current_cycle(&config, &env);
+
+    let session = match CURRENT_SESSION.may_load(deps.storage)? {
+        Some(mut session) if session.session_id == session_id => {
+            if !session.settled && !session.cancelled && session.cycle < cycle {
+                cancel_session(deps.storage, &mut session)?;
+                CURRENT_SESSION.save(deps.storage, &session)?;
+            }
+            session
+        }
+        _ => SESSION_DETAILS.may_load(deps.storage, session_id.clone())?
+            .ok_or(ContractError::SessionNotFound)?,
+    };
+
+    if !session.cancelled {
+        return Err(ContractError::SessionNotCancelled);
+    }
+
+    let key = (session_id.as_str(), &info.sender);
+    let participant = participants().may_load(deps.storage, key)?
+        .ok_or(ContractError::ParticipantNotFound)?;
+
+    if REFUNDS.has(deps.storage, key) {
+        return Err(ContractError::AlreadyRefunded);
+    }
+    REFUNDS.save(deps.storage, key, &participant.bet_amount)?;
+    debit_ledger(deps.storage, &PENDING_REFUNDS, participant.bet_amount)?;
+    clear_partial_settlement(deps.storage, &session_id, &info.sender)?;
+
+    let refund_msg = config.bet_asset.transfer_msg(&info.sender, participant.bet_amount)?;
+
+    Ok(Response::new()
+        .add_message(refund_msg)
+        .add_attribute("method", "refund")
+        .add_attribute("session_id", session_id)
+        .add_attribute("participant", info.sender.to_string())
+        .add_attribute("amount", participant.bet_amount.to_string()))
+}
+
+/// 信标回调
+/// 只接受当前会话所配置信标代理合约的回调，且轮次必须与请求时一致
+fn execute_beacon_callback(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    job_id: String,
+    round: u64,
+    randomness: HexBinary,
+) -> Result<Response, ContractError> {
+    let mut session = CURRENT_SESSION.may_load(deps.storage)?
+        .ok_or(ContractError::SessionNotFound)?;
+
+    let beacon = session.randomness_source.beacon()
+        .ok_or(ContractError::Unauthorized)?;
+    if info.sender != beacon.address {
+        return Err(ContractError::Unauthorized);
+    }
+
+    if session.session_id != job_id || session.settled {
+        return Err(ContractError::SessionNotFound);
+    }
+
+    // 只接受尚未交付的请求
+    let request = match session.beacon_request.as_mut() {
+        Some(request) if request.randomness.is_none() => request,
+        _ => return Err(ContractError::SessionNotFound),
+    };
+    if request.round != round {
+        return Err(ContractError::UnexpectedBeaconRound { expected: request.round, actual: round });
+    }
+    if randomness.len() != 32 {
+        return Err(ContractError::InvalidRandomSeed);
+    }
+    request.randomness = Some(randomness);
+
+    CURRENT_SESSION.save(deps.storage, &session)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "beacon_callback")
+        .add_attribute("session_id", job_id)
+        .add_attribute("round", round.to_string()))
+}
+
+/// 领取奖金
+fn execute_claim_reward(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    session_id: Option<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    let mut total = Uint128::zero();
+    let mut claimed_sessions: Vec<String> = vec![];
+    let mut expired_sessions: Vec<String> = vec![];
+    let mut cancelled_sessions: Vec<String> = vec![];
+
+    match session_id {
+        Some(session_id) => {
+            total += claim_session_reward(deps.storage, &info.sender, &session_id, env.block.height)?;
+            claimed_sessions.push(session_id);
+        }
+        None => {
+            // 一键领取：单笔交易最多读取 MAX_CLAIMS_PER_TX 个待领取条目，跳过尚未完成结算的会话；
+            // 已过期的条目直接删除，过期金额仍由 ExpireRewards 按会话退回累积奖池；
+            // 结算中途取消的会话留下的条目作废并删除
+            let session_ids = CLAIMABLE
+                .prefix(&info.sender)
+                .keys(deps.storage, None, None, Order::Ascending)
+                .take(MAX_CLAIMS_PER_TX)
+                .collect::<StdResult<Vec<String>>>()?;
+
+            for session_id in session_ids {
+                match claim_session_reward(deps.storage, &info.sender, &session_id, env.block.height) {
+                    Ok(amount) => {
+                        total += amount;
+                        claimed_sessions.push(session_id);
+                    }
+                    Err(ContractError::ClaimExpired { .. }) => {
+                        CLAIMABLE.remove(deps.storage, (&info.sender, session_id.as_str()));
+                        expired_sessions.push(session_id);
+                    }
+                    Err(ContractError::SessionCancelled) => {
+                        clear_partial_settlement(deps.storage, &session_id, &info.sender)?;
+                        cancelled_sessions.push(session_id);
+                    }
+                    Err(ContractError::LotteryNotSettled) => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+
+    if total.is_zero() && expired_sessions.is_empty() && cancelled_sessions.is_empty() {
+        return Err(ContractError::NothingToClaim);
+    }
+
+    let mut response = Response::new();
+    if !total.is_zero() {
+        response = response.add_message(config.bet_asset.transfer_msg(&info.sender, total)?);
+    }
+
+    Ok(response
+        .add_attribute("method", "claim_reward")
+        .add_attribute("claimant", info.sender)
+        .add_attribute("sessions", claimed_sessions.join(","))
+        .add_attribute("expired_sessions", expired_sessions.join(","))
+        .add_attribute("cancelled_sessions", cancelled_sessions.join(","))
+        .add_attribute("amount", total.to_string()))
+}
+
+/// 领取单个会话的奖金，返回领取金额
+fn claim_session_reward(
+    storage: &mut dyn Storage,
+    claimant: &Addr,
+    session_id: &str,
+    block_height: u64,
+) -> Result<Uint128, ContractError> {
+    let amount = CLAIMABLE.may_load(storage, (claimant, session_id))?
+        .ok_or(ContractError::NothingToClaim)?;
+
+    // 待领取余额在结算过程中写入，会话完成结算后才能领取，结算中途取消的会话作废
+    let mut result = match LOTTERY_HISTORY.may_load(storage, session_id.to_string())? {
+        Some(result) => result,
+        None => match load_session(storage, session_id)? {
+            Some(session) if session.cancelled => return Err(ContractError::SessionCancelled),
+            _ => return Err(ContractError::LotteryNotSettled),
+        },
+    };
+    if block_height > result.claim_deadline_height {
+        return Err(ContractError::ClaimExpired { deadline: result.claim_deadline_height });
+    }
+
+    result.total_paid += amount;
+    result.total_pending = result.total_pending.checked_sub(amount)
+        .map_err(|_| ContractError::RewardCalculationError)?;
+
+    LOTTERY_HISTORY.save(storage, session_id.to_string(), &result)?;
+    CLAIMABLE.remove(storage, (claimant, session_id));
+    PRIZES.update(storage, (claimant, session_id), |prize| -> StdResult<_> {
+        let mut prize = prize.unwrap_or_default();
+        prize.claimed = true;
+        Ok(prize)
+    })?;
+    debit_ledger(storage, &UNCLAIMED_PRIZES, amount)?;
+
+    Ok(amount)
+}
+
+/// 回收过期奖金
+/// 领取截止高度之后，任何人都可以将该会话未领取的奖金退回累积奖池，并入下一次结算；
+/// 过期的待领取余额不能再领取，条目由收款人一键领取时删除
+fn execute_expire_rewards(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    session_id: String,
+) -> Result<Response, ContractError> {
+    let mut result = LOTTERY_HISTORY.may_load(deps.storage, session_id.clone())?
+        .ok_or(ContractError::SessionNotFound)?;
+
+    if env.block.height <= result.claim_deadline_height {
+        return Err(ContractError::ClaimNotExpired { deadline: result.claim_deadline_height });
+    }
+
+    let expired = result.total_pending;
+    if expired.is_zero() {
+        return Err(ContractError::NothingToClaim);
+    }
+
+    result.total_pending = Uint128::zero();
+    result.total_expired += expired;
+    LOTTERY_HISTORY.save(deps.storage, session_id.clone(), &result)?;
+
+    debit_ledger(deps.storage, &UNCLAIMED_PRIZES, expired)?;
+    add_to_jackpot(deps.storage, expired)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "expire_rewards")
+        .add_attribute("session_id", session_id)
+        .add_attribute("expired_amount", expired.to_string()))
+}
+
 /// 更新配置
 fn execute_update_config(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    service_fee_rate: Option<Decimal>,
-    min_bet_amount: Option<Uint128>,
-    max_bet_amount: Option<Uint128>,
-    bet_denom: Option<String>,
-    pause_requested: Option<bool>,
+    msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    let ExecuteMsg::UpdateConfig {
+        service_fee_rate,
+        min_bet_amount,
+        max_bet_amount,
+        bet_denom,
+        pause_requested,
+        claim_expiry_blocks,
+        commitment_blocks,
+        reveal_blocks,
+        settlement_blocks,
+        commitment_seconds,
+        reveal_seconds,
+        settlement_seconds,
+        non_reveal_policy,
+        commitment_deposit,
+        randomness_source,
+        prize_structure,
+        prize_tiers,
+        ticket_prices,
+        extra_funds_policy,
+        fee_recipients,
+        fee_distribution,
+        referral_fee_share,
+    } = msg else {
+        return Err(ContractError::InvalidConfiguration);
+    };
+
     // 检查管理员权限
     let config = CONFIG.load(deps.storage)?;
-    if info.sender != config.admin {
+    if !config.is_admin(&info.sender) {
         return Err(ContractError::Unauthorized);
     }
 
@@ -481,27 +1388,147 @@ fn execute_update_config(
         new_config.max_bet_amount = max_amount;
     }
 
+    // 只有原生代币投注可以切换面额，CW20 投注资产在实例化后不可更改
+    // 台账和当前会话以原面额记账，全部清空前不能切换
     if let Some(denom) = bet_denom {
         if denom.is_empty() {
             return Err(ContractError::InvalidBetDenom);
         }
-        new_config.bet_denom = denom;
+        if !matches!(new_config.bet_asset, BetAsset::Native { .. }) {
+            return Err(ContractError::InvalidConfiguration);
+        }
+        if new_config.bet_asset.to_string() != denom && has_outstanding_balances(deps.storage)? {
+            return Err(ContractError::OutstandingBalances);
+        }
+        new_config.bet_asset = BetAsset::Native { denom };
     }
 
     if let Some(pause_req) = pause_requested {
         new_config.pause_requested = pause_req;
     }
 
+    if let Some(expiry) = claim_expiry_blocks {
+        if expiry == 0 {
+            return Err(ContractError::InvalidConfiguration);
+        }
+        new_config.claim_expiry_blocks = expiry;
+    }
+
+    // 未揭秘处理策略在会话创建时确定，当前会话不受影响
+    if let Some(policy) = non_reveal_policy {
+        if !policy.is_valid() {
+            return Err(ContractError::InvalidConfiguration);
+        }
+        new_config.non_reveal_policy = policy;
+    }
+
+    // 押金在投注时按当时的配置收取，修改不影响已投注的参与者
+    if let Some(deposit) = commitment_deposit {
+        new_config.commitment_deposit = deposit;
+    }
+
+    // 随机数来源在会话创建时确定，当前会话不受影响
+    if let Some(source) = randomness_source {
+        crate::contract::validate_randomness_source(deps.as_ref(), &source)?;
+        new_config.randomness_source = source;
+    }
+
+    // 奖金结构在会话创建时确定，当前会话不受影响
+    if let Some(structure) = prize_structure {
+        if !structure.is_valid() {
+            return Err(ContractError::InvalidConfiguration);
+        }
+        new_config.prize_structure = structure;
+    }
+
+    // 奖级配置在会话创建时确定，当前会话不受影响
+    if let Some(tiers) = prize_tiers {
+        if !TierPrize::validate_all(&tiers) {
+            return Err(ContractError::InvalidConfiguration);
+        }
+        new_config.prize_tiers = tiers;
+    }
+
+    // 投注类型单价在会话创建时确定，当前会话不受影响
+    if let Some(prices) = ticket_prices {
+        if !prices.is_valid() {
+            return Err(ContractError::InvalidConfiguration);
+        }
+        new_config.ticket_prices = prices;
+    }
+
+    if let Some(policy) = extra_funds_policy {
+        new_config.extra_funds_policy = policy;
+    }
+
+    if let Some(recipients) = fee_recipients {
+        crate::contract::validate_fee_recipients(deps.as_ref(), &recipients)?;
+        new_config.fee_recipients = recipients;
+    }
+
+    if let Some(distribution) = fee_distribution {
+        new_config.fee_distribution = distribution;
+    }
+
+    // 推荐奖励在投注时计算，已投注的推荐奖励不受影响
+    if let Some(share) = referral_fee_share {
+        if share > Decimal::one() {
+            return Err(ContractError::InvalidConfiguration);
+        }
+        new_config.referral_fee_share = share;
+    }
+
+    // 阶段长度的修改只对之后的周期生效，当前周期保持不变
+    let (commitment_length, reveal_length, settlement_length) = match new_config.schedule.mode {
+        ScheduleMode::BlockHeight => {
+            if commitment_seconds.is_some() || reveal_seconds.is_some() || settlement_seconds.is_some() {
+                return Err(ContractError::InvalidConfiguration);
+            }
+            (commitment_blocks, reveal_blocks, settlement_blocks)
+        }
+        ScheduleMode::Timestamp => {
+            if commitment_blocks.is_some() || reveal_blocks.is_some() || settlement_blocks.is_some() {
+                return Err(ContractError::InvalidConfiguration);
+            }
+            (commitment_seconds, reveal_seconds, settlement_seconds)
+        }
+    };
+    if commitment_length.is_some() || reveal_length.is_some() || settlement_length.is_some() {
+        let current = new_config.schedule_at(&env.block).clone();
+        let position = current.position(&env.block);
+
+        // 已到达生效位置的待生效时间表先转正；未生效的待生效时间表作为修改基准
+        let latest = new_config.pending_schedule.take()
+            .filter(|pending| position < pending.genesis)
+            .unwrap_or_else(|| current.clone());
+        new_config.schedule = current.clone();
+
+        let next_cycle = current.cycle_at(position) + 1;
+        let next_schedule = PhaseSchedule {
+            mode: current.mode,
+            genesis: current.cycle_start(next_cycle),
+            first_cycle: next_cycle,
+            commitment_length: commitment_length.unwrap_or(latest.commitment_length),
+            reveal_length: reveal_length.unwrap_or(latest.reveal_length),
+            settlement_length: settlement_length.unwrap_or(latest.settlement_length),
+        };
+        if !next_schedule.is_valid() {
+            return Err(ContractError::InvalidConfiguration);
+        }
+        new_config.pending_schedule = Some(next_schedule);
+    }
+
     CONFIG.save(deps.storage, &new_config)?;
 
     Ok(Response::new()
         .add_attribute("method", "update_config")
-        .add_attribute("admin", new_config.admin)
+        .add_attribute("admin", info.sender)
         .add_attribute("service_fee_rate", new_config.service_fee_rate.to_string())
         .add_attribute("min_bet_amount", new_config.min_bet_amount.to_string())
         .add_attribute("max_bet_amount", new_config.max_bet_amount.to_string())
-        .add_attribute("bet_denom", new_config.bet_denom)
-        .add_attribute("pause_requested", new_config.pause_requested.to_string()))
+        .add_attribute("bet_asset", new_config.bet_asset.to_string())
+        .add_attribute("pause_requested", new_config.pause_requested.to_string())
+        .add_attribute("claim_expiry_blocks", new_config.claim_expiry_blocks.to_string()))
 }
 
 /// 紧急暂停
@@ -513,7 +1540,7 @@ fn execute_emergency_pause(
 ) -> Result<Response, ContractError> {
     // 检查管理员权限
     let config = CONFIG.load(deps.storage)?;
-    if info.sender != config.admin {
+    if !config.is_admin(&info.sender) {
         return Err(ContractError::Unauthorized);
     }
 
@@ -524,10 +1551,102 @@ fn execute_emergency_pause(
     Ok(Response::new()
         .add_attribute("method", "emergency_pause")
         .add_attribute("paused", paused.to_string())
-        .add_attribute("admin", new_config.admin))
+        .add_attribute("admin", info.sender))
+}
+
+/// 提名新管理员
+/// 覆盖之前未接受的提名，被提名地址接受后才转移管理权
+fn execute_propose_admin(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    new_admin: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if !config.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized);
+    }
+
+    let new_admin = deps.api.addr_validate(&new_admin)?;
+    PENDING_ADMIN.save(deps.storage, &new_admin)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "propose_admin")
+        .add_attribute("admin", info.sender)
+        .add_attribute("pending_admin", new_admin))
+}
+
+/// 接受管理员提名
+fn execute_accept_admin(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let pending_admin = PENDING_ADMIN.may_load(deps.storage)?
+        .ok_or(ContractError::NoAdminProposal)?;
+    if info.sender != pending_admin {
+        return Err(ContractError::Unauthorized);
+    }
+
+    let mut config = CONFIG.load(deps.storage)?;
+    config.admin = Some(pending_admin);
+    CONFIG.save(deps.storage, &config)?;
+    PENDING_ADMIN.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("method", "accept_admin")
+        .add_attribute("admin", info.sender))
+}
+
+/// 撤销管理员提名
+fn execute_cancel_admin_proposal(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if !config.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized);
+    }
+    if !PENDING_ADMIN.exists(deps.storage) {
+        return Err(ContractError::NoAdminProposal);
+    }
+    PENDING_ADMIN.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("method", "cancel_admin_proposal")
+        .add_attribute("admin", info.sender))
+}
+
+/// 放弃管理权
+/// 同时撤销未接受的提名；之后配置更新和暂停不可用。
+/// 必须先配置服务费分成表，放弃后任何人都可触发服务费提取，按分成表发放
+fn execute_renounce_admin(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if !config.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized);
+    }
+    if config.fee_recipients.is_empty() {
+        return Err(ContractError::FeeRecipientsRequired);
+    }
+
+    config.admin = None;
+    CONFIG.save(deps.storage, &config)?;
+    PENDING_ADMIN.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("method", "renounce_admin")
+        .add_attribute("previous_admin", info.sender))
 }
 
 /// 提取服务费
+/// 只能提取结算时已计提的服务费，不会动用投注、待领取奖金和累积奖池；
+/// 提取的服务费按分成表发放，未配置分成表时发给管理员；
+/// 管理权已放弃时任何人都可以触发提取
 fn execute_withdraw_service_fee(
     deps: DepsMut,
     env: Env,
@@ -536,28 +1655,96 @@ fn execute_withdraw_service_fee(
 ) -> Result<Response, ContractError> {
     // 检查管理员权限
     let config = CONFIG.load(deps.storage)?;
-    if info.sender != config.admin {
+    if config.admin.is_some() && !config.is_admin(&info.sender) {
         return Err(ContractError::Unauthorized);
     }
 
+    // 检查已计提的服务费
+    let accrued = ACCRUED_FEES.may_load(deps.storage)?.unwrap_or_default();
+    if accrued < amount {
+        return Err(ContractError::InsufficientAccruedFees { available: accrued });
+    }
+
     // 检查余额
-    let balance = deps.querier.query_balance(&env.contract.address, &config.bet_denom)?;
-    if balance.amount < amount {
+    let balance = config.bet_asset.query_balance(&deps.querier, &env.contract.address)?;
+    if balance < amount {
         return Err(ContractError::InsufficientFunds);
     }
+    ACCRUED_FEES.save(deps.storage, &(accrued - amount))?;
 
     // 发送代币
-    let send_msg = cosmwasm_std::BankMsg::Send {
-        to_address: info.sender.to_string(),
-        amount: vec![cosmwasm_std::Coin {
-            denom: config.bet_denom.clone(),
-            amount,
-        }],
-    };
+    let send_msgs = fee_transfer_msgs(&config, amount)?;
 
     Ok(Response::new()
-        .add_message(send_msg)
+        .add_messages(send_msgs)
         .add_attribute("method", "withdraw_service_fee")
         .add_attribute("amount", amount.to_string())
         .add_attribute("admin", info.sender))
 }
+
+/// 领取推荐奖励
+/// 已结算会话的推荐奖励一并领取，已取消会话的推荐奖励作废，尚未结算的会话跳过；
+/// 单笔交易最多处理 MAX_CLAIMS_PER_TX 个会话
+fn execute_claim_referral_rewards(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    let entries = REFERRAL_EARNINGS
+        .prefix(&info.sender)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(String, Uint128)>>>()?;
+
+    let mut total = Uint128::zero();
+    let mut voided = Uint128::zero();
+    let mut claimed_sessions: Vec<String> = vec![];
+    for (session_id, amount) in entries {
+        if claimed_sessions.len() >= MAX_CLAIMS_PER_TX {
+            break;
+        }
+        match load_session(deps.storage, &session_id)? {
+            Some(session) if session.settled => total += amount,
+            Some(session) if session.cancelled => voided += amount,
+            _ => continue,
+        }
+        REFERRAL_EARNINGS.remove(deps.storage, (&info.sender, session_id.as_str()));
+        claimed_sessions.push(session_id);
+    }
+
+    if claimed_sessions.is_empty() {
+        return Err(ContractError::NothingToClaim);
+    }
+
+    let mut referrer_stats = REFERRER_STATS.may_load(deps.storage, &info.sender)?.unwrap_or_default();
+    referrer_stats.earned_fees = referrer_stats.earned_fees.saturating_sub(voided);
+    referrer_stats.claimed_fees += total;
+    REFERRER_STATS.save(deps.storage, &info.sender, &referrer_stats)?;
+    debit_ledger(deps.storage, &REFERRAL_PAYABLE, total)?;
+
+    let mut response = Response::new();
+    if !total.is_zero() {
+        response = response.add_message(config.bet_asset.transfer_msg(&info.sender, total)?);
+    }
+
+    Ok(response
+        .add_attribute("method", "claim_referral_rewards")
+        .add_attribute("referrer", info.sender)
+        .add_attribute("sessions", claimed_sessions.join(","))
+        .add_attribute("amount", total.to_string())
+        .add_attribute("voided", voided.to_string()))
+}
+
+/// 服务费发放消息：按分成表拆分，未配置分成表时全部发给管理员
+fn fee_transfer_msgs(config: &Config, amount: Uint128) -> StdResult<Vec<CosmosMsg>> {
+    let shares = if config.fee_recipients.is_empty() {
+        config.admin.iter().map(|admin| (admin.clone(), amount)).collect()
+    } else {
+        FeeRecipient::split(&config.fee_recipients, amount)
+    };
+    shares.into_iter()
+        .filter(|(_, share)| !share.is_zero())
+        .map(|(address, share)| config.bet_asset.transfer_msg(address, share))
+        .collect()
+}
@@ -1,7 +1,8 @@
+use cosmwasm_std::{Timestamp, Uint128};
 use sha2::{Sha256, Digest};
 use dd_algorithms_lib::get_one_dd_3d_rand_num;
 use crate::error::ContractError;
-use crate::state::Participant;
+use crate::state::{Participant, PrizeTier, BetType, Ticket, TicketPrices};
 
 /// 彩票逻辑管理器
 pub struct LotteryLogic;
@@ -9,27 +10,59 @@ pub struct LotteryLogic;
 impl LotteryLogic {
     /// 计算中奖号码
     pub fn calculate_winning_number(participants: &[Participant]) -> Result<u16, ContractError> {
+        Self::calculate_winning_number_with_entropy(participants, None)
+    }
+    
+    /// 计算中奖号码，并混入结算熵
+    /// 
+    /// 使用信标时结算熵为信标随机数，揭秘阶段内任何参与者都无法预知中奖号码；
+    /// 只使用参与者种子时结算熵由首次结算交易所在区块生成（见 `post_reveal_entropy`），
+    /// 揭秘时无法预知，但结算者可以选择首次结算的区块
+    pub fn calculate_winning_number_with_entropy(
+        participants: &[Participant],
+        post_reveal_entropy: Option<&str>,
+    ) -> Result<u16, ContractError> {
         if participants.is_empty() {
             return Err(ContractError::NoParticipants);
         }
         
-        // 收集所有已揭秘的随机种子
-        let mut random_values = Vec::new();
+        // 按顺序将已揭秘的随机种子折叠为种子摘要
+        let mut seed_digest = String::new();
         
         for participant in participants {
             if let Some(seed) = &participant.random_seed {
                 if participant.revealed {
-                    // 将随机种子转换为u128数值
-                    let seed_value = Self::hash_to_u128(seed);
-                    random_values.push(seed_value);
+                    seed_digest = Self::fold_seed(&seed_digest, seed);
                 }
             }
         }
         
-        if random_values.is_empty() {
+        Self::calculate_winning_number_from_digest(&seed_digest, post_reveal_entropy)
+    }
+    
+    /// 将随机种子折叠进种子摘要：SHA256(摘要|种子)，初始摘要为空
+    /// 
+    /// 分批结算按参与者地址顺序逐个折叠，进度中只保存摘要，
+    /// 与对按地址排序的参与者调用 `calculate_winning_number_with_entropy` 的结果一致
+    pub fn fold_seed(seed_digest: &str, seed: &str) -> String {
+        let data = format!("{}|{}", seed_digest, seed);
+        hex::encode(Sha256::digest(data.as_bytes()))
+    }
+    
+    /// 由种子摘要计算中奖号码，并混入结算熵，摘要为空表示没有已揭秘的种子
+    pub fn calculate_winning_number_from_digest(
+        seed_digest: &str,
+        post_reveal_entropy: Option<&str>,
+    ) -> Result<u16, ContractError> {
+        if seed_digest.is_empty() {
             return Err(ContractError::NoParticipants);
         }
         
+        let mut random_values = vec![Self::hash_to_u128(seed_digest)];
+        if let Some(entropy) = post_reveal_entropy {
+            random_values.push(Self::hash_to_u128(entropy));
+        }
+        
         // 使用dd_algorithms_lib的去中心化算法计算中奖号码
         let n = random_values.len();
         let k = 1000; // 3D彩票号码范围0-999
@@ -41,6 +74,40 @@ impl LotteryLogic {
         Ok(result as u16)
     }
     
+    /// 只使用信标随机数计算中奖号码
+    /// 参与者种子不参与计算，但至少需要一名参与者完成揭秘
+    pub fn calculate_winning_number_from_beacon(
+        participants: &[Participant],
+        beacon_randomness: &str,
+    ) -> Result<u16, ContractError> {
+        if !participants.iter().any(|p| p.revealed) {
+            return Err(ContractError::NoParticipants);
+        }
+        
+        Self::calculate_winning_number_from_beacon_randomness(beacon_randomness)
+    }
+    
+    /// 由信标随机数计算中奖号码，调用方需确认至少有一名参与者已揭秘
+    pub fn calculate_winning_number_from_beacon_randomness(beacon_randomness: &str) -> Result<u16, ContractError> {
+        let random_values = [Self::hash_to_u128(beacon_randomness)];
+        let mut result = 0u128;
+        
+        get_one_dd_3d_rand_num(&random_values, 1, 1000, &mut result)
+            .map_err(|_| ContractError::RandomGenerationFailed)?;
+        
+        Ok(result as u16)
+    }
+    
+    /// 不使用信标时的结算熵
+    /// 
+    /// 由链ID、会话ID和首次结算交易所在区块的高度和时间生成，只在首次结算时计算一次，
+    /// 揭秘阶段结束前无法预知，最后揭秘者不能据此决定是否揭秘。
+    /// 首次结算的发送者可以在结算阶段内选择提交区块，从而对结果有一次性的影响；之后的批次不能再改变
+    pub fn post_reveal_entropy(chain_id: &str, session_id: &str, height: u64, time: Timestamp) -> String {
+        let data = format!("{}|{}|{}|{}", chain_id, session_id, height, time.nanos());
+        hex::encode(Sha256::digest(data.as_bytes()))
+    }
+    
     /// 计算数字匹配数量
     /// 计算用户的幸运数字中有几个等于中奖号码
     pub fn count_matches(lucky_numbers: &[u16], winning_number: u16) -> u32 {
@@ -49,15 +116,155 @@ impl LotteryLogic {
             .count() as u32
     }
     
-    /// 确定中奖等级
-    /// 有几个中奖号码就中奖几次，每次都是一等奖
-    pub fn determine_winner_level(match_count: u32) -> Result<u8, ContractError> {
-        match match_count {
-            0 => Err(ContractError::InvalidWinnerLevel { level: 0 }),
-            _ => Ok(1), // 每次匹配都是一等奖
+    /// 拆分号码为 [百位, 十位, 个位]
+    fn digits(number: u16) -> [u16; 3] {
+        [number / 100, number / 10 % 10, number % 10]
+    }
+    
+    /// 判断投注号码是否满足指定奖级
+    pub fn matches_tier(tier: PrizeTier, number: u16, winning_number: u16) -> bool {
+        let ticket = Self::digits(number);
+        let winning = Self::digits(winning_number);
+        
+        let is_permutation = number != winning_number && Self::sorted_digits(number) == Self::sorted_digits(winning_number);
+        let distinct_digits = Self::distinct_digits(winning_number);
+        
+        match tier {
+            PrizeTier::Straight => number == winning_number,
+            PrizeTier::GroupThree => is_permutation && distinct_digits == 2,
+            PrizeTier::GroupSix => is_permutation && distinct_digits == 3,
+            PrizeTier::FrontPair => ticket[..2] == winning[..2],
+            PrizeTier::BackPair => ticket[1..] == winning[1..],
+        }
+    }
+    
+    /// 按从小到大排序的三位数字
+    fn sorted_digits(number: u16) -> [u16; 3] {
+        let mut digits = Self::digits(number);
+        digits.sort_unstable();
+        digits
+    }
+    
+    /// 号码中不同数字的个数
+    fn distinct_digits(number: u16) -> usize {
+        let digits = Self::sorted_digits(number);
+        1 + digits.windows(2).filter(|pair| pair[0] != pair[1]).count()
+    }
+    
+    /// 组选票的中奖奖级
+    /// 中奖号码为投注号码三位数字的任意排列（含原顺序）即中奖，
+    /// 中奖号码有两位相同时为组选三，三位各不相同时为组选六
+    pub fn box_tier(number: u16, winning_number: u16) -> Option<PrizeTier> {
+        if Self::sorted_digits(number) != Self::sorted_digits(winning_number) {
+            return None;
+        }
+        match Self::distinct_digits(winning_number) {
+            1 => Some(PrizeTier::Straight),
+            2 => Some(PrizeTier::GroupThree),
+            _ => Some(PrizeTier::GroupSix),
         }
     }
     
+    /// 投注票在启用奖级中的中奖奖级，每个号码最多中奖一次
+    pub fn ticket_tiers(ticket: &Ticket, winning_number: u16, enabled: &[PrizeTier]) -> Vec<PrizeTier> {
+        ticket.numbers.iter()
+            .filter_map(|&number| Self::bet_tier(ticket.bet_type, number, winning_number, enabled))
+            .collect()
+    }
+    
+    /// 按投注类型投注单个号码在启用奖级中的中奖奖级
+    pub fn bet_tier(bet_type: BetType, number: u16, winning_number: u16, enabled: &[PrizeTier]) -> Option<PrizeTier> {
+        match bet_type {
+            BetType::Straight | BetType::Combination => Self::match_tier(number, winning_number, enabled),
+            BetType::Box => Self::box_tier(number, winning_number)
+                .filter(|tier| enabled.contains(tier)),
+        }
+    }
+    
+    /// 验证投注票
+    /// 直选和组选票只能有一个号码，组选号码至少有两个不同数字，复式票至少两个不重复的号码
+    pub fn validate_tickets(tickets: &[Ticket]) -> Result<(), ContractError> {
+        if tickets.len() > Self::get_max_lucky_number_count() as usize {
+            return Err(ContractError::invalid_lucky_numbers(
+                &format!("Number of tickets {} exceeds maximum allowed {}",
+                        tickets.len(), Self::get_max_lucky_number_count())
+            ));
+        }
+        
+        for ticket in tickets {
+            if ticket.numbers.iter().any(|&number| number > 999) {
+                return Err(ContractError::invalid_lucky_numbers("Ticket numbers must be 0-999"));
+            }
+            match ticket.bet_type {
+                BetType::Straight => {
+                    if ticket.numbers.len() != 1 {
+                        return Err(ContractError::invalid_lucky_numbers("Straight ticket must have exactly 1 number"));
+                    }
+                }
+                BetType::Box => {
+                    if ticket.numbers.len() != 1 {
+                        return Err(ContractError::invalid_lucky_numbers("Box ticket must have exactly 1 number"));
+                    }
+                    if Self::distinct_digits(ticket.numbers[0]) < 2 {
+                        return Err(ContractError::invalid_lucky_numbers("Box ticket number must have at least 2 distinct digits"));
+                    }
+                }
+                BetType::Combination => {
+                    let mut numbers = ticket.numbers.clone();
+                    numbers.sort_unstable();
+                    numbers.dedup();
+                    if numbers.len() < 2 || numbers.len() != ticket.numbers.len() {
+                        return Err(ContractError::invalid_lucky_numbers(
+                            "Combination ticket must have at least 2 distinct numbers"
+                        ));
+                    }
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// 计算投注票总价
+    /// 直选和组选按张计价，复式按号码个数计价
+    pub fn tickets_cost(tickets: &[Ticket], prices: &TicketPrices) -> Result<Uint128, ContractError> {
+        tickets.iter().try_fold(Uint128::zero(), |total, ticket| {
+            let cost = prices.unit_price(ticket.bet_type)
+                .checked_mul(Uint128::from(ticket.numbers.len() as u128))
+                .ok();
+            cost.and_then(|cost| total.checked_add(cost).ok())
+                .ok_or_else(|| ContractError::invalid_lucky_numbers("Ticket cost overflow"))
+        })
+    }
+    
+    /// 确定投注号码（投注码、直选票和复式票的号码）在启用奖级中的最高奖级
+    /// 组选奖级只能由组选票通过 `box_tier` 获得，这里不参与匹配
+    pub fn match_tier(number: u16, winning_number: u16, enabled: &[PrizeTier]) -> Option<PrizeTier> {
+        PrizeTier::ALL
+            .into_iter()
+            .filter(|tier| !tier.is_group())
+            .find(|tier| enabled.contains(tier) && Self::matches_tier(*tier, number, winning_number))
+    }
+    
+    /// 可能中奖的号码
+    /// 在启用奖级中中奖的号码，以及启用组选奖级时组选票可能中奖的中奖号码排列
+    pub fn winning_candidates(winning_number: u16, enabled: &[PrizeTier]) -> Vec<u16> {
+        (0..1000)
+            .filter(|&number| {
+                Self::match_tier(number, winning_number, enabled).is_some()
+                    || Self::box_tier(number, winning_number).is_some_and(|tier| enabled.contains(&tier))
+            })
+            .collect()
+    }
+    
+    /// 确定中奖等级
+    /// 返回投注号码在所有奖级中的最高奖级编号，未中奖时返回错误
+    pub fn determine_winner_level(number: u16, winning_number: u16) -> Result<u8, ContractError> {
+        Self::match_tier(number, winning_number, &PrizeTier::ALL)
+            .map(|tier| tier.level())
+            .ok_or(ContractError::InvalidWinnerLevel { level: 0 })
+    }
+    
     /// 获取系统允许的最大单个幸运号码出现次数
     /// 当前固定返回1000，用于限制单个幸运号码的重复次数
     pub fn get_max_lucky_number_count() -> u32 {
@@ -145,6 +352,126 @@ impl LotteryLogic {
         Ok(hex::encode(hash))
     }
     
+    /// 将投注码列表转换为按号码升序的直方图 [(号码, 注数)]
+    pub fn to_number_counts(numbers: &[u16]) -> Vec<(u16, u32)> {
+        let mut counts = std::collections::BTreeMap::new();
+        for &number in numbers {
+            *counts.entry(number).or_insert(0u32) += 1;
+        }
+        counts.into_iter().collect()
+    }
+    
+    /// 验证投注码直方图，返回投注码总数
+    /// 号码须在0-999之间且严格升序，每个号码的注数在1到系统上限之间
+    pub fn validate_number_counts(counts: &[(u16, u32)]) -> Result<u128, ContractError> {
+        let max_count = Self::get_max_lucky_number_count();
+        let mut total = 0u128;
+        
+        for (i, &(number, count)) in counts.iter().enumerate() {
+            if number > 999 {
+                return Err(ContractError::invalid_lucky_numbers("Lucky numbers must be 0-999"));
+            }
+            if i > 0 && counts[i - 1].0 >= number {
+                return Err(ContractError::invalid_lucky_numbers(
+                    "Lucky number counts must be sorted by number without duplicates"
+                ));
+            }
+            if count == 0 || count > max_count {
+                return Err(ContractError::invalid_lucky_numbers(
+                    &format!("Lucky number {} appears {} times, but allowed range is 1-{}",
+                            number, count, max_count)
+                ));
+            }
+            total += count as u128;
+        }
+        
+        Ok(total)
+    }
+    
+    /// 将投注码直方图编码为承诺数据
+    /// 格式: 号码:注数,号码:注数 例如 "123:3,456:2"
+    pub fn encode_number_counts(counts: &[(u16, u32)]) -> String {
+        counts.iter()
+            .map(|(number, count)| format!("{}:{}", number, count))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+    
+    /// 生成投注码直方图的承诺哈希
+    /// 算法: SHA256(投注数量|号码:注数列表|随机种子)，包含投注票时为 SHA256(投注数量|号码:注数列表|投注票列表|随机种子)
+    pub fn generate_histogram_commitment_hash(
+        bet_amount: u128,
+        counts: &[(u16, u32)],
+        tickets: &[Ticket],
+        random_seed: &str,
+    ) -> String {
+        let commitment_data = if tickets.is_empty() {
+            format!("{}|{}|{}", bet_amount, Self::encode_number_counts(counts), random_seed)
+        } else {
+            format!(
+                "{}|{}|{}|{}",
+                bet_amount,
+                Self::encode_number_counts(counts),
+                Self::encode_tickets(tickets),
+                random_seed
+            )
+        };
+        
+        hex::encode(Sha256::digest(commitment_data.as_bytes()))
+    }
+    
+    /// 将投注票编码为承诺数据
+    /// 格式: 类型:号码-号码,类型:号码 例如 "straight:123,box:456,combination:1-2-3"
+    pub fn encode_tickets(tickets: &[Ticket]) -> String {
+        tickets.iter()
+            .map(|ticket| {
+                let bet_type = match ticket.bet_type {
+                    BetType::Straight => "straight",
+                    BetType::Box => "box",
+                    BetType::Combination => "combination",
+                };
+                let numbers = ticket.numbers.iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join("-");
+                format!("{}:{}", bet_type, numbers)
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+    
+    /// 生成包含投注票的承诺哈希
+    /// 算法: SHA256(投注数量|投注码列表|投注票列表|随机种子)，没有投注票时与 `generate_commitment_hash` 相同
+    pub fn generate_ticket_commitment_hash(
+        bet_amount: u128,
+        lucky_numbers: &[u16],
+        tickets: &[Ticket],
+        random_seed: &str,
+    ) -> Result<String, ContractError> {
+        if tickets.is_empty() {
+            return Self::generate_commitment_hash(bet_amount, lucky_numbers, random_seed);
+        }
+        
+        if lucky_numbers.iter().any(|&number| number > 999) {
+            return Err(ContractError::invalid_lucky_numbers("Lucky numbers must be 0-999"));
+        }
+        
+        let numbers_str = lucky_numbers.iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let commitment_data = format!(
+            "{}|{}|{}|{}",
+            bet_amount,
+            numbers_str,
+            Self::encode_tickets(tickets),
+            random_seed
+        );
+        
+        let hash = Sha256::digest(commitment_data.as_bytes());
+        Ok(hex::encode(hash))
+    }
+    
     /// 验证承诺哈希
     pub fn verify_commitment_hash(
         bet_amount: u128,
@@ -176,15 +503,19 @@ impl LotteryLogic {
     pub fn calculate_win_probability(match_count: u8) -> f64 {
         match match_count {
             0 => 999.0 / 1000.0,   // 不中奖概率
-            _ => 1.0 / 1000.0,    // 每次匹配的中奖概率（一等奖）
+            _ => 1.0 / 1000.0,    // 每次匹配的中奖概率（直选）
         }
     }
     
     /// 获取中奖等级名称
     pub fn get_winner_level_name(level: u8) -> &'static str {
-        match level {
-            1 => "一等奖",
-            _ => "未中奖",
+        match PrizeTier::from_level(level) {
+            Some(PrizeTier::Straight) => "直选",
+            Some(PrizeTier::GroupThree) => "组选三",
+            Some(PrizeTier::GroupSix) => "组选六",
+            Some(PrizeTier::FrontPair) => "前二",
+            Some(PrizeTier::BackPair) => "后二",
+            None => "未中奖",
         }
     }
     
@@ -257,4 +588,62 @@ impl LotteryLogic {
         
         Ok(())
     }
+    
+    /// 验证包含投注票的承诺
+    /// 投注金额须等于投注码数量（每个1个基础代币）加上投注票总价，承诺哈希须与揭秘数据一致
+    pub fn validate_ticket_commitment(
+        bet_amount: u128,
+        lucky_numbers: &[u16],
+        tickets: &[Ticket],
+        prices: &TicketPrices,
+        random_seed: &str,
+        commitment_hash: &str,
+    ) -> Result<(), ContractError> {
+        Self::validate_tickets(tickets)?;
+        
+        let total_cost = Self::tickets_cost(tickets, prices)?
+            .checked_add(Uint128::from(lucky_numbers.len() as u128))
+            .map_err(|_| ContractError::invalid_lucky_numbers("Ticket cost overflow"))?;
+        if total_cost.u128() != bet_amount {
+            return Err(ContractError::invalid_lucky_numbers(
+                "Bet amount must equal the number of betting codes plus total ticket cost"
+            ));
+        }
+        
+        let actual_hash = Self::generate_ticket_commitment_hash(bet_amount, lucky_numbers, tickets, random_seed)?;
+        if actual_hash != commitment_hash {
+            return Err(ContractError::CommitmentHashMismatch);
+        }
+        
+        Ok(())
+    }
+    
+    /// 验证投注码直方图的承诺
+    /// 投注金额须等于投注码总数加上投注票总价，承诺哈希须与揭秘数据一致
+    pub fn validate_histogram_commitment(
+        bet_amount: u128,
+        counts: &[(u16, u32)],
+        tickets: &[Ticket],
+        prices: &TicketPrices,
+        random_seed: &str,
+        commitment_hash: &str,
+    ) -> Result<(), ContractError> {
+        let code_count = Self::validate_number_counts(counts)?;
+        Self::validate_tickets(tickets)?;
+        
+        let total_cost = Self::tickets_cost(tickets, prices)?
+            .checked_add(Uint128::from(code_count))
+            .map_err(|_| ContractError::invalid_lucky_numbers("Ticket cost overflow"))?;
+        if total_cost.u128() != bet_amount {
+            return Err(ContractError::invalid_lucky_numbers(
+                "Bet amount must equal the number of betting codes plus total ticket cost"
+            ));
+        }
+        
+        if Self::generate_histogram_commitment_hash(bet_amount, counts, tickets, random_seed) != commitment_hash {
+            return Err(ContractError::CommitmentHashMismatch);
+        }
+        
+        Ok(())
+    }
 }
@@ -1,6 +1,7 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Uint128, Decimal};
-use crate::state::{LotteryPhase, Participant, LotteryResult, Config};
+use cosmwasm_std::{Addr, Uint128, Decimal, HexBinary, Order};
+use cw20::Cw20ReceiveMsg;
+use crate::state::{LotteryPhase, Participant, LotteryResult, Config, ScheduleMode, NonRevealPolicy, ExtraFundsPolicy, FeeRecipient, FeeDistribution, ReferrerStats, RandomnessSource, BeaconRequest, PrizeStructure, TierPrize, Ticket, TicketPrices, SettlementStage, PrizeTier, Winner};
 
 #[cw_serde]
 pub struct InstantiateMsg {
@@ -12,43 +13,166 @@ pub struct InstantiateMsg {
     pub min_bet_amount: Uint128,
     /// 最大投注金额
     pub max_bet_amount: Uint128,
-    /// 投注代币类型
+    /// 投注代币类型（原生代币）
     pub bet_denom: String,
     /// 是否请求暂停（完成当前周期后暂停）
     pub pause_requested: Option<bool>,
+    /// 奖金领取有效期（区块数），默认10个周期
+    pub claim_expiry_blocks: Option<u64>,
+    /// 承诺阶段区块数，默认6000
+    pub commitment_blocks: Option<u64>,
+    /// 揭秘阶段区块数，默认3000
+    pub reveal_blocks: Option<u64>,
+    /// 结算阶段区块数，默认1000
+    pub settlement_blocks: Option<u64>,
+    /// 第一个周期的起始区块高度，默认0
+    pub genesis_height: Option<u64>,
+    /// 阶段调度模式，默认按区块高度
+    pub schedule_mode: Option<ScheduleMode>,
+    /// 按时间调度时承诺阶段秒数，默认72000（20小时）
+    pub commitment_seconds: Option<u64>,
+    /// 按时间调度时揭秘阶段秒数，默认10800（3小时）
+    pub reveal_seconds: Option<u64>,
+    /// 按时间调度时结算阶段秒数，默认3600（1小时）
+    pub settlement_seconds: Option<u64>,
+    /// 按时间调度时第一个周期的起始时间（Unix 秒），默认为实例化时的区块时间
+    pub genesis_time: Option<u64>,
+    /// 未揭秘参与者的处理策略，默认没收并入奖金池
+    pub non_reveal_policy: Option<NonRevealPolicy>,
+    /// 承诺押金，默认0（不收取）
+    pub commitment_deposit: Option<Uint128>,
+    /// 随机数来源，默认只使用参与者随机种子
+    pub randomness_source: Option<RandomnessSource>,
+    /// 奖金结构，默认每注中奖固定获得800倍投注单位
+    pub prize_structure: Option<PrizeStructure>,
+    /// 奖级配置，默认只设直选奖级
+    pub prize_tiers: Option<Vec<TierPrize>>,
+    /// 投注类型单价，默认每种均为1个基础代币
+    pub ticket_prices: Option<TicketPrices>,
+    /// CW20代币合约地址，设置时使用该代币投注，`bet_denom` 不再使用
+    pub cw20_token: Option<String>,
+    /// 投注时附带的非投注代币的处理方式，默认拒绝
+    pub extra_funds_policy: Option<ExtraFundsPolicy>,
+    /// 服务费分成表，默认为空（服务费全部归管理员）
+    pub fee_recipients: Option<Vec<FeeRecipient>>,
+    /// 服务费的分配时机，默认提取时发放
+    pub fee_distribution: Option<FeeDistribution>,
+    /// 推荐奖励占推荐投注服务费的比例，默认为0
+    pub referral_fee_share: Option<Decimal>,
 }
 
 #[cw_serde]
 pub struct MigrateMsg {
-    pub new_admin: Option<String>,
+    /// 从 0.1.x 迁移时单次转换的开奖记录数上限（默认100，最多1000），
+    /// 未转换完时以相同版本再次迁移继续
+    #[serde(default)]
+    pub limit: Option<u32>,
 }
 
+/// CW20 `Send` 携带的钩子消息
 #[cw_serde]
+pub enum Cw20HookMsg {
+    /// 投注 - 发送金额为投注金额K加上承诺押金
+    PlaceBet {
+        /// 承诺哈希 (客户端计算的SHA256哈希)
+        commitment_hash: String,
+        /// 推荐人地址，不能是投注人自己
+        referrer: Option<String>,
+    },
+}
+
+// 消息只在反序列化后使用一次，UpdateConfig 的体积不影响性能
+#[cw_serde]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
     /// 投注 - 在承诺阶段执行，只发送承诺哈希
+    /// 转账金额为投注金额K加上承诺押金
     PlaceBet {
         /// 承诺哈希 (客户端计算的SHA256哈希)
         commitment_hash: String,
+        /// 推荐人地址，不能是投注人自己
+        referrer: Option<String>,
     },
     
+    /// 接收CW20代币 - 投注资产为CW20时，通过代币合约的 `Send` 携带 `Cw20HookMsg` 投注
+    Receive(Cw20ReceiveMsg),
+    
     /// 揭秘随机数 - 在中奖揭秘阶段执行
     RevealRandom {
         /// 投注码列表 (每个投注码对应一个幸运数字0-999)
         lucky_numbers: Vec<u16>,
         /// 用户随机种子
         random_seed: String,
+        /// 投注票列表，按会话的投注类型单价计价，与投注码一起计入承诺哈希
+        #[serde(default)]
+        tickets: Vec<Ticket>,
+        /// 投注码直方图 [(号码, 注数)]，按号码升序，与 `lucky_numbers` 二选一
+        #[serde(default)]
+        lucky_number_counts: Vec<(u16, u32)>,
+    },
+    
+    /// 结算彩票 - 在结算阶段执行，参与者较多时分多次调用，每次从上次的进度继续
+    SettleLottery {
+        /// 本次最多处理的参与者或号码索引记录数，默认100，最多1000
+        #[serde(default)]
+        limit: Option<u32>,
     },
     
-    /// 结算彩票 - 在结算阶段执行
-    SettleLottery {},
+    /// 领取奖金 - 指定会话ID时只领取该会话，否则领取所有未过期会话的奖金
+    ClaimReward {
+        session_id: Option<String>,
+    },
+    
+    /// 回收过期奖金 - 任何人可调用，将指定会话过期未领取的奖金退回累积奖池
+    ExpireRewards {
+        session_id: String,
+    },
+    
+    /// 退款 - 会话取消后，投注者取回投注金额
+    Refund {
+        session_id: String,
+    },
+    
+    /// 信标回调 - 仅配置的信标代理合约，交付指定会话所请求轮次的随机数
+    BeaconCallback {
+        /// 请求时携带的任务ID（会话ID）
+        job_id: String,
+        round: u64,
+        randomness: HexBinary,
+    },
     
     /// 更新配置 - 仅管理员
+    /// 阶段长度的修改从下一周期开始生效，长度单位需与当前调度模式一致
     UpdateConfig {
         service_fee_rate: Option<Decimal>,
         min_bet_amount: Option<Uint128>,
         max_bet_amount: Option<Uint128>,
         bet_denom: Option<String>,
         pause_requested: Option<bool>,
+        claim_expiry_blocks: Option<u64>,
+        commitment_blocks: Option<u64>,
+        reveal_blocks: Option<u64>,
+        settlement_blocks: Option<u64>,
+        commitment_seconds: Option<u64>,
+        reveal_seconds: Option<u64>,
+        settlement_seconds: Option<u64>,
+        /// 未揭秘处理策略的修改从下一会话开始生效
+        non_reveal_policy: Option<NonRevealPolicy>,
+        commitment_deposit: Option<Uint128>,
+        /// 随机数来源的修改从下一会话开始生效
+        randomness_source: Option<RandomnessSource>,
+        /// 奖金结构的修改从下一会话开始生效
+        prize_structure: Option<PrizeStructure>,
+        /// 奖级配置的修改从下一会话开始生效
+        prize_tiers: Option<Vec<TierPrize>>,
+        /// 投注类型单价的修改从下一会话开始生效
+        ticket_prices: Option<TicketPrices>,
+        extra_funds_policy: Option<ExtraFundsPolicy>,
+        /// 替换整个服务费分成表，空列表表示服务费全部归管理员
+        fee_recipients: Option<Vec<FeeRecipient>>,
+        fee_distribution: Option<FeeDistribution>,
+        /// 推荐奖励比例的修改对之后的投注生效
+        referral_fee_share: Option<Decimal>,
     },
     
     /// 紧急暂停 - 仅管理员
@@ -56,10 +180,37 @@ pub enum ExecuteMsg {
         paused: bool,
     },
     
-    /// 提取服务费 - 仅管理员
+    /// 提取服务费 - 仅管理员（管理权放弃后任何人），按分成表发放给各收款人
     WithdrawServiceFee {
         amount: Uint128,
     },
+    
+    /// 领取推荐奖励 - 领取已结算会话中的推荐奖励，已取消会话的推荐奖励作废
+    ClaimReferralRewards {},
+    
+    /// 提名新管理员 - 仅管理员，被提名地址通过 AcceptAdmin 接受后生效
+    ProposeAdmin {
+        new_admin: String,
+    },
+    
+    /// 接受管理员提名 - 仅被提名地址
+    AcceptAdmin {},
+    
+    /// 撤销管理员提名 - 仅管理员
+    CancelAdminProposal {},
+    
+    /// 放弃管理权 - 仅管理员，须先配置服务费分成表；之后服务费提取对任何人开放，其余管理员操作不可用
+    RenounceAdmin {},
+}
+
+/// 随机数信标代理合约的执行消息
+#[cw_serde]
+pub enum BeaconExecuteMsg {
+    /// 请求指定轮次的随机数，轮次发布后代理合约通过 `ExecuteMsg::BeaconCallback` 回调
+    RequestRandomness {
+        job_id: String,
+        round: u64,
+    },
 }
 
 #[cw_serde]
@@ -89,16 +240,23 @@ pub enum QueryMsg {
     #[returns(ConfigResponse)]
     GetConfig {},
     
-    /// 获取彩票历史
+    /// 获取彩票历史，按周期编号排序（默认从新到旧）
     #[returns(LotteryHistoryResponse)]
     GetLotteryHistory {
         limit: Option<u32>,
-        start_after: Option<String>,
+        /// 从该周期编号之后开始（按 `order` 方向）
+        start_after: Option<u64>,
+        order: Option<Order>,
     },
     
-    /// 获取参与者列表
+    /// 获取当前会话的参与者列表，按地址排序（默认升序）
     #[returns(ParticipantsResponse)]
-    GetParticipants {},
+    GetParticipants {
+        /// 从该地址之后开始（按 `order` 方向）
+        start_after: Option<String>,
+        limit: Option<u32>,
+        order: Option<Order>,
+    },
     
     /// 获取统计信息
     #[returns(StatsResponse)]
@@ -107,6 +265,54 @@ pub enum QueryMsg {
     /// 获取合约版本
     #[returns(VersionResponse)]
     GetVersion {},
+    
+    /// 获取地址的待领取奖金
+    #[returns(ClaimableResponse)]
+    GetClaimable {
+        address: String,
+    },
+    
+    /// 获取当前会话的结算进度
+    #[returns(SettlementProgressResponse)]
+    GetSettlementProgress {},
+    
+    /// 获取地址的投注历史，按周期编号从新到旧
+    #[returns(UserHistoryResponse)]
+    GetUserHistory {
+        address: String,
+        /// 从该周期编号之后开始
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    
+    /// 获取会话中各号码的投注数（揭秘后计入）
+    #[returns(NumberPopularityResponse)]
+    GetNumberPopularity {
+        session_id: String,
+    },
+    
+    /// 获取已结算会话的中奖记录，按 (中奖者地址, 奖级) 升序
+    #[returns(WinnersResponse)]
+    GetWinners {
+        session_id: String,
+        /// 从该 (中奖者地址, 奖级) 之后开始
+        start_after: Option<(String, u8)>,
+        limit: Option<u32>,
+    },
+    
+    /// 获取资金台账，核对合约余额与各项应付资金
+    #[returns(TreasuryResponse)]
+    GetTreasury {},
+    
+    /// 获取待接受的管理员提名
+    #[returns(PendingAdminResponse)]
+    GetPendingAdmin {},
+    
+    /// 获取推荐人统计和推荐奖励
+    #[returns(ReferrerStatsResponse)]
+    GetReferrerStats {
+        address: String,
+    },
 }
 
 // 响应结构体
@@ -115,18 +321,30 @@ pub struct CurrentSessionResponse {
     pub session: Option<LotterySession>,
     pub phase: LotteryPhase,
     pub block_height: u64,
+    /// 累积奖池，下一次结算时并入奖金池
+    pub jackpot: Uint128,
 }
 
 #[cw_serde]
 pub struct LotterySession {
     pub session_id: String,
+    pub cycle: u64,
     pub phase: LotteryPhase,
     pub total_pool: Uint128,
     pub service_fee: Uint128,
-    pub participants: Vec<Participant>,
+    /// 参与者列表通过分页查询 `GetParticipants` 获取
+    pub participant_count: u32,
+    pub revealed_count: u32,
     pub created_height: u64,
     pub winning_number: Option<u16>,
     pub settled: bool,
+    pub non_reveal_policy: NonRevealPolicy,
+    pub randomness_source: RandomnessSource,
+    pub prize_structure: PrizeStructure,
+    pub prize_tiers: Vec<TierPrize>,
+    pub ticket_prices: TicketPrices,
+    pub beacon_request: Option<BeaconRequest>,
+    pub cancelled: bool,
 }
 
 #[cw_serde]
@@ -144,6 +362,14 @@ pub struct PhaseResponse {
     pub phase: LotteryPhase,
     pub block_height: u64,
     pub phase_mod: u64,
+    pub cycle: u64,
+    pub mode: ScheduleMode,
+    pub block_time: u64,
+    /// 当前阶段剩余区块数；按区块时间调度时按平均出块时间估算
+    pub remaining_blocks: Option<u64>,
+    /// 当前阶段剩余秒数；按区块高度调度时按平均出块时间估算
+    /// 两者中的估算值在实例化（或迁移）后出块前无法给出，为 None
+    pub remaining_seconds: Option<u64>,
 }
 
 #[cw_serde]
@@ -154,12 +380,14 @@ pub struct ConfigResponse {
 #[cw_serde]
 pub struct LotteryHistoryResponse {
     pub results: Vec<LotteryResult>,
+    /// 已结算会话总数
     pub total: u32,
 }
 
 #[cw_serde]
 pub struct ParticipantsResponse {
     pub participants: Vec<Participant>,
+    /// 当前会话参与者总数
     pub total: u32,
 }
 
@@ -172,8 +400,159 @@ pub struct StatsResponse {
     pub total_rewards: Uint128,
 }
 
+/// 资金台账
+/// 
+/// 账目一致时 `balance` 等于 `liabilities`，超出部分为直接转入合约的资金
+#[cw_serde]
+pub struct TreasuryResponse {
+    /// 合约持有的投注资产余额
+    pub balance: Uint128,
+    /// 当前未结算会话的投注和未退还的承诺押金
+    pub active_pool: Uint128,
+    /// 待领取奖金和退款
+    pub unclaimed_prizes: Uint128,
+    /// 已取消会话尚未退款的投注
+    pub pending_refunds: Uint128,
+    /// 累积奖池
+    pub jackpot: Uint128,
+    /// 已计提未提取的服务费
+    pub accrued_fees: Uint128,
+    /// 已结算会话中尚未领取的推荐奖励
+    pub referral_rewards: Uint128,
+    /// 以上各项合计
+    pub liabilities: Uint128,
+    /// 余额是否足以支付全部应付资金
+    pub solvent: bool,
+}
+
+#[cw_serde]
+pub struct PendingAdminResponse {
+    pub pending_admin: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct ReferrerStatsResponse {
+    pub stats: ReferrerStats,
+    /// 已结算会话中可领取的推荐奖励
+    pub claimable: Uint128,
+    /// 尚未结算会话中的推荐奖励
+    pub pending: Uint128,
+}
+
 #[cw_serde]
 pub struct VersionResponse {
     pub contract_name: String,
     pub contract_version: String,
 }
+
+#[cw_serde]
+pub struct ClaimableReward {
+    pub session_id: String,
+    pub amount: Uint128,
+    /// 领取截止区块高度（含）
+    pub claim_deadline_height: u64,
+    pub expired: bool,
+}
+
+#[cw_serde]
+pub struct ClaimableResponse {
+    pub claims: Vec<ClaimableReward>,
+    /// 未过期的可领取总额
+    pub total: Uint128,
+}
+
+#[cw_serde]
+pub struct SettlementProgressResponse {
+    /// 当前会话ID，没有当前会话时为空
+    pub session_id: Option<String>,
+    /// 是否已完成结算
+    pub settled: bool,
+    /// 结算阶段，尚未开始或已完成时为空
+    pub stage: Option<SettlementStage>,
+    /// 中奖号码（计算完成后）
+    pub winning_number: Option<u16>,
+    /// 已处理的参与者数量
+    pub processed_participants: u32,
+    /// 参与者总数
+    pub participant_count: u32,
+    /// 已处理的候选号码数量
+    pub processed_numbers: u32,
+    /// 已累计的中奖人数
+    pub winners_count: u32,
+}
+
+#[cw_serde]
+pub struct NumberPopularity {
+    pub number: u16,
+    /// 投注数（投注码注数与投注票中出现次数之和）
+    pub bets: u32,
+}
+
+#[cw_serde]
+pub struct NumberPopularityResponse {
+    pub session_id: String,
+    /// 有人投注的号码，按号码升序
+    pub numbers: Vec<NumberPopularity>,
+    pub total_bets: u64,
+}
+
+#[cw_serde]
+pub struct WinnersResponse {
+    pub session_id: String,
+    /// 中奖记录，同一中奖者同一奖级合并为一条；会话尚未结算时为空
+    pub winners: Vec<Winner>,
+    /// 中奖人数
+    pub winners_count: u32,
+}
+
+/// 奖金或退款的领取状态
+#[cw_serde]
+pub enum ClaimStatus {
+    /// 没有应得金额（未中奖，或会话尚未结算）
+    None,
+    /// 待领取
+    Pending,
+    /// 已领取
+    Claimed,
+    /// 超过领取期限
+    Expired,
+    /// 会话已取消，投注待退款
+    Refundable,
+    /// 会话已取消，投注已退款
+    Refunded,
+}
+
+#[cw_serde]
+pub struct UserSessionRecord {
+    pub session_id: String,
+    pub cycle: u64,
+    pub bet_amount: Uint128,
+    /// 揭秘的投注码直方图 [(号码, 注数)]，未揭秘时为空
+    pub lucky_numbers: Vec<(u16, u32)>,
+    /// 揭秘的投注票，未揭秘时为空
+    pub tickets: Vec<Ticket>,
+    pub revealed: bool,
+    pub settled: bool,
+    pub cancelled: bool,
+    pub winning_number: Option<u16>,
+    /// 应得金额：奖金、未揭秘退款或取消会话的退款
+    pub prize: Uint128,
+    pub claim_status: ClaimStatus,
+    /// 本期中奖的奖级，会话结算后才有
+    pub winnings: Vec<UserWinning>,
+}
+
+#[cw_serde]
+pub struct UserWinning {
+    pub tier: PrizeTier,
+    /// 中奖注数
+    pub count: u32,
+    pub reward_amount: Uint128,
+}
+
+#[cw_serde]
+pub struct UserHistoryResponse {
+    pub address: String,
+    pub records: Vec<UserSessionRecord>,
+}
+
@@ -1,30 +1,60 @@
 use cosmwasm_std::{Deps, Env};
 use crate::error::ContractError;
-use crate::state::{LotteryPhase, CONFIG};
+use crate::state::{ClockAnchor, Config, LotteryPhase, ScheduleMode, CONFIG};
 
 /// 阶段管理器
+///
+/// 所有阶段计算都基于配置中的阶段时间表（见 `Config::schedule_at`）
 pub struct PhaseManager;
 
 impl PhaseManager {
     /// 获取当前阶段
-    pub fn get_current_phase(env: &Env) -> LotteryPhase {
-        LotteryPhase::from_block_height(env.block.height)
+    pub fn get_current_phase(config: &Config, env: &Env) -> LotteryPhase {
+        let schedule = config.schedule_at(&env.block);
+        schedule.phase_at(schedule.position(&env.block))
+    }
+    
+    /// 获取当前周期编号
+    /// 周期编号同时作为会话标识
+    pub fn get_current_cycle(config: &Config, env: &Env) -> u64 {
+        let schedule = config.schedule_at(&env.block);
+        schedule.cycle_at(schedule.position(&env.block))
+    }
+    
+    /// 获取当前区块在周期内的偏移量（区块数或秒，取决于调度模式）
+    pub fn get_phase_mod(config: &Config, env: &Env) -> u64 {
+        let schedule = config.schedule_at(&env.block);
+        schedule.offset_at(schedule.position(&env.block))
     }
     
     /// 检查是否在指定阶段
-    pub fn is_in_phase(env: &Env, phase: LotteryPhase) -> bool {
-        Self::get_current_phase(env) == phase
+    pub fn is_in_phase(config: &Config, env: &Env, phase: LotteryPhase) -> bool {
+        Self::get_current_phase(config, env) == phase
     }
     
-    /// 获取阶段剩余时间（区块数）
-    pub fn get_phase_remaining_blocks(env: &Env) -> u64 {
-        let current_height = env.block.height;
-        let phase_mod = current_height % 10000;
-        
-        match LotteryPhase::from_block_height(current_height) {
-            LotteryPhase::Commitment => 6000 - phase_mod,
-            LotteryPhase::Reveal => 9000 - phase_mod,
-            LotteryPhase::Settlement => 10000 - phase_mod,
+    /// 获取阶段剩余区块数
+    /// 按区块时间调度时由锚点以来的平均出块时间估算，无法估算时为 None
+    pub fn get_phase_remaining_blocks(config: &Config, env: &Env, anchor: Option<&ClockAnchor>) -> Option<u64> {
+        let schedule = config.schedule_at(&env.block);
+        match schedule.mode {
+            ScheduleMode::BlockHeight => Some(schedule.remaining_at(env.block.height)),
+            ScheduleMode::Timestamp => {
+                let seconds = schedule.remaining_at(env.block.time.seconds());
+                anchor?.seconds_to_blocks(&env.block, seconds)
+            }
+        }
+    }
+    
+    /// 获取阶段剩余秒数
+    /// 按区块高度调度时由锚点以来的平均出块时间估算，无法估算时为 None
+    pub fn get_phase_remaining_seconds(config: &Config, env: &Env, anchor: Option<&ClockAnchor>) -> Option<u64> {
+        let schedule = config.schedule_at(&env.block);
+        match schedule.mode {
+            ScheduleMode::BlockHeight => {
+                let blocks = schedule.remaining_at(env.block.height);
+                anchor?.blocks_to_seconds(&env.block, blocks)
+            }
+            ScheduleMode::Timestamp => Some(schedule.remaining_at(env.block.time.seconds())),
         }
     }
     
@@ -40,11 +70,11 @@ impl PhaseManager {
         }
         
         // 检查是否请求暂停且当前是新的承诺阶段
-        if config.pause_requested && Self::is_new_commitment_phase(env) {
+        if config.pause_requested && Self::is_new_commitment_phase(&config, env) {
             return Err(ContractError::ContractPaused);
         }
         
-        let current_phase = Self::get_current_phase(env);
+        let current_phase = Self::get_current_phase(&config, env);
         
         match operation {
             "place_bet" => Ok(current_phase == LotteryPhase::Commitment),
@@ -55,27 +85,29 @@ impl PhaseManager {
     }
     
     /// 检查是否是新周期的承诺阶段
-    pub fn is_new_commitment_phase(env: &Env) -> bool {
-        let phase_mod = env.block.height % 10000;
-        phase_mod == 0 // 新周期开始
+    pub fn is_new_commitment_phase(config: &Config, env: &Env) -> bool {
+        Self::get_phase_mod(config, env) == 0 // 新周期开始
     }
     
     /// 检查是否应该暂停（完成当前周期后）
     pub fn should_pause_after_cycle(deps: Deps, env: &Env) -> Result<bool, ContractError> {
         let config = CONFIG.load(deps.storage)?;
-        Ok(config.pause_requested && Self::is_new_commitment_phase(env))
+        Ok(config.pause_requested && Self::is_new_commitment_phase(&config, env))
     }
     
     /// 获取阶段信息
-    pub fn get_phase_info(env: &Env) -> PhaseInfo {
-        let current_phase = Self::get_current_phase(env);
-        let remaining_blocks = Self::get_phase_remaining_blocks(env);
+    pub fn get_phase_info(config: &Config, env: &Env, anchor: Option<&ClockAnchor>) -> PhaseInfo {
+        let current_phase = Self::get_current_phase(config, env);
         
         PhaseInfo {
             phase: current_phase,
-            remaining_blocks,
+            mode: config.schedule_at(&env.block).mode,
+            remaining_blocks: Self::get_phase_remaining_blocks(config, env, anchor),
+            remaining_seconds: Self::get_phase_remaining_seconds(config, env, anchor),
             block_height: env.block.height,
-            phase_mod: env.block.height % 10000,
+            block_time: env.block.time.seconds(),
+            phase_mod: Self::get_phase_mod(config, env),
+            cycle: Self::get_current_cycle(config, env),
         }
     }
 }
@@ -84,9 +116,13 @@ impl PhaseManager {
 #[derive(Debug, Clone)]
 pub struct PhaseInfo {
     pub phase: LotteryPhase,
-    pub remaining_blocks: u64,
+    pub mode: ScheduleMode,
+    pub remaining_blocks: Option<u64>,
+    pub remaining_seconds: Option<u64>,
     pub block_height: u64,
+    pub block_time: u64,
     pub phase_mod: u64,
+    pub cycle: u64,
 }
 
 impl PhaseInfo {
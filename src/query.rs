@@ -1,6 +1,14 @@
-use cosmwasm_std::{Deps, StdResult, Binary};
+use cosmwasm_std::{Addr, Deps, StdError, StdResult, Binary, Order, Uint128};
+use cw_storage_plus::Bound;
 use crate::msg::*;
-use crate::state::{LotteryPhase, LotteryResult, CONFIG, CURRENT_SESSION, COMMITMENTS, LOTTERY_HISTORY, STATS};
+use crate::phase_manager::PhaseManager;
+use crate::state::{CONFIG, CLOCK_ANCHOR, CURRENT_SESSION, COMMITMENTS, LOTTERY_HISTORY, STATS, CLAIMABLE, PRIZES, JACKPOT, ACCRUED_FEES, UNCLAIMED_PRIZES, PENDING_REFUNDS, REFERRAL_EARNINGS, REFERRAL_PAYABLE, REFERRER_STATS, PENDING_ADMIN, load_session, SETTLEMENT_PROGRESS, SETTLED_SESSIONS, SESSION_DETAILS, REFUNDS, USER_SESSIONS, WINNINGS, PrizeTier, Prize, participants};
+
+/// 分页查询默认返回的条数
+const DEFAULT_LIMIT: u32 = 30;
+
+/// 分页查询最多返回的条数
+const MAX_LIMIT: u32 = 100;
 
 /// 查询处理函数
 pub fn query(deps: Deps, env: cosmwasm_std::Env, msg: QueryMsg) -> StdResult<Binary> {
@@ -25,12 +33,12 @@ pub fn query(deps: Deps, env: cosmwasm_std::Env, msg: QueryMsg) -> StdResult<Bin
             let result = query_config(deps)?;
             cosmwasm_std::to_json_binary(&result)
         }
-        QueryMsg::GetLotteryHistory { limit, start_after } => {
-            let result = query_lottery_history(deps, limit, start_after)?;
+        QueryMsg::GetLotteryHistory { limit, start_after, order } => {
+            let result = query_lottery_history(deps, limit, start_after, order)?;
             cosmwasm_std::to_json_binary(&result)
         }
-        QueryMsg::GetParticipants {} => {
-            let result = query_participants(deps)?;
+        QueryMsg::GetParticipants { start_after, limit, order } => {
+            let result = query_participants(deps, start_after, limit, order)?;
             cosmwasm_std::to_json_binary(&result)
         }
         QueryMsg::GetStats {} => {
@@ -41,45 +49,84 @@ pub fn query(deps: Deps, env: cosmwasm_std::Env, msg: QueryMsg) -> StdResult<Bin
             let result = query_version()?;
             cosmwasm_std::to_json_binary(&result)
         }
+        QueryMsg::GetClaimable { address } => {
+            let result = query_claimable(deps, env, address)?;
+            cosmwasm_std::to_json_binary(&result)
+        }
+        QueryMsg::GetSettlementProgress {} => {
+            let result = query_settlement_progress(deps)?;
+            cosmwasm_std::to_json_binary(&result)
+        }
+        QueryMsg::GetUserHistory { address, start_after, limit } => {
+            let result = query_user_history(deps, env, address, start_after, limit)?;
+            cosmwasm_std::to_json_binary(&result)
+        }
+        QueryMsg::GetNumberPopularity { session_id } => {
+            let result = query_number_popularity(deps, session_id)?;
+            cosmwasm_std::to_json_binary(&result)
+        }
+        QueryMsg::GetWinners { session_id, start_after, limit } => {
+            let result = query_winners(deps, session_id, start_after, limit)?;
+            cosmwasm_std::to_json_binary(&result)
+        }
+        QueryMsg::GetTreasury {} => {
+            let result = query_treasury(deps, env)?;
+            cosmwasm_std::to_json_binary(&result)
+        }
+        QueryMsg::GetPendingAdmin {} => {
+            let result = PendingAdminResponse {
+                pending_admin: PENDING_ADMIN.may_load(deps.storage)?,
+            };
+            cosmwasm_std::to_json_binary(&result)
+        }
+        QueryMsg::GetReferrerStats { address } => {
+            let result = query_referrer_stats(deps, address)?;
+            cosmwasm_std::to_json_binary(&result)
+        }
     }
 }
 
 /// 查询当前彩票会话
 pub fn query_current_session(deps: Deps, env: cosmwasm_std::Env) -> StdResult<CurrentSessionResponse> {
-    let phase = LotteryPhase::from_block_height(env.block.height);
+    let config = CONFIG.load(deps.storage)?;
+    let phase = PhaseManager::get_current_phase(&config, &env);
     let session = CURRENT_SESSION.may_load(deps.storage)?;
-    
+
     Ok(CurrentSessionResponse {
         session: session.map(|s| crate::msg::LotterySession {
             session_id: s.session_id,
+            cycle: s.cycle,
             phase: s.phase,
             total_pool: s.total_pool,
             service_fee: s.service_fee,
-            participants: s.participants,
+            participant_count: s.participant_count,
+            revealed_count: s.revealed_count,
             created_height: s.created_height,
             winning_number: s.winning_number,
             settled: s.settled,
+            non_reveal_policy: s.non_reveal_policy,
+            randomness_source: s.randomness_source,
+            prize_structure: s.prize_structure,
+            prize_tiers: s.prize_tiers,
+            ticket_prices: s.ticket_prices,
+            beacon_request: s.beacon_request,
+            cancelled: s.cancelled,
         }),
         phase,
         block_height: env.block.height,
+        jackpot: JACKPOT.may_load(deps.storage)?.unwrap_or_default(),
     })
 }
 
 /// 查询参与者信息
 pub fn query_participant_info(deps: Deps, participant: String) -> StdResult<ParticipantResponse> {
     let participant_addr = cosmwasm_std::Addr::unchecked(&participant);
-    let commitment = COMMITMENTS.may_load(deps.storage, &participant_addr)?;
-    
-    let participant_info = if let Some(_commitment) = commitment {
-        // 从当前会话中获取完整的参与者信息
-        let session = CURRENT_SESSION.may_load(deps.storage)?;
-        let participant = session.and_then(|s| {
-            s.participants.into_iter()
-                .find(|p| p.address == participant_addr)
-        });
-        participant
-    } else {
-        None
+    // 从当前会话中获取完整的参与者信息
+    let participant_info = match CURRENT_SESSION.may_load(deps.storage)? {
+        Some(s) if COMMITMENTS.has(deps.storage, (s.session_id.as_str(), &participant_addr)) => {
+            participants().may_load(deps.storage, (s.session_id.as_str(), &participant_addr))?
+        }
+        _ => None,
     };
     
     Ok(ParticipantResponse {
@@ -98,13 +145,19 @@ pub fn query_lottery_result(deps: Deps, session_id: String) -> StdResult<Lottery
 
 /// 查询当前阶段
 pub fn query_current_phase(deps: Deps, env: cosmwasm_std::Env) -> StdResult<PhaseResponse> {
-    // 🎯 直接调用 query_current_session 获取完整信息
-    let session_response = query_current_session(deps, env.clone())?;
+    let config = CONFIG.load(deps.storage)?;
+    let anchor = CLOCK_ANCHOR.may_load(deps.storage)?;
+    let phase_info = PhaseManager::get_phase_info(&config, &env, anchor.as_ref());
     
     Ok(PhaseResponse {
-        phase: session_response.phase,
-        block_height: session_response.block_height,
-        phase_mod: env.block.height % 10000,
+        phase: phase_info.phase,
+        block_height: phase_info.block_height,
+        phase_mod: phase_info.phase_mod,
+        cycle: phase_info.cycle,
+        mode: phase_info.mode,
+        block_time: phase_info.block_time,
+        remaining_blocks: phase_info.remaining_blocks,
+        remaining_seconds: phase_info.remaining_seconds,
     })
 }
 
@@ -118,45 +171,75 @@ pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
 }
 
 /// 查询彩票历史
+/// 按周期编号分页，默认从新到旧
 pub fn query_lottery_history(
     deps: Deps,
     limit: Option<u32>,
-    _start_after: Option<String>,
+    start_after: Option<u64>,
+    order: Option<Order>,
 ) -> StdResult<LotteryHistoryResponse> {
-    let limit = limit.unwrap_or(30).min(100) as usize;
-    let _results: Vec<LotteryResult> = Vec::new();
-    
-    // 这里简化实现，实际应该使用分页查询
-    let all_results: StdResult<Vec<_>> = LOTTERY_HISTORY
-        .range(deps.storage, None, None, cosmwasm_std::Order::Descending)
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let order = order.unwrap_or(Order::Descending);
+    let (min, max) = page_bounds(start_after.map(Bound::exclusive), order);
+
+    let results = SETTLED_SESSIONS
+        .range(deps.storage, min, max, order)
         .take(limit)
-        .collect();
-    
-    let all_results = all_results?;
-    let total = all_results.len() as u32;
-    
+        .map(|item| {
+            let (_, session_id) = item?;
+            LOTTERY_HISTORY.load(deps.storage, session_id)
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let total = STATS.load(deps.storage)?.total_sessions as u32;
+
     Ok(LotteryHistoryResponse {
-        results: all_results.into_iter().map(|(_, result)| result).collect(),
+        results,
         total,
     })
 }
 
-/// 查询参与者列表
-pub fn query_participants(deps: Deps) -> StdResult<ParticipantsResponse> {
-    let session = CURRENT_SESSION.may_load(deps.storage)?;
-    
-    let participants = if let Some(session) = session {
-        session.participants
-    } else {
-        vec![]
+/// 查询当前会话的参与者列表
+/// 按地址分页，默认升序
+pub fn query_participants(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order: Option<Order>,
+) -> StdResult<ParticipantsResponse> {
+    let session = match CURRENT_SESSION.may_load(deps.storage)? {
+        Some(session) => session,
+        None => return Ok(ParticipantsResponse {
+            participants: vec![],
+            total: 0,
+        }),
     };
-    
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let order = order.unwrap_or(Order::Ascending);
+    let start_after = start_after.map(Addr::unchecked);
+    let (min, max) = page_bounds(start_after.as_ref().map(Bound::exclusive), order);
+
+    let participants = participants()
+        .prefix(session.session_id.as_str())
+        .range(deps.storage, min, max, order)
+        .take(limit)
+        .map(|item| item.map(|(_, participant)| participant))
+        .collect::<StdResult<Vec<_>>>()?;
+
     Ok(ParticipantsResponse {
-        participants: participants.clone(),
-        total: participants.len() as u32,
+        participants,
+        total: session.participant_count,
     })
 }
 
+/// 分页边界：升序时游标为下界，降序时为上界
+fn page_bounds<T>(start_after: Option<T>, order: Order) -> (Option<T>, Option<T>) {
+    match order {
+        Order::Ascending => (start_after, None),
+        Order::Descending => (None, start_after),
+    }
+}
+
 /// 查询统计信息
 pub fn query_stats(deps: Deps) -> StdResult<StatsResponse> {
     let stats = STATS.load(deps.storage)?;
@@ -170,6 +253,64 @@ pub fn query_stats(deps: Deps) -> StdResult<StatsResponse> {
     })
 }
 
+/// 查询资金台账
+/// 合约余额应等于当前会话资金、待领取奖金、待退款、累积奖池、已计提服务费和待领取推荐奖励之和
+pub fn query_treasury(deps: Deps, env: cosmwasm_std::Env) -> StdResult<TreasuryResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let balance = config.bet_asset.query_balance(&deps.querier, &env.contract.address)?;
+
+    // 已结算或已取消的会话资金已转入其他台账
+    let active_pool = match CURRENT_SESSION.may_load(deps.storage)? {
+        Some(session) if !session.settled && !session.cancelled => session.total_pool + session.pending_deposits,
+        _ => Uint128::zero(),
+    };
+    let unclaimed_prizes = UNCLAIMED_PRIZES.may_load(deps.storage)?.unwrap_or_default();
+    let pending_refunds = PENDING_REFUNDS.may_load(deps.storage)?.unwrap_or_default();
+    let jackpot = JACKPOT.may_load(deps.storage)?.unwrap_or_default();
+    let accrued_fees = ACCRUED_FEES.may_load(deps.storage)?.unwrap_or_default();
+    let referral_rewards = REFERRAL_PAYABLE.may_load(deps.storage)?.unwrap_or_default();
+    let liabilities = active_pool + unclaimed_prizes + pending_refunds + jackpot + accrued_fees + referral_rewards;
+
+    Ok(TreasuryResponse {
+        balance,
+        active_pool,
+        unclaimed_prizes,
+        pending_refunds,
+        jackpot,
+        accrued_fees,
+        referral_rewards,
+        liabilities,
+        solvent: balance >= liabilities,
+    })
+}
+
+/// 查询推荐人统计
+/// 推荐奖励按所在会话的状态分为可领取（已结算）和待结算，已取消会话的推荐奖励不计入
+pub fn query_referrer_stats(deps: Deps, address: String) -> StdResult<ReferrerStatsResponse> {
+    let addr = deps.api.addr_validate(&address)?;
+    let mut stats = REFERRER_STATS.may_load(deps.storage, &addr)?.unwrap_or_default();
+
+    let mut claimable = Uint128::zero();
+    let mut pending = Uint128::zero();
+    let mut voided = Uint128::zero();
+    for item in REFERRAL_EARNINGS.prefix(&addr).range(deps.storage, None, None, Order::Ascending) {
+        let (session_id, amount) = item?;
+        match load_session(deps.storage, &session_id)? {
+            Some(session) if session.settled => claimable += amount,
+            Some(session) if session.cancelled => voided += amount,
+            _ => pending += amount,
+        }
+    }
+    // 尚未领取的作废部分还计在统计中，查询时一并扣除
+    stats.earned_fees = stats.earned_fees.saturating_sub(voided);
+
+    Ok(ReferrerStatsResponse {
+        stats,
+        claimable,
+        pending,
+    })
+}
+
 /// 查询合约版本
 pub fn query_version() -> StdResult<VersionResponse> {
     Ok(VersionResponse {
@@ -178,3 +319,217 @@ pub fn query_version() -> StdResult<VersionResponse> {
     })
 }
 
+/// 查询地址的待领取奖金
+pub fn query_claimable(deps: Deps, env: cosmwasm_std::Env, address: String) -> StdResult<ClaimableResponse> {
+    let addr = cosmwasm_std::Addr::unchecked(&address);
+    let entries = CLAIMABLE
+        .prefix(&addr)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut claims = Vec::with_capacity(entries.len());
+    let mut total = Uint128::zero();
+    for (session_id, amount) in entries {
+        // 尚未完成结算的会话不能领取
+        let deadline = match LOTTERY_HISTORY.may_load(deps.storage, session_id.clone())? {
+            Some(result) => result.claim_deadline_height,
+            None => continue,
+        };
+        let expired = env.block.height > deadline;
+        if !expired {
+            total += amount;
+        }
+        claims.push(ClaimableReward {
+            session_id,
+            amount,
+            claim_deadline_height: deadline,
+            expired,
+        });
+    }
+
+    Ok(ClaimableResponse {
+        claims,
+        total,
+    })
+}
+
+/// 查询当前会话的结算进度
+pub fn query_settlement_progress(deps: Deps) -> StdResult<SettlementProgressResponse> {
+    let session = match CURRENT_SESSION.may_load(deps.storage)? {
+        Some(session) => session,
+        None => return Ok(SettlementProgressResponse {
+            session_id: None,
+            settled: false,
+            stage: None,
+            winning_number: None,
+            processed_participants: 0,
+            participant_count: 0,
+            processed_numbers: 0,
+            winners_count: 0,
+        }),
+    };
+    let progress = SETTLEMENT_PROGRESS.may_load(deps.storage)?
+        .filter(|progress| progress.session_id == session.session_id);
+    let winners_count = match &progress {
+        Some(progress) => progress.winners_count,
+        None => LOTTERY_HISTORY.may_load(deps.storage, session.session_id.clone())?
+            .map(|result| result.winners_count)
+            .unwrap_or(0),
+    };
+
+    Ok(SettlementProgressResponse {
+        session_id: Some(session.session_id),
+        settled: session.settled,
+        stage: progress.as_ref().map(|progress| progress.stage),
+        winning_number: session.winning_number,
+        processed_participants: progress.as_ref()
+            .map(|progress| progress.processed_participants)
+            .unwrap_or(if session.settled { session.participant_count } else { 0 }),
+        participant_count: session.participant_count,
+        processed_numbers: progress.as_ref().map(|progress| progress.number_cursor).unwrap_or(0),
+        winners_count,
+    })
+}
+
+/// 查询会话中各号码的投注数
+pub fn query_number_popularity(deps: Deps, session_id: String) -> StdResult<NumberPopularityResponse> {
+    let numbers: Vec<NumberPopularity> = participants().idx.number
+        .popularity(deps.storage, &session_id)?
+        .into_iter()
+        .map(|(number, bets)| NumberPopularity { number, bets })
+        .collect();
+    let total_bets = numbers.iter().map(|popularity| popularity.bets as u64).sum();
+
+    Ok(NumberPopularityResponse {
+        session_id,
+        numbers,
+        total_bets,
+    })
+}
+
+/// 查询会话的中奖记录
+/// 按 (中奖者地址, 奖级) 分页，只返回已结算会话的记录
+pub fn query_winners(
+    deps: Deps,
+    session_id: String,
+    start_after: Option<(String, u8)>,
+    limit: Option<u32>,
+) -> StdResult<WinnersResponse> {
+    let result = match LOTTERY_HISTORY.may_load(deps.storage, session_id.clone())? {
+        Some(result) => result,
+        None => return Ok(WinnersResponse {
+            session_id,
+            winners: vec![],
+            winners_count: 0,
+        }),
+    };
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after.map(|(address, level)| (Addr::unchecked(address), level));
+    let winners = WINNINGS
+        .sub_prefix(session_id.as_str())
+        .range(deps.storage, start_after.as_ref().map(|(address, level)| Bound::exclusive((address, *level))), None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, winner)| winner))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(WinnersResponse {
+        session_id,
+        winners,
+        winners_count: result.winners_count,
+    })
+}
+
+/// 查询地址的投注历史
+/// 按周期编号从新到旧分页，包含投注、揭秘、中奖和领取状态
+pub fn query_user_history(
+    deps: Deps,
+    env: cosmwasm_std::Env,
+    address: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<UserHistoryResponse> {
+    let addr = Addr::unchecked(&address);
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let current = CURRENT_SESSION.may_load(deps.storage)?;
+
+    let sessions = USER_SESSIONS
+        .prefix(&addr)
+        .range(deps.storage, None, start_after.map(Bound::exclusive), Order::Descending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut records = Vec::with_capacity(sessions.len());
+    for (cycle, session_id) in sessions {
+        let session = match &current {
+            Some(session) if session.session_id == session_id => session.clone(),
+            _ => SESSION_DETAILS.load(deps.storage, session_id.clone())?,
+        };
+        let participant = participants().load(deps.storage, (session_id.as_str(), &addr))?;
+        let result = LOTTERY_HISTORY.may_load(deps.storage, session_id.clone())?;
+        let winnings = match &result {
+            Some(_) => WINNINGS
+                .prefix((session_id.as_str(), &addr))
+                .range(deps.storage, None, None, Order::Ascending)
+                .map(|item| {
+                    let (level, winner) = item?;
+                    Ok(UserWinning {
+                        tier: PrizeTier::from_level(level).ok_or_else(|| StdError::generic_err("Invalid prize tier"))?,
+                        count: winner.count,
+                        reward_amount: winner.reward_amount,
+                    })
+                })
+                .collect::<StdResult<Vec<_>>>()?,
+            None => vec![],
+        };
+
+        let (prize, claim_status) = if session.cancelled {
+            let status = if REFUNDS.has(deps.storage, (session_id.as_str(), &addr)) {
+                ClaimStatus::Refunded
+            } else {
+                ClaimStatus::Refundable
+            };
+            (participant.bet_amount, status)
+        } else {
+            // 应发金额在结算完成后生效，领取状态记录在应发金额中，不依赖可能已删除的待领取余额
+            let prize = match &result {
+                Some(_) => PRIZES.may_load(deps.storage, (&addr, session_id.as_str()))?.unwrap_or_default(),
+                None => Prize::default(),
+            };
+            match result {
+                Some(result) if !prize.amount.is_zero() => {
+                    let status = if prize.claimed {
+                        ClaimStatus::Claimed
+                    } else if env.block.height <= result.claim_deadline_height {
+                        ClaimStatus::Pending
+                    } else {
+                        ClaimStatus::Expired
+                    };
+                    (prize.amount, status)
+                }
+                _ => (Uint128::zero(), ClaimStatus::None),
+            }
+        };
+
+        records.push(UserSessionRecord {
+            session_id,
+            cycle,
+            bet_amount: participant.bet_amount,
+            lucky_numbers: participant.lucky_numbers,
+            tickets: participant.tickets,
+            revealed: participant.revealed,
+            settled: session.settled,
+            cancelled: session.cancelled,
+            winning_number: session.winning_number,
+            prize,
+            claim_status,
+            winnings,
+        });
+    }
+
+    Ok(UserHistoryResponse {
+        address,
+        records,
+    })
+}
+
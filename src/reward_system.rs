@@ -1,17 +1,32 @@
-use cosmwasm_std::{Uint128, Fraction};
+use cosmwasm_std::{Addr, Uint128, Fraction};
 use crate::error::ContractError;
-use crate::state::{Participant, Winner};
+use crate::state::{Participant, Winner, Payout, NonRevealPolicy, NonRevealOutcome, PrizeStructure, PrizeTier, TierPrize, TicketPrices};
 use crate::lottery_logic::LotteryLogic;
 
 /// 奖励系统管理器
 pub struct RewardSystem;
 
 impl RewardSystem {
-    /// 计算中奖者
+    /// 计算直选中奖者
     pub fn calculate_winners(
         participants: &[Participant],
         winning_number: u16,
     ) -> Result<Vec<Winner>, ContractError> {
+        Self::calculate_winners_with_tiers(participants, winning_number, &TierPrize::default_tiers(), &TicketPrices::default())
+    }
+    
+    /// 按启用的奖级计算中奖者
+    /// 
+    /// 每个投注码只计其满足的最高奖级，投注码的投注额为1个基础代币；
+    /// 投注票按类型兑奖，复式票的每个号码分别兑奖，投注额为该类型的单价。
+    /// 同一参与者同一奖级的中奖合并为一条记录，记录中奖注数和投注额
+    pub fn calculate_winners_with_tiers(
+        participants: &[Participant],
+        winning_number: u16,
+        tiers: &[TierPrize],
+        prices: &TicketPrices,
+    ) -> Result<Vec<Winner>, ContractError> {
+        let enabled: Vec<PrizeTier> = tiers.iter().map(|prize| prize.tier).collect();
         let mut winners = Vec::new();
         
         for participant in participants {
@@ -19,108 +34,290 @@ impl RewardSystem {
                 continue; // 跳过未揭秘的参与者
             }
             
-            let match_count = LotteryLogic::count_matches(
-                &participant.lucky_numbers, 
-                winning_number
-            );
+            let code_wins = participant.lucky_numbers.iter()
+                .filter_map(|&(number, count)| {
+                    LotteryLogic::match_tier(number, winning_number, &enabled)
+                        .map(|tier| (tier, count, Uint128::from(count)))
+                });
+            let ticket_wins = participant.tickets.iter()
+                .flat_map(|ticket| {
+                    let price = prices.unit_price(ticket.bet_type);
+                    LotteryLogic::ticket_tiers(ticket, winning_number, &enabled)
+                        .into_iter()
+                        .map(move |tier| (tier, 1, price))
+                });
             
-            // 用户投注数字中有几个中奖号码就中奖几次
-            if match_count > 0 {
-                let level = LotteryLogic::determine_winner_level(match_count)?;
-                
-                // 为每个匹配创建一个中奖记录
-                for _ in 0..match_count {
-                    let winner = Winner {
-                        address: participant.address.clone(),
-                        level,
-                        match_count: 1, // 每个中奖记录代表一次中奖
-                        reward_amount: Uint128::zero(), // 稍后计算
-                    };
-                    
-                    winners.push(winner);
-                }
-            }
+            winners.extend(Self::merge_wins(&participant.address, code_wins.chain(ticket_wins)));
         }
         
         Ok(winners)
     }
     
+    /// 计算参与者投注的单个号码的中奖记录
+    /// 
+    /// 分批结算时按号码索引逐个号码兑奖，同一参与者各号码的中奖记录按奖级合并后
+    /// 与 `calculate_winners_with_tiers` 的结果一致
+    pub fn calculate_number_winners(
+        participant: &Participant,
+        number: u16,
+        winning_number: u16,
+        tiers: &[TierPrize],
+        prices: &TicketPrices,
+    ) -> Vec<Winner> {
+        if !participant.revealed {
+            return vec![];
+        }
+        let enabled: Vec<PrizeTier> = tiers.iter().map(|prize| prize.tier).collect();
+        
+        let count = participant.lucky_numbers.iter()
+            .find(|&&(n, _)| n == number)
+            .map(|&(_, count)| count)
+            .unwrap_or(0);
+        let code_wins = LotteryLogic::match_tier(number, winning_number, &enabled)
+            .filter(|_| count > 0)
+            .map(|tier| (tier, count, Uint128::from(count)));
+        let ticket_wins = participant.tickets.iter()
+            .flat_map(|ticket| ticket.numbers.iter()
+                .filter(|&&n| n == number)
+                .filter_map(|&n| LotteryLogic::bet_tier(ticket.bet_type, n, winning_number, &enabled))
+                .map(|tier| (tier, 1, prices.unit_price(ticket.bet_type))));
+        
+        Self::merge_wins(&participant.address, code_wins.into_iter().chain(ticket_wins))
+    }
+    
+    /// 将 (奖级, 注数, 投注额) 按奖级合并为中奖记录，保持奖级首次出现的顺序
+    fn merge_wins(address: &Addr, wins: impl Iterator<Item = (PrizeTier, u32, Uint128)>) -> Vec<Winner> {
+        let mut records: Vec<Winner> = Vec::new();
+        for (tier, count, stake) in wins {
+            let level = tier.level();
+            match records.iter_mut().find(|w| w.level == level) {
+                Some(record) => {
+                    record.count += count;
+                    record.stake += stake;
+                }
+                None => records.push(Winner {
+                    address: address.clone(),
+                    level,
+                    count,
+                    stake,
+                    reward_amount: Uint128::zero(), // 稍后计算
+                }),
+            }
+        }
+        records
+    }
+    
+    /// 按默认奖金结构分配奖金（每个基础代币的中奖投注固定获得800个基础代币）
+    pub fn distribute_rewards(
+        winners: &mut [Winner],
+        total_reward_pool: Uint128,
+    ) -> Result<Vec<Winner>, ContractError> {
+        Self::distribute_rewards_with_structure(winners, total_reward_pool, &PrizeStructure::default())
+    }
+    
     /// 奖金分配算法
     /// 
     /// ## 分配规则
     /// 
-    /// 本系统采用公平的奖金分配机制，确保所有中奖者获得相同的奖金金额：
+    /// 奖金总额由会话的奖金结构决定，中奖者按中奖投注额比例分得奖金，
+    /// 投注单价为10的直选票中奖所得是1个基础代币投注码的10倍：
+    /// 
+    /// ### 1. 固定倍数（`FixedMultiplier`）
+    /// - **触发条件：** 奖金池金额 ≥ 中奖投注额 × 倍数
+    /// - **分配方式：** 每个基础代币的中奖投注获得 倍数 个基础代币
+    /// - **兜底策略：** 奖金池不足时整个奖金池按中奖投注额比例分配
     /// 
-    /// ### 1. 固定奖金分配（优先策略）
-    /// - **触发条件：** 奖金池金额 ≥ 中奖者数量 × 800个基础代币
-    /// - **分配方式：** 每名中奖者固定获得800个基础代币
-    /// - **优势：** 保证中奖者获得稳定的奖金
+    /// ### 2. 奖金池比例（`PoolPercentage`）
+    /// - **分配方式：** 奖金池 × 比例 按中奖投注额比例分配
     /// 
-    /// ### 2. 平分奖金分配（兜底策略）
-    /// - **触发条件：** 奖金池金额 < 中奖者数量 × 800个基础代币
-    /// - **分配方式：** 所有中奖者平分奖金池
-    /// - **计算方式：** 使用整数除法，确保公平分配
-    /// - **余数处理：** 余数部分保留在合约资金池中
+    /// ### 3. 彩池制（`PariMutuel`）
+    /// - **分配方式：** 整个奖金池按中奖投注额比例分配
+    /// 
+    /// 按比例分配时向下取整，余数与未分配部分滚入累积奖池
     /// 
     /// ## 分配示例
     /// 
-    /// ### 示例1：固定奖金分配
-    /// - 奖金池：10,000个代币
-    /// - 中奖者：5人
+    /// ### 示例1：固定倍数 800
+    /// - 奖金池：10,000个代币，中奖投注：5注，每注1个代币
     /// - 计算：5 × 800 = 4,000 < 10,000 ✅
-    /// - 结果：每人获得800个代币，剩余6,000个代币保留在资金池
-    /// 
-    /// ### 示例2：平分奖金分配
-    /// - 奖金池：1,500个代币
-    /// - 中奖者：3人
-    /// - 计算：3 × 800 = 2,400 > 1,500 ❌
-    /// - 结果：每人获得500个代币（1,500 ÷ 3 = 500），余数0
+    /// - 结果：每注获得800个代币，剩余6,000个代币滚入累积奖池
     /// 
-    /// ### 示例3：有余数的平分分配
-    /// - 奖金池：1,000个代币
-    /// - 中奖者：3人
+    /// ### 示例2：固定倍数 800，奖金池不足
+    /// - 奖金池：1,000个代币，中奖投注：3注，每注1个代币
     /// - 计算：3 × 800 = 2,400 > 1,000 ❌
-    /// - 结果：每人获得333个代币（1,000 ÷ 3 = 333），余数1个代币保留在资金池
+    /// - 结果：每注获得333个代币（1,000 × 1/3 = 333），余数1个代币滚入累积奖池
     /// 
-    /// ## 重要说明
-    /// 
-    /// ⚠️ **理性投注提醒：** 当资金池不足以分配固定奖金时，系统将采用平分机制。
-    /// 请用户理性投注，了解奖金分配规则，避免因资金池不足导致的奖金减少。
-    /// 
-    /// 💡 **余数处理：** 平分分配产生的余数将永久保留在合约资金池中，
-    /// 这是确保分配公平性的必要设计，所有中奖者将获得完全相同的奖金金额。
-    pub fn distribute_rewards(
-        winners: &mut Vec<Winner>,
+    /// ### 示例3：彩池制，不同投注单价
+    /// - 奖金池：1,100个代币，中奖投注：1个代币的投注码和单价10的直选票
+    /// - 结果：投注码获得100个代币，直选票获得1,000个代币
+    pub fn distribute_rewards_with_structure(
+        winners: &mut [Winner],
         total_reward_pool: Uint128,
+        structure: &PrizeStructure,
     ) -> Result<Vec<Winner>, ContractError> {
         // 如果没有中奖者，直接返回空列表
         if winners.is_empty() {
             return Ok(vec![]);
         }
         
-        let winner_count = Uint128::from(winners.len() as u128);
-        let fixed_reward_per_winner = Uint128::from(800u128); // 固定奖金：800个基础代币
-        let total_fixed_rewards = winner_count * fixed_reward_per_winner;
+        Self::distribute_by_stake(winners.iter_mut(), total_reward_pool, structure)?;
         
-        // 计算每个中奖者应得的奖金金额
-        let reward_per_winner = if total_reward_pool >= total_fixed_rewards {
-            // 情况1：奖金池充足，使用固定奖金分配
-            // 每名中奖者获得800个基础代币
-            fixed_reward_per_winner
-        } else {
-            // 情况2：奖金池不足，使用平分分配
-            // 所有中奖者平分奖金池，使用整数除法确保公平
-            // 余数部分将保留在合约资金池中
-            total_reward_pool / winner_count
+        Ok(winners.to_vec())
+    }
+    
+    /// 分奖级分配奖金
+    /// 
+    /// 每个奖级按比例分得奖金池的一部分，由该奖级的中奖者按奖金结构和中奖投注额比例分配；
+    /// 固定倍数结构下奖级可设置自己的倍数。未分配的比例与各奖级的剩余滚入累积奖池
+    pub fn distribute_tiered_rewards(
+        winners: &mut [Winner],
+        total_reward_pool: Uint128,
+        structure: &PrizeStructure,
+        tiers: &[TierPrize],
+    ) -> Result<Vec<Winner>, ContractError> {
+        for prize in tiers {
+            let level = prize.tier.level();
+            let total_stake: Uint128 = winners.iter()
+                .filter(|w| w.level == level)
+                .map(|w| w.stake)
+                .sum();
+            let budget = Self::tier_prize_budget(prize, total_stake, total_reward_pool, structure)?;
+            for winner in winners.iter_mut().filter(|w| w.level == level) {
+                winner.reward_amount = Self::stake_reward(budget, winner.stake, total_stake);
+            }
+        }
+        
+        Ok(winners.to_vec())
+    }
+    
+    /// 奖级的奖金总额：奖级按比例分得奖金池的一部分，再按奖金结构和中奖投注额确定，
+    /// 固定倍数结构下奖级设置的倍数优先
+    pub fn tier_prize_budget(
+        prize: &TierPrize,
+        total_stake: Uint128,
+        total_reward_pool: Uint128,
+        structure: &PrizeStructure,
+    ) -> Result<Uint128, ContractError> {
+        let tier_pool = total_reward_pool.multiply_ratio(
+            prize.pool_share.numerator(),
+            prize.pool_share.denominator()
+        );
+        let tier_structure = match (structure, prize.multiplier) {
+            (PrizeStructure::FixedMultiplier { .. }, Some(multiplier)) => {
+                PrizeStructure::FixedMultiplier { multiplier }
+            }
+            _ => structure.clone(),
+        };
+        Self::tier_budget(total_stake, tier_pool, &tier_structure)
+    }
+    
+    /// 按中奖投注额比例分配奖金
+    fn distribute_by_stake<'a>(
+        winners: impl Iterator<Item = &'a mut Winner>,
+        total_reward_pool: Uint128,
+        structure: &PrizeStructure,
+    ) -> Result<(), ContractError> {
+        let winners: Vec<&mut Winner> = winners.collect();
+        let total_stake: Uint128 = winners.iter().map(|w| w.stake).sum();
+        let budget = Self::tier_budget(total_stake, total_reward_pool, structure)?;
+        for winner in winners {
+            winner.reward_amount = Self::stake_reward(budget, winner.stake, total_stake);
+        }
+        Ok(())
+    }
+    
+    /// 按奖金结构计算奖级的奖金总额
+    fn tier_budget(
+        total_stake: Uint128,
+        total_reward_pool: Uint128,
+        structure: &PrizeStructure,
+    ) -> Result<Uint128, ContractError> {
+        // 无人中奖的奖级不分配奖金
+        if total_stake.is_zero() {
+            return Ok(Uint128::zero());
+        }
+        let budget = match structure {
+            PrizeStructure::FixedMultiplier { multiplier } => {
+                let total_fixed_rewards = total_stake
+                    .checked_mul(*multiplier)
+                    .map_err(|_| ContractError::RewardCalculationError)?;
+                // 奖金池不足时以整个奖金池为限
+                total_fixed_rewards.min(total_reward_pool)
+            }
+            PrizeStructure::PoolPercentage { rate } => {
+                total_reward_pool.multiply_ratio(rate.numerator(), rate.denominator())
+            }
+            PrizeStructure::PariMutuel => total_reward_pool,
         };
         
-        // 更新所有中奖者的奖金金额
-        // 注意：所有中奖者将获得完全相同的奖金金额，确保分配公平性
-        for winner in winners.iter_mut() {
-            winner.reward_amount = reward_per_winner;
+        Ok(budget)
+    }
+    
+    /// 中奖者按投注额占奖级中奖投注额的比例分得的奖金，向下取整
+    pub fn stake_reward(budget: Uint128, stake: Uint128, total_stake: Uint128) -> Uint128 {
+        if total_stake.is_zero() {
+            return Uint128::zero();
         }
+        budget.multiply_ratio(stake, total_stake)
+    }
+    
+    /// 处理未揭秘的参与者
+    /// 
+    /// 每名未揭秘参与者的投注按会话实际服务费比例扣除服务费后，按策略没收或部分退还；
+    /// 承诺押金无论采用何种策略均罚没并入奖金池。
+    /// 返回处理结果以及需要记入待领取余额的退款
+    pub fn settle_non_revealers(
+        participants: &[Participant],
+        policy: &NonRevealPolicy,
+        total_pool: Uint128,
+        service_fee: Uint128,
+    ) -> Result<(NonRevealOutcome, Vec<Payout>), ContractError> {
+        let mut outcome = NonRevealOutcome {
+            policy: policy.clone(),
+            non_revealed_count: 0,
+            non_revealed_amount: Uint128::zero(),
+            forfeited_to_pool: Uint128::zero(),
+            forfeited_to_treasury: Uint128::zero(),
+            refunded: Uint128::zero(),
+            slashed_deposits: Uint128::zero(),
+        };
+        let mut refunds = Vec::new();
         
-        Ok(winners.clone())
+        for participant in participants.iter().filter(|p| !p.revealed) {
+            // 扣除服务费后的净投注，向下取整保证总和不超过奖金池
+            let net_stake = participant.bet_amount.multiply_ratio(
+                total_pool - service_fee,
+                total_pool
+            );
+            
+            outcome.non_revealed_count += 1;
+            outcome.non_revealed_amount += participant.bet_amount;
+            outcome.slashed_deposits += participant.deposit;
+            
+            match policy {
+                NonRevealPolicy::ForfeitToPool => {
+                    outcome.forfeited_to_pool += net_stake;
+                }
+                NonRevealPolicy::ForfeitToTreasury => {
+                    outcome.forfeited_to_treasury += net_stake;
+                }
+                NonRevealPolicy::PartialRefund { penalty_rate } => {
+                    let penalty = net_stake.multiply_ratio(
+                        penalty_rate.numerator(),
+                        penalty_rate.denominator()
+                    );
+                    let refund = net_stake - penalty;
+                    outcome.forfeited_to_pool += penalty;
+                    outcome.refunded += refund;
+                    refunds.push(Payout {
+                        address: participant.address.clone(),
+                        amount: refund,
+                    });
+                }
+            }
+        }
+        
+        Ok((outcome, refunds))
     }
     
     /// 计算奖金池分配
@@ -158,13 +355,16 @@ impl RewardSystem {
         let mut level_counts = std::collections::HashMap::new();
         let mut total_rewards = Uint128::zero();
         
+        let mut total_winners = 0;
+        
         for winner in winners {
-            *level_counts.entry(winner.level).or_insert(0) += 1;
+            *level_counts.entry(winner.level).or_insert(0) += winner.count;
+            total_winners += winner.count;
             total_rewards += winner.reward_amount;
         }
         
         WinnerStatistics {
-            total_winners: winners.len() as u32,
+            total_winners,
             level_counts,
             total_rewards,
         }
@@ -182,6 +382,7 @@ pub struct RewardDistribution {
 /// 中奖统计信息
 #[derive(Debug, Clone)]
 pub struct WinnerStatistics {
+    /// 中奖注数
     pub total_winners: u32,
     pub level_counts: std::collections::HashMap<u8, u32>,
     pub total_rewards: Uint128,
@@ -1,49 +1,634 @@
-use cosmwasm_std::{Addr, Uint128, Decimal, Timestamp};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{to_json_binary, Addr, BankMsg, BlockInfo, Coin, CosmosMsg, HexBinary, QuerierWrapper, Uint128, Decimal, Timestamp, Order, StdResult, Storage, WasmMsg};
+use cw20::{BalanceResponse as Cw20BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+use cw_storage_plus::{Bound, Index, IndexList, IndexedMap, Item, KeyDeserialize, Map};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// 系统配置
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
-    /// 管理员地址
-    pub admin: Addr,
+    /// 管理员地址，放弃管理权后为空
+    pub admin: Option<Addr>,
     /// 服务费率 (0.1 = 10%)
     pub service_fee_rate: Decimal,
     /// 最小投注金额
     pub min_bet_amount: Uint128,
     /// 最大投注金额
     pub max_bet_amount: Uint128,
-    /// 投注代币类型
-    pub bet_denom: String,
+    /// 投注资产（原生代币或CW20代币），投注、奖金、退款和服务费均使用该资产
+    pub bet_asset: BetAsset,
     /// 是否暂停
     pub paused: bool,
     /// 是否请求暂停（完成当前周期后暂停）
     pub pause_requested: bool,
+    /// 奖金领取有效期（区块数，自结算高度起算），过期未领取的奖金退回奖金池
+    pub claim_expiry_blocks: u64,
+    /// 当前阶段时间表
+    pub schedule: PhaseSchedule,
+    /// 待生效的阶段时间表（从下一周期开始生效）
+    pub pending_schedule: Option<PhaseSchedule>,
+    /// 未揭秘参与者的处理策略
+    pub non_reveal_policy: NonRevealPolicy,
+    /// 承诺押金，投注时与投注金额一并支付，揭秘时退还，未揭秘则罚没并入奖金池
+    pub commitment_deposit: Uint128,
+    /// 中奖号码的随机数来源
+    pub randomness_source: RandomnessSource,
+    /// 奖金结构
+    pub prize_structure: PrizeStructure,
+    /// 启用的奖级及各奖级分得的奖金池比例
+    pub prize_tiers: Vec<TierPrize>,
+    /// 各投注类型的单价
+    pub ticket_prices: TicketPrices,
+    /// 投注时附带的非投注代币的处理方式
+    pub extra_funds_policy: ExtraFundsPolicy,
+    /// 服务费分成表，为空时服务费全部归管理员
+    pub fee_recipients: Vec<FeeRecipient>,
+    /// 服务费的分配时机
+    pub fee_distribution: FeeDistribution,
+    /// 推荐奖励占推荐投注服务费的比例 (0.2 = 20%)
+    pub referral_fee_share: Decimal,
+}
+
+impl Config {
+    /// 是否为当前管理员
+    pub fn is_admin(&self, address: &Addr) -> bool {
+        self.admin.as_ref() == Some(address)
+    }
+
+    /// 获取指定区块适用的阶段时间表
+    pub fn schedule_at(&self, block: &BlockInfo) -> &PhaseSchedule {
+        match &self.pending_schedule {
+            Some(pending) if pending.position(block) >= pending.genesis => pending,
+            _ => &self.schedule,
+        }
+    }
+}
+
+/// 投注资产
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BetAsset {
+    /// 原生代币，通过 `PlaceBet` 随消息转账投注
+    Native { denom: String },
+    /// CW20代币，通过代币合约的 `Send` 携带 `PlaceBet` 钩子消息投注
+    Cw20 { address: Addr },
+}
+
+impl BetAsset {
+    /// 向收款人转账的消息
+    pub fn transfer_msg(&self, recipient: impl Into<String>, amount: Uint128) -> StdResult<CosmosMsg> {
+        Ok(match self {
+            BetAsset::Native { denom } => BankMsg::Send {
+                to_address: recipient.into(),
+                amount: vec![Coin { denom: denom.clone(), amount }],
+            }.into(),
+            BetAsset::Cw20 { address } => WasmMsg::Execute {
+                contract_addr: address.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer { recipient: recipient.into(), amount })?,
+                funds: vec![],
+            }.into(),
+        })
+    }
+
+    /// 查询地址持有的投注资产余额
+    pub fn query_balance(&self, querier: &QuerierWrapper, address: &Addr) -> StdResult<Uint128> {
+        match self {
+            BetAsset::Native { denom } => Ok(querier.query_balance(address, denom)?.amount),
+            BetAsset::Cw20 { address: token } => {
+                let response: Cw20BalanceResponse = querier.query_wasm_smart(
+                    token,
+                    &Cw20QueryMsg::Balance { address: address.to_string() },
+                )?;
+                Ok(response.balance)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for BetAsset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BetAsset::Native { denom } => write!(f, "{}", denom),
+            BetAsset::Cw20 { address } => write!(f, "cw20:{}", address),
+        }
+    }
+}
+
+/// 投注时附带的非投注代币的处理方式
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtraFundsPolicy {
+    /// 拒绝投注
+    #[default]
+    Reject,
+    /// 接受投注，并在同一响应中将多余代币退还投注人
+    Refund,
+}
+
+/// 推荐人统计
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+pub struct ReferrerStats {
+    /// 推荐的投注笔数
+    pub referred_bets: u64,
+    /// 推荐的投注总额
+    pub referred_volume: Uint128,
+    /// 获得的推荐奖励，已取消会话中作废的部分在领取推荐奖励时才扣除；
+    /// `GetReferrerStats` 返回时已扣除全部作废部分
+    pub earned_fees: Uint128,
+    /// 已领取的推荐奖励
+    pub claimed_fees: Uint128,
+}
+
+/// 服务费分成表最多收款人数量
+pub const MAX_FEE_RECIPIENTS: usize = 10;
+
+/// 服务费分成收款人
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeRecipient {
+    /// 收款地址（DAO国库、节点运营方、开发基金等）
+    pub address: Addr,
+    /// 分成权重，按占全部权重之和的比例分得服务费
+    pub weight: u32,
+}
+
+impl FeeRecipient {
+    /// 验证分成表：收款人不超过上限且不重复，权重大于0
+    pub fn validate_all(recipients: &[FeeRecipient]) -> bool {
+        if recipients.len() > MAX_FEE_RECIPIENTS {
+            return false;
+        }
+        recipients.iter().enumerate().all(|(i, recipient)| {
+            recipient.weight > 0 && !recipients[..i].iter().any(|other| other.address == recipient.address)
+        })
+    }
+
+    /// 按权重拆分金额，向下取整的余数归第一个收款人
+    pub fn split(recipients: &[FeeRecipient], amount: Uint128) -> Vec<(Addr, Uint128)> {
+        let total_weight: u128 = recipients.iter().map(|recipient| recipient.weight as u128).sum();
+        if total_weight == 0 {
+            return vec![];
+        }
+        let mut shares: Vec<(Addr, Uint128)> = recipients.iter()
+            .map(|recipient| (recipient.address.clone(), amount.multiply_ratio(recipient.weight as u128, total_weight)))
+            .collect();
+        let distributed: Uint128 = shares.iter().map(|(_, share)| *share).sum();
+        shares[0].1 += amount - distributed;
+        shares
+    }
+}
+
+/// 服务费的分配时机
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeDistribution {
+    /// 结算时计提，管理员提取时按分成表发放
+    #[default]
+    OnWithdraw,
+    /// 结算时直接按分成表发放，未配置分成表时仍计提待提取
+    AtSettlement,
+}
+
+/// 默认奖金领取有效期：10个周期
+pub const DEFAULT_CLAIM_EXPIRY_BLOCKS: u64 = 100_000;
+
+/// 未揭秘参与者的处理策略
+/// 
+/// 参与者承诺后未在揭秘阶段揭秘时，其投注（扣除已计提的服务费）按此策略处理
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NonRevealPolicy {
+    /// 没收并入当期奖金池
+    #[default]
+    ForfeitToPool,
+    /// 没收并入服务费（国库），由管理员提取
+    ForfeitToTreasury,
+    /// 扣除罚金后退还剩余部分，罚金并入当期奖金池
+    PartialRefund {
+        /// 罚金比例 (0.2 = 20%)
+        penalty_rate: Decimal,
+    },
+}
+
+impl NonRevealPolicy {
+    /// 验证策略参数，罚金比例不能超过100%
+    pub fn is_valid(&self) -> bool {
+        match self {
+            NonRevealPolicy::PartialRefund { penalty_rate } => *penalty_rate <= Decimal::one(),
+            _ => true,
+        }
+    }
+}
+
+/// 奖金结构
+/// 
+/// 每个投注码花费1个基础代币，每个与中奖号码相同的投注码计为一注中奖
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PrizeStructure {
+    /// 每注中奖固定获得投注单位的倍数，奖金池不足时所有中奖注平分奖金池
+    FixedMultiplier {
+        /// 赔付倍数 (800 = 每注获得800个基础代币)
+        multiplier: Uint128,
+    },
+    /// 奖金池的固定比例由所有中奖注平分，其余滚入累积奖池
+    PoolPercentage {
+        /// 分配比例 (0.5 = 50%)
+        rate: Decimal,
+    },
+    /// 整个奖金池由所有中奖注平分（彩池制）
+    PariMutuel,
+}
+
+impl Default for PrizeStructure {
+    fn default() -> Self {
+        PrizeStructure::FixedMultiplier { multiplier: Uint128::new(800) }
+    }
+}
+
+impl PrizeStructure {
+    /// 验证奖金结构参数，倍数不能为0，比例须在 (0, 1] 之间
+    pub fn is_valid(&self) -> bool {
+        match self {
+            PrizeStructure::FixedMultiplier { multiplier } => !multiplier.is_zero(),
+            PrizeStructure::PoolPercentage { rate } => !rate.is_zero() && *rate <= Decimal::one(),
+            PrizeStructure::PariMutuel => true,
+        }
+    }
+}
+
+/// 3D 奖级，按优先级从高到低排列，每注只计最高的一个奖级
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PrizeTier {
+    /// 直选：三位数字与中奖号码按位相同
+    Straight,
+    /// 组选三：中奖号码有两位相同，组选票号码为其任意排列，仅组选票可中
+    GroupThree,
+    /// 组选六：中奖号码三位各不相同，组选票号码为其任意排列，仅组选票可中
+    GroupSix,
+    /// 前二：百位和十位与中奖号码相同
+    FrontPair,
+    /// 后二：十位和个位与中奖号码相同
+    BackPair,
+}
+
+impl PrizeTier {
+    /// 所有奖级，按优先级从高到低
+    pub const ALL: [PrizeTier; 5] = [
+        PrizeTier::Straight,
+        PrizeTier::GroupThree,
+        PrizeTier::GroupSix,
+        PrizeTier::FrontPair,
+        PrizeTier::BackPair,
+    ];
+
+    /// 中奖等级编号 (1=直选 … 5=后二)
+    pub fn level(&self) -> u8 {
+        match self {
+            PrizeTier::Straight => 1,
+            PrizeTier::GroupThree => 2,
+            PrizeTier::GroupSix => 3,
+            PrizeTier::FrontPair => 4,
+            PrizeTier::BackPair => 5,
+        }
+    }
+
+    pub fn from_level(level: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|tier| tier.level() == level)
+    }
+    
+    /// 是否为组选奖级（组选三、组选六），只有组选票能中组选奖级
+    pub fn is_group(&self) -> bool {
+        matches!(self, PrizeTier::GroupThree | PrizeTier::GroupSix)
+    }
+}
+
+/// 奖级奖金配置
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TierPrize {
+    /// 奖级
+    pub tier: PrizeTier,
+    /// 该奖级分得的奖金池比例，由该奖级的中奖注按奖金结构分配
+    pub pool_share: Decimal,
+    /// 固定倍数奖金结构下该奖级的赔付倍数，未设置时使用奖金结构的倍数
+    pub multiplier: Option<Uint128>,
+}
+
+impl TierPrize {
+    /// 默认只设直选一个奖级，独占整个奖金池
+    pub fn default_tiers() -> Vec<TierPrize> {
+        vec![TierPrize {
+            tier: PrizeTier::Straight,
+            pool_share: Decimal::one(),
+            multiplier: None,
+        }]
+    }
+
+    /// 验证奖级配置：至少一个奖级，奖级不重复，比例大于0且总和不超过100%，倍数不能为0
+    pub fn validate_all(tiers: &[TierPrize]) -> bool {
+        if tiers.is_empty() {
+            return false;
+        }
+        let mut total_share = Decimal::zero();
+        for (i, prize) in tiers.iter().enumerate() {
+            if prize.pool_share.is_zero() || prize.multiplier.is_some_and(|m| m.is_zero()) {
+                return false;
+            }
+            if tiers[..i].iter().any(|other| other.tier == prize.tier) {
+                return false;
+            }
+            total_share += prize.pool_share;
+        }
+        total_share <= Decimal::one()
+    }
+}
+
+/// 投注类型
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BetType {
+    /// 直选：单个号码，与幸运数字相同按奖级规则兑奖
+    Straight,
+    /// 组选：单个号码，中奖号码为其三位数字的任意排列即中奖，按组选三/组选六奖级兑奖
+    Box,
+    /// 复式：同时覆盖多个号码，每个号码按直选兑奖
+    Combination,
+}
+
+/// 投注票
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Ticket {
+    /// 投注类型
+    pub bet_type: BetType,
+    /// 号码 (0-999)，直选和组选为一个号码，复式为多个不重复的号码
+    pub numbers: Vec<u16>,
+}
+
+/// 各投注类型的单价（基础代币）
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TicketPrices {
+    /// 每张直选票的价格
+    pub straight_price: Uint128,
+    /// 每张组选票的价格
+    pub box_price: Uint128,
+    /// 复式票每个号码的价格
+    pub combination_price: Uint128,
+}
+
+impl Default for TicketPrices {
+    fn default() -> Self {
+        TicketPrices {
+            straight_price: Uint128::one(),
+            box_price: Uint128::one(),
+            combination_price: Uint128::one(),
+        }
+    }
+}
+
+impl TicketPrices {
+    /// 验证价格，所有价格不能为0
+    pub fn is_valid(&self) -> bool {
+        !self.straight_price.is_zero() && !self.box_price.is_zero() && !self.combination_price.is_zero()
+    }
+    
+    /// 投注类型每个号码的单价，复式票为每个号码的价格
+    pub fn unit_price(&self, bet_type: BetType) -> Uint128 {
+        match bet_type {
+            BetType::Straight => self.straight_price,
+            BetType::Box => self.box_price,
+            BetType::Combination => self.combination_price,
+        }
+    }
+}
+
+/// 中奖号码的随机数来源
+///
+/// 只有 `Beacon` 和 `Hybrid` 能防止最后揭秘者偏差，部署时推荐使用 `Hybrid`；
+/// 默认的 `ParticipantSeeds` 无需外部依赖，偏差只能由承诺押金抑制
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RandomnessSource {
+    /// 只使用参与者揭秘的随机种子（混入首次结算时生成的结算熵）
+    #[default]
+    ParticipantSeeds,
+    /// 只使用外部随机数信标
+    Beacon(BeaconConfig),
+    /// 参与者随机种子与外部随机数信标混合
+    Hybrid(BeaconConfig),
+}
+
+impl RandomnessSource {
+    /// 获取信标配置，只使用参与者种子时为 None
+    pub fn beacon(&self) -> Option<&BeaconConfig> {
+        match self {
+            RandomnessSource::ParticipantSeeds => None,
+            RandomnessSource::Beacon(beacon) | RandomnessSource::Hybrid(beacon) => Some(beacon),
+        }
+    }
+}
+
+/// 外部随机数信标配置（drand / Nois 风格，按固定周期发布轮次）
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct BeaconConfig {
+    /// 信标代理合约地址
+    pub address: Addr,
+    /// 信标第1轮的发布时间（Unix 秒）
+    pub genesis_time: u64,
+    /// 轮次间隔（秒）
+    pub period: u64,
+}
+
+impl BeaconConfig {
+    /// 验证信标配置，轮次间隔必须大于0
+    pub fn is_valid(&self) -> bool {
+        self.period > 0
+    }
+
+    /// 指定时间之后才会发布的第一个轮次
+    /// 第 r 轮发布于 `genesis_time + (r - 1) * period`
+    pub fn next_round_after(&self, seconds: u64) -> u64 {
+        if seconds < self.genesis_time {
+            return 1;
+        }
+        (seconds - self.genesis_time) / self.period + 2
+    }
+}
+
+/// 信标随机数请求
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BeaconRequest {
+    /// 请求的轮次，回调时必须与之一致
+    pub round: u64,
+    /// 信标回调的随机数
+    pub randomness: Option<HexBinary>,
+}
+
+/// 阶段调度模式
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleMode {
+    /// 按区块高度划分阶段，长度单位为区块数
+    BlockHeight,
+    /// 按区块时间划分阶段，长度单位为秒
+    Timestamp,
+}
+
+/// 阶段时间表
+/// 
+/// 从 `genesis` 开始，每 `commitment_length + reveal_length + settlement_length`
+/// 为一个周期，周期内依次为承诺、揭秘、结算阶段。
+/// `BlockHeight` 模式下单位为区块，`Timestamp` 模式下单位为秒
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PhaseSchedule {
+    /// 调度模式
+    pub mode: ScheduleMode,
+    /// 起始位置（区块高度或 Unix 秒）
+    pub genesis: u64,
+    /// 起始位置对应的周期编号
+    pub first_cycle: u64,
+    /// 承诺阶段长度
+    pub commitment_length: u64,
+    /// 揭秘阶段长度
+    pub reveal_length: u64,
+    /// 结算阶段长度
+    pub settlement_length: u64,
+}
+
+impl Default for PhaseSchedule {
+    /// 默认时间表：每10000个区块一个周期，按 6000/3000/1000 划分
+    fn default() -> Self {
+        Self {
+            mode: ScheduleMode::BlockHeight,
+            genesis: 0,
+            first_cycle: 0,
+            commitment_length: 6000,
+            reveal_length: 3000,
+            settlement_length: 1000,
+        }
+    }
+}
+
+impl PhaseSchedule {
+    /// 默认按时间调度的时间表：每天一个周期，按 20/3/1 小时划分
+    pub fn daily(genesis_time: u64) -> Self {
+        Self {
+            mode: ScheduleMode::Timestamp,
+            genesis: genesis_time,
+            first_cycle: 0,
+            commitment_length: 72_000,
+            reveal_length: 10_800,
+            settlement_length: 3_600,
+        }
+    }
+
+    /// 区块在时间表中的位置（区块高度或 Unix 秒）
+    pub fn position(&self, block: &BlockInfo) -> u64 {
+        match self.mode {
+            ScheduleMode::BlockHeight => block.height,
+            ScheduleMode::Timestamp => block.time.seconds(),
+        }
+    }
+
+    /// 周期长度
+    pub fn cycle_length(&self) -> u64 {
+        self.commitment_length + self.reveal_length + self.settlement_length
+    }
+
+    /// 验证时间表，各阶段长度必须大于0
+    pub fn is_valid(&self) -> bool {
+        self.commitment_length > 0 && self.reveal_length > 0 && self.settlement_length > 0
+    }
+
+    /// 位置在当前周期内的偏移量
+    /// 起始位置之前视为第一个周期的开始
+    pub fn offset_at(&self, position: u64) -> u64 {
+        position.saturating_sub(self.genesis) % self.cycle_length()
+    }
+
+    /// 位置所在的周期编号
+    pub fn cycle_at(&self, position: u64) -> u64 {
+        self.first_cycle + position.saturating_sub(self.genesis) / self.cycle_length()
+    }
+
+    /// 指定周期的起始位置
+    pub fn cycle_start(&self, cycle: u64) -> u64 {
+        self.genesis + cycle.saturating_sub(self.first_cycle) * self.cycle_length()
+    }
+
+    /// 位置所在的阶段
+    pub fn phase_at(&self, position: u64) -> LotteryPhase {
+        let offset = self.offset_at(position);
+        if offset < self.commitment_length {
+            LotteryPhase::Commitment
+        } else if offset < self.commitment_length + self.reveal_length {
+            LotteryPhase::Reveal
+        } else {
+            LotteryPhase::Settlement
+        }
+    }
+
+    /// 当前阶段剩余长度（区块数或秒）
+    pub fn remaining_at(&self, position: u64) -> u64 {
+        let offset = self.offset_at(position);
+        match self.phase_at(position) {
+            LotteryPhase::Commitment => self.commitment_length - offset,
+            LotteryPhase::Reveal => self.commitment_length + self.reveal_length - offset,
+            LotteryPhase::Settlement => self.cycle_length() - offset,
+        }
+    }
+}
+
+/// 区块时钟锚点：实例化（或迁移）时的区块高度和时间
+///
+/// 时间表只按一种单位调度，另一种单位的剩余量按锚点以来的平均出块时间估算
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClockAnchor {
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+impl ClockAnchor {
+    pub fn new(block: &BlockInfo) -> Self {
+        ClockAnchor { height: block.height, time: block.time }
+    }
+
+    /// 锚点以来的区块数和经过的纳秒数，尚未出新块或时间未前进时无法估算
+    fn elapsed(&self, block: &BlockInfo) -> Option<(u128, u128)> {
+        let blocks = block.height.checked_sub(self.height).filter(|&blocks| blocks > 0)?;
+        let nanos = block.time.nanos().checked_sub(self.time.nanos()).filter(|&nanos| nanos > 0)?;
+        Some((blocks as u128, nanos as u128))
+    }
+
+    /// 按平均出块时间把区块数换算为秒数
+    pub fn blocks_to_seconds(&self, block: &BlockInfo, blocks: u64) -> Option<u64> {
+        let (elapsed_blocks, elapsed_nanos) = self.elapsed(block)?;
+        u64::try_from(blocks as u128 * elapsed_nanos / elapsed_blocks / 1_000_000_000).ok()
+    }
+
+    /// 按平均出块时间把秒数换算为区块数（向上取整）
+    pub fn seconds_to_blocks(&self, block: &BlockInfo, seconds: u64) -> Option<u64> {
+        let (elapsed_blocks, elapsed_nanos) = self.elapsed(block)?;
+        u64::try_from((seconds as u128 * 1_000_000_000 * elapsed_blocks).div_ceil(elapsed_nanos)).ok()
+    }
 }
 
 /// 彩票阶段
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum LotteryPhase {
-    /// 承诺阶段 (0-5999)
+    /// 承诺阶段
     Commitment,
-    /// 中奖揭秘阶段 (6000-8999)
+    /// 中奖揭秘阶段
     Reveal,
-    /// 结算阶段 (9000-9999)
+    /// 结算阶段
     Settlement,
 }
 
 impl LotteryPhase {
-    /// 根据区块高度获取当前阶段
+    /// 根据区块高度获取默认时间表下的阶段 (0-5999 / 6000-8999 / 9000-9999)
+    /// 合约内部应使用 `Config::schedule_at` 获取实际配置的时间表
     pub fn from_block_height(block_height: u64) -> Self {
-        let phase_mod = block_height % 10000;
-        match phase_mod {
-            0..=5999 => LotteryPhase::Commitment,
-            6000..=8999 => LotteryPhase::Reveal,
-            9000..=9999 => LotteryPhase::Settlement,
-            _ => LotteryPhase::Commitment,
-        }
+        PhaseSchedule::default().phase_at(block_height)
     }
     
     /// 获取阶段名称
@@ -63,8 +648,10 @@ pub struct Participant {
     pub address: Addr,
     /// 投注金额 (K个基础代币)
     pub bet_amount: Uint128,
-    /// 幸运数字列表 (每个投注码对应一个幸运数字0-999)
-    pub lucky_numbers: Vec<u16>,
+    /// 幸运数字直方图 [(号码0-999, 注数)]，按号码升序
+    pub lucky_numbers: Vec<(u16, u32)>,
+    /// 投注票列表（直选、组选、复式）
+    pub tickets: Vec<Ticket>,
     /// 用户随机种子
     pub random_seed: Option<String>,
     /// 是否已揭秘
@@ -75,6 +662,8 @@ pub struct Participant {
     pub bet_time: Timestamp,
     /// 揭秘时间
     pub reveal_time: Option<Timestamp>,
+    /// 承诺押金（揭秘时退还）
+    pub deposit: Uint128,
 }
 
 /// 彩票结果
@@ -82,32 +671,204 @@ pub struct Participant {
 pub struct LotteryResult {
     /// 会话ID
     pub session_id: String,
+    /// 周期编号
+    pub cycle: u64,
     /// 中奖号码
     pub winning_number: u16,
     /// 总投注金额
     pub total_pool: Uint128,
     /// 服务费
     pub service_fee: Uint128,
-    /// 奖金池
+    /// 奖金池（含从上期滚入的累积奖池）
     pub reward_pool: Uint128,
-    /// 中奖者列表
-    pub winners: Vec<Winner>,
+    /// 从上期滚入的累积奖池
+    pub jackpot_in: Uint128,
+    /// 滚入下一期的累积奖池（奖金分配后的剩余）
+    pub jackpot_out: Uint128,
+    /// 各奖级的中奖汇总，中奖记录见 `WINNINGS`
+    pub tiers: Vec<TierSummary>,
+    /// 中奖人数
+    pub winners_count: u32,
     /// 结算时间
     pub settled_at: Timestamp,
     /// 结算区块高度
     pub settled_height: u64,
+    /// 已领取奖金总额
+    pub total_paid: Uint128,
+    /// 待领取奖金总额
+    pub total_pending: Uint128,
+    /// 已过期并退回奖金池的奖金总额
+    pub total_expired: Uint128,
+    /// 领取截止区块高度（含）
+    pub claim_deadline_height: u64,
+    /// 未揭秘参与者的处理结果
+    pub non_reveal: NonRevealOutcome,
+    /// 混入中奖号码计算的结算熵（由首次结算交易所在区块生成，或信标随机数）
+    pub post_reveal_entropy: String,
+    /// 本期随机数来源
+    pub randomness_source: RandomnessSource,
+    /// 使用的信标轮次
+    pub beacon_round: Option<u64>,
+    /// 本期奖金结构
+    pub prize_structure: PrizeStructure,
+    /// 本期奖级配置
+    pub prize_tiers: Vec<TierPrize>,
+    /// 本期投注类型单价
+    pub ticket_prices: TicketPrices,
 }
 
-/// 中奖者信息
+/// 未揭秘参与者的处理结果
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NonRevealOutcome {
+    /// 本期采用的处理策略
+    pub policy: NonRevealPolicy,
+    /// 未揭秘参与者数量
+    pub non_revealed_count: u32,
+    /// 未揭秘参与者的投注总额
+    pub non_revealed_amount: Uint128,
+    /// 没收并入奖金池的金额
+    pub forfeited_to_pool: Uint128,
+    /// 没收并入服务费的金额
+    pub forfeited_to_treasury: Uint128,
+    /// 退还给未揭秘参与者的金额（记入待领取余额）
+    pub refunded: Uint128,
+    /// 罚没并入奖金池的承诺押金
+    pub slashed_deposits: Uint128,
+}
+
+impl NonRevealOutcome {
+    /// 合并分批处理的结果
+    pub fn merge(&mut self, batch: &NonRevealOutcome) {
+        self.non_revealed_count += batch.non_revealed_count;
+        self.non_revealed_amount += batch.non_revealed_amount;
+        self.forfeited_to_pool += batch.forfeited_to_pool;
+        self.forfeited_to_treasury += batch.forfeited_to_treasury;
+        self.refunded += batch.refunded;
+        self.slashed_deposits += batch.slashed_deposits;
+    }
+}
+
+/// 分批结算的阶段
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SettlementStage {
+    /// 逐批读取参与者：将已揭秘者的随机种子折叠进种子摘要并处理未揭秘者，读完后计算中奖号码
+    Drawing,
+    /// 逐批读取可能中奖号码下的参与者，累计中奖记录和奖级汇总，读完后确定各奖级奖金总额
+    Winners,
+    /// 逐批按中奖投注额比例计算中奖记录的奖金并记入待领取余额，完成后结算会话
+    Payouts,
+}
+
+impl SettlementStage {
+    /// 获取阶段名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            SettlementStage::Drawing => "drawing",
+            SettlementStage::Winners => "winners",
+            SettlementStage::Payouts => "payouts",
+        }
+    }
+}
+
+/// 分批结算进度
+/// 
+/// 首次结算交易创建，之后每次 `SettleLottery` 从游标处继续处理至多 `limit` 条记录，
+/// 最后一批处理完成后删除并标记会话已结算。进度只保存摘要和计数，大小与参与者和中奖者数量无关
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SettlementProgress {
+    /// 会话ID
+    pub session_id: String,
+    /// 当前阶段
+    pub stage: SettlementStage,
+    /// 结算熵（首次结算时由所在区块生成并固定，使用信标时为信标随机数）
+    pub post_reveal_entropy: String,
+    /// 信标轮次
+    pub beacon_round: Option<u64>,
+    /// 已揭秘随机种子的滚动摘要，见 `LotteryLogic::fold_seed`
+    pub seed_digest: String,
+    /// 中奖号码（Drawing 阶段完成后确定）
+    pub winning_number: Option<u16>,
+    /// 已处理的未揭秘参与者（累计）
+    pub non_reveal: NonRevealOutcome,
+    /// 各奖级的中奖汇总
+    pub tiers: Vec<TierSummary>,
+    /// 中奖人数
+    pub winners_count: u32,
+    /// 并入本期奖金池的累积奖池（Winners 阶段完成后确定）
+    pub jackpot_in: Uint128,
+    /// 奖金池（Winners 阶段完成后确定）
+    pub reward_pool: Uint128,
+    /// 已记入待领取余额的奖金和退款
+    pub total_pending: Uint128,
+    /// Winners 阶段当前处理的候选号码序号
+    pub number_cursor: u32,
+    /// 最后处理的参与者地址，阶段或号码切换时清空
+    pub participant_cursor: Option<Addr>,
+    /// Payouts 阶段最后处理的中奖记录 (中奖者, 奖级)
+    pub payout_cursor: Option<(Addr, u8)>,
+    /// Drawing 阶段已处理的参与者数量
+    pub processed_participants: u32,
+}
+
+/// 奖级中奖汇总
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TierSummary {
+    /// 奖级
+    pub tier: PrizeTier,
+    /// 中奖注数
+    pub count: u64,
+    /// 中奖投注额
+    pub stake: Uint128,
+    /// 奖级奖金总额，Winners 阶段完成后按奖金结构确定
+    pub budget: Uint128,
+    /// 已分配的奖金，按比例向下取整的余数滚入累积奖池
+    pub distributed: Uint128,
+}
+
+impl TierSummary {
+    /// 尚无中奖的奖级汇总
+    pub fn new(tier: PrizeTier) -> Self {
+        TierSummary {
+            tier,
+            count: 0,
+            stake: Uint128::zero(),
+            budget: Uint128::zero(),
+            distributed: Uint128::zero(),
+        }
+    }
+}
+
+/// 奖金发放记录
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Payout {
+    /// 收款地址
+    pub address: Addr,
+    /// 应发奖金总额（同一地址的多次中奖合并为一笔）
+    pub amount: Uint128,
+}
+
+/// 每期应发金额
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+pub struct Prize {
+    /// 中奖奖金与未揭秘退款的合计
+    pub amount: Uint128,
+    /// 是否已领取
+    pub claimed: bool,
+}
+
+/// 中奖者信息，同一中奖者同一奖级的所有中奖注合并为一条记录
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Winner {
     /// 中奖者地址
     pub address: Addr,
-    /// 中奖等级 (1=一等奖)
+    /// 中奖等级，见 `PrizeTier::level` (1=直选)
     pub level: u8,
-    /// 匹配数字数量
-    pub match_count: u8,
-    /// 奖金金额
+    /// 中奖注数
+    pub count: u32,
+    /// 中奖投注额：投注码每注1个基础代币，投注票每注为该类型的单价，奖金按投注额比例分配
+    pub stake: Uint128,
+    /// 该记录所有中奖注的奖金总额
     pub reward_amount: Uint128,
 }
 
@@ -133,7 +894,7 @@ pub struct Stats {
     pub total_participants: u64,
     /// 总投注金额
     pub total_pool: Uint128,
-    /// 总服务费
+    /// 累计计提的服务费（结算时计入，已扣除推荐奖励，含罚没归入国库的部分）
     pub total_service_fee: Uint128,
     /// 总奖金
     pub total_rewards: Uint128,
@@ -145,42 +906,253 @@ pub struct Stats {
 /// 系统配置
 pub const CONFIG: Item<Config> = Item::new("config");
 
+/// 待接受的管理员，由当前管理员提名，被提名地址接受后生效
+pub const PENDING_ADMIN: Item<Addr> = Item::new("pending_admin");
+
 /// 当前彩票会话
 pub const CURRENT_SESSION: Item<LotterySession> = Item::new("current_session");
 
 
-/// 会话详情映射 (会话ID -> 会话详情)
+/// 已归档的历史会话 (会话ID -> 会话详情)
 pub const SESSION_DETAILS: Map<String, LotterySession> = Map::new("session_details");
 
-/// 参与者承诺
-pub const COMMITMENTS: Map<&Addr, Commitment> = Map::new("commitments");
+/// 参与者承诺 ((会话ID, 参与者地址) -> 承诺)，按会话隔离，换期时无需清空
+pub const COMMITMENTS: Map<(&str, &Addr), Commitment> = Map::new("session_commitments");
 
 /// 彩票历史结果
 pub const LOTTERY_HISTORY: Map<String, LotteryResult> = Map::new("lottery_history");
 
+/// 地址参与过的会话 ((参与者地址, 周期编号) -> 会话ID)，投注时写入
+pub const USER_SESSIONS: Map<(&Addr, u64), String> = Map::new("user_sessions");
+
+/// 已结算会话按周期排序的索引 (周期编号 -> 会话ID)，用于分页查询彩票历史
+pub const SETTLED_SESSIONS: Map<u64, String> = Map::new("settled_sessions");
+
+/// 待领取奖金 ((中奖者地址, 会话ID) -> 金额)
+/// 结算过程中写入，会话结算完成后才能领取；领取后删除，过期后不能领取，由一键领取删除
+pub const CLAIMABLE: Map<(&Addr, &str), Uint128> = Map::new("claimable");
+
+/// 每期应发金额和领取情况，领取和过期后保留，供查询投注记录
+pub const PRIZES: Map<(&Addr, &str), Prize> = Map::new("prizes");
+
+/// 每期中奖记录，按 (会话ID, 中奖者, 奖级) 存储，Payouts 阶段写入奖金金额
+pub const WINNINGS: Map<(&str, &Addr, u8), Winner> = Map::new("winnings");
+
+/// 累积奖池：奖金分配后的剩余、无人中奖的奖金池、过期未领取的奖金和罚没的押金，
+/// 全部滚入下一次结算的奖金池
+pub const JACKPOT: Item<Uint128> = Item::new("jackpot");
+
+/// 已计提未提取的服务费：结算时计入当期服务费和没收至国库的投注，提取服务费不能超过该金额
+pub const ACCRUED_FEES: Item<Uint128> = Item::new("accrued_fees");
+
+/// 全部待领取奖金和退款的合计：结算完成时计入，领取和过期回收时扣除
+pub const UNCLAIMED_PRIZES: Item<Uint128> = Item::new("unclaimed_prizes");
+
+/// 已取消会话尚未退款的投注合计，取消时计入，退款时扣减
+pub const PENDING_REFUNDS: Item<Uint128> = Item::new("pending_refunds");
+
+/// 推荐奖励 ((推荐人地址, 会话ID) -> 金额)，投注时记入，会话结算后可领取，会话取消则作废
+pub const REFERRAL_EARNINGS: Map<(&Addr, &str), Uint128> = Map::new("referral_earnings");
+
+/// 已结算会话中尚未领取的推荐奖励合计
+pub const REFERRAL_PAYABLE: Item<Uint128> = Item::new("referral_payable");
+
+/// 推荐人统计
+pub const REFERRER_STATS: Map<&Addr, ReferrerStats> = Map::new("referrer_stats");
+
+/// 已取消会话的退款记录 ((会话ID, 投注者地址) -> 退款金额)
+pub const REFUNDS: Map<(&str, &Addr), Uint128> = Map::new("refunds");
+
+/// 参与者号码索引
+/// 
+/// 每个参与者揭秘的号码（直方图中的号码和投注票中的号码）各占一条索引记录
+/// ((会话ID, 号码, 参与者地址) -> 投注数)，同时按号码汇总全部参与者的投注数
+/// ((会话ID, 号码) -> 投注数)，结算时只需读取有人投注的可能中奖号码
+pub struct NumberIndex {
+    idx: Map<(String, u16, Addr), u32>,
+    totals: Map<(String, u16), u32>,
+}
+
+impl NumberIndex {
+    pub const fn new(namespace: &'static str, totals_namespace: &'static str) -> Self {
+        NumberIndex {
+            idx: Map::new(namespace),
+            totals: Map::new(totals_namespace),
+        }
+    }
+
+    /// 参与者揭秘的全部号码及投注数（直方图注数加上号码在投注票中出现的次数）
+    fn numbers(participant: &Participant) -> Vec<(u16, u32)> {
+        let mut numbers: std::collections::BTreeMap<u16, u32> = participant.lucky_numbers.iter().copied().collect();
+        for number in participant.tickets.iter().flat_map(|ticket| ticket.numbers.iter()) {
+            *numbers.entry(*number).or_insert(0) += 1;
+        }
+        numbers.into_iter().collect()
+    }
+
+    /// 会话中指定号码的总投注数
+    pub fn bets(&self, store: &dyn Storage, session_id: &str, number: u16) -> StdResult<u32> {
+        Ok(self.totals.may_load(store, (session_id.to_string(), number))?.unwrap_or_default())
+    }
+
+    /// 会话中有人投注的号码及总投注数，按号码升序
+    pub fn popularity(&self, store: &dyn Storage, session_id: &str) -> StdResult<Vec<(u16, u32)>> {
+        self.totals
+            .prefix(session_id.to_string())
+            .range(store, None, None, Order::Ascending)
+            .collect()
+    }
+
+    /// 投注了指定号码的参与者 [(地址, 投注数)]
+    pub fn participants(
+        &self,
+        store: &dyn Storage,
+        session_id: &str,
+        number: u16,
+    ) -> StdResult<Vec<(Addr, u32)>> {
+        self.page(store, session_id, number, None, usize::MAX)
+    }
+
+    /// 分页读取投注了指定号码的参与者，从 `start_after` 之后开始
+    pub fn page(
+        &self,
+        store: &dyn Storage,
+        session_id: &str,
+        number: u16,
+        start_after: Option<&Addr>,
+        limit: usize,
+    ) -> StdResult<Vec<(Addr, u32)>> {
+        self.idx
+            .prefix((session_id.to_string(), number))
+            .range(store, start_after.cloned().map(Bound::exclusive), None, Order::Ascending)
+            .take(limit)
+            .collect()
+    }
+}
+
+impl Index<Participant> for NumberIndex {
+    fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &Participant) -> StdResult<()> {
+        let (session_id, address) = <(String, Addr)>::from_slice(pk)?;
+        for (number, count) in Self::numbers(data) {
+            self.idx.save(store, (session_id.clone(), number, address.clone()), &count)?;
+            self.totals.update(store, (session_id.clone(), number), |total| -> StdResult<_> {
+                Ok(total.unwrap_or_default() + count)
+            })?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &Participant) -> StdResult<()> {
+        let (session_id, address) = <(String, Addr)>::from_slice(pk)?;
+        for (number, count) in Self::numbers(old_data) {
+            self.idx.remove(store, (session_id.clone(), number, address.clone()));
+            let key = (session_id.clone(), number);
+            let total = self.totals.may_load(store, key.clone())?.unwrap_or_default().saturating_sub(count);
+            if total == 0 {
+                self.totals.remove(store, key);
+            } else {
+                self.totals.save(store, key, &total)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 参与者索引
+pub struct ParticipantIndexes {
+    pub number: NumberIndex,
+}
+
+impl IndexList<Participant> for ParticipantIndexes {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Participant>> + '_> {
+        let v: Vec<&dyn Index<Participant>> = vec![&self.number];
+        Box::new(v.into_iter())
+    }
+}
+
+/// 参与者 ((会话ID, 参与者地址) -> 参与者信息)，按号码建立索引
+pub fn participants<'a>() -> IndexedMap<(&'a str, &'a Addr), Participant, ParticipantIndexes> {
+    IndexedMap::new(
+        "participants",
+        ParticipantIndexes {
+            number: NumberIndex::new("participants__number", "participants__number_totals"),
+        },
+    )
+}
+
+/// 按地址顺序加载会话的全部参与者
+pub fn load_session_participants(store: &dyn Storage, session_id: &str) -> StdResult<Vec<Participant>> {
+    participants()
+        .prefix(session_id)
+        .range(store, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, participant)| participant))
+        .collect()
+}
+
+/// 按会话ID加载当前会话或已归档的会话
+pub fn load_session(store: &dyn Storage, session_id: &str) -> StdResult<Option<LotterySession>> {
+    match CURRENT_SESSION.may_load(store)? {
+        Some(session) if session.session_id == session_id => Ok(Some(session)),
+        _ => SESSION_DETAILS.may_load(store, session_id.to_string()),
+    }
+}
+
+/// 当前会话的分批结算进度
+pub const SETTLEMENT_PROGRESS: Item<SettlementProgress> = Item::new("settlement_progress");
+
 /// 统计信息
 pub const STATS: Item<Stats> = Item::new("stats");
 
+/// 区块时钟锚点
+pub const CLOCK_ANCHOR: Item<ClockAnchor> = Item::new("clock_anchor");
+
 /// 防重入锁
 pub const REENTRANCY_LOCK: Item<bool> = Item::new("reentrancy_lock");
 
+/// 根据周期编号生成会话ID
+pub fn session_id_for_cycle(cycle: u64) -> String {
+    format!("session_{}", cycle)
+}
+
 /// 当前彩票会话
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct LotterySession {
     /// 会话ID
     pub session_id: String,
+    /// 周期编号
+    pub cycle: u64,
     /// 当前阶段
     pub phase: LotteryPhase,
     /// 总投注金额
     pub total_pool: Uint128,
     /// 服务费
     pub service_fee: Uint128,
-    /// 参与者列表
-    pub participants: Vec<Participant>,
+    /// 参与者数量（参与者明细见 `participants()`）
+    pub participant_count: u32,
+    /// 已揭秘的参与者数量
+    pub revealed_count: u32,
+    /// 未揭秘参与者的承诺押金合计
+    pub pending_deposits: Uint128,
+    /// 本期推荐奖励合计，结算时从服务费中扣除
+    pub referral_fees: Uint128,
     /// 创建区块高度
     pub created_height: u64,
     /// 中奖号码
     pub winning_number: Option<u16>,
     /// 是否已结算
     pub settled: bool,
+    /// 本期未揭秘参与者的处理策略（会话创建时确定）
+    pub non_reveal_policy: NonRevealPolicy,
+    /// 本期随机数来源（会话创建时确定）
+    pub randomness_source: RandomnessSource,
+    /// 本期奖金结构（会话创建时确定）
+    pub prize_structure: PrizeStructure,
+    /// 本期奖级配置（会话创建时确定）
+    pub prize_tiers: Vec<TierPrize>,
+    /// 本期投注类型单价（会话创建时确定）
+    pub ticket_prices: TicketPrices,
+    /// 信标随机数请求，首次结算时发出
+    pub beacon_request: Option<BeaconRequest>,
+    /// 是否已取消（无人揭秘，或结算阶段结束仍未结算），取消后投注者可通过 Refund 取回投注
+    pub cancelled: bool,
 }
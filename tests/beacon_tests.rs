@@ -0,0 +1,269 @@
+use cosmwasm_std::{coins, Addr, Decimal, HexBinary, Timestamp, Uint128};
+use cw_multi_test::{App, ContractWrapper, Executor};
+use std::str::FromStr;
+
+use dd_3d_lottery::{
+    contract::instantiate,
+    execute::execute,
+    query::query,
+    msg::{InstantiateMsg, ExecuteMsg, QueryMsg, ParticipantsResponse, LotteryResultResponse},
+    state::{BeaconConfig, RandomnessSource, Participant},
+    lottery_logic::LotteryLogic,
+    error::ContractError,
+};
+
+const DENOM: &str = "uusd";
+/// 信标每30秒发布一轮，第1轮发布于时间0
+const BEACON_PERIOD: u64 = 30;
+
+/// 模拟随机数信标代理合约
+mod mock_beacon {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{
+        to_json_binary, Addr, Binary, Deps, DepsMut, Env, HexBinary, MessageInfo, Response, StdResult, WasmMsg,
+    };
+    use cw_storage_plus::Item;
+    use dd_3d_lottery::msg::ExecuteMsg as LotteryExecuteMsg;
+
+    /// 最近一次随机数请求 (请求方, 任务ID, 轮次)
+    const LAST_REQUEST: Item<(Addr, String, u64)> = Item::new("last_request");
+
+    #[cw_serde]
+    pub struct InstantiateMsg {}
+
+    #[cw_serde]
+    pub enum ExecuteMsg {
+        /// 与 `BeaconExecuteMsg::RequestRandomness` 的格式一致
+        RequestRandomness { job_id: String, round: u64 },
+        /// 模拟发布一个轮次并回调请求方
+        Deliver { round: u64, randomness: HexBinary },
+    }
+
+    #[cw_serde]
+    pub enum QueryMsg {
+        LastRequest {},
+    }
+
+    pub fn instantiate(_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: InstantiateMsg) -> StdResult<Response> {
+        Ok(Response::new())
+    }
+
+    pub fn execute(deps: DepsMut, _env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+        match msg {
+            ExecuteMsg::RequestRandomness { job_id, round } => {
+                LAST_REQUEST.save(deps.storage, &(info.sender, job_id, round))?;
+                Ok(Response::new())
+            }
+            ExecuteMsg::Deliver { round, randomness } => {
+                let (requester, job_id, _) = LAST_REQUEST.load(deps.storage)?;
+                let callback = WasmMsg::Execute {
+                    contract_addr: requester.to_string(),
+                    msg: to_json_binary(&LotteryExecuteMsg::BeaconCallback { job_id, round, randomness })?,
+                    funds: vec![],
+                };
+                Ok(Response::new().add_message(callback))
+            }
+        }
+    }
+
+    pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+        match msg {
+            QueryMsg::LastRequest {} => to_json_binary(&LAST_REQUEST.may_load(deps.storage)?),
+        }
+    }
+}
+
+/// 生成承诺哈希的辅助函数
+fn generate_commitment_hash(bet_amount: u128, lucky_numbers: &[u16], random_seed: &str) -> String {
+    use sha2::{Sha256, Digest};
+
+    let numbers_str = lucky_numbers.iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let commitment_data = format!("{}|{}|{}", bet_amount, numbers_str, random_seed);
+
+    hex::encode(Sha256::digest(commitment_data.as_bytes()))
+}
+
+fn mock_instantiate_msg(admin: &Addr, randomness_source: RandomnessSource) -> InstantiateMsg {
+    InstantiateMsg {
+        admin: admin.to_string(),
+        service_fee_rate: Decimal::from_str("0.1").unwrap(),
+        min_bet_amount: Uint128::from(1000u128),
+        max_bet_amount: Uint128::from(1000000u128),
+        bet_denom: DENOM.to_string(),
+        pause_requested: Some(false),
+        claim_expiry_blocks: None,
+        commitment_blocks: None,
+        reveal_blocks: None,
+        settlement_blocks: None,
+        genesis_height: None,
+        schedule_mode: None,
+        commitment_seconds: None,
+        reveal_seconds: None,
+        settlement_seconds: None,
+        genesis_time: None,
+        non_reveal_policy: None,
+        commitment_deposit: None,
+        randomness_source: Some(randomness_source),
+        prize_structure: None,
+        prize_tiers: None,
+        ticket_prices: None,
+        cw20_token: None,
+        extra_funds_policy: None,
+        fee_recipients: None,
+        fee_distribution: None,
+        referral_fee_share: None,
+    }
+}
+
+fn set_height(app: &mut App, height: u64) {
+    app.update_block(|block| {
+        block.height = height;
+        block.time = Timestamp::from_seconds(height * 6);
+    });
+}
+
+/// 部署模拟信标和彩票合约，两名用户完成投注和揭秘后进入结算阶段（高度9500）
+/// 返回 (app, 彩票合约, 信标合约, 管理员)
+fn setup_until_settlement(source: fn(BeaconConfig) -> RandomnessSource) -> (App, Addr, Addr, Addr) {
+    let api = cosmwasm_std::testing::MockApi::default();
+    let admin = api.addr_make("admin");
+    let users = [api.addr_make("user1"), api.addr_make("user2")];
+
+    let mut app = App::new(|router, _api, storage| {
+        for user in &users {
+            router.bank.init_balance(storage, user, coins(1000, DENOM)).unwrap();
+        }
+    });
+    set_height(&mut app, 1000);
+
+    let beacon_code = app.store_code(Box::new(ContractWrapper::new(
+        mock_beacon::execute,
+        mock_beacon::instantiate,
+        mock_beacon::query,
+    )));
+    let beacon = app
+        .instantiate_contract(beacon_code, admin.clone(), &mock_beacon::InstantiateMsg {}, &[], "beacon", None)
+        .unwrap();
+
+    let lottery_code = app.store_code(Box::new(ContractWrapper::new(execute, instantiate, query)));
+    let source = source(BeaconConfig {
+        address: beacon.clone(),
+        genesis_time: 0,
+        period: BEACON_PERIOD,
+    });
+    let lottery = app
+        .instantiate_contract(lottery_code, admin.clone(), &mock_instantiate_msg(&admin, source), &[], "lottery", None)
+        .unwrap();
+
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    for (i, user) in users.iter().enumerate() {
+        let bet_msg = ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, &format!("user{}_seed", i)),
+            referrer: None,
+        };
+        app.execute_contract(user.clone(), lottery.clone(), &bet_msg, &coins(1000, DENOM)).unwrap();
+    }
+
+    set_height(&mut app, 7000);
+    for (i, user) in users.iter().enumerate() {
+        let reveal_msg = ExecuteMsg::RevealRandom {
+            lucky_numbers: all_numbers.clone(),
+            random_seed: format!("user{}_seed", i),
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        };
+        app.execute_contract(user.clone(), lottery.clone(), &reveal_msg, &[]).unwrap();
+    }
+
+    set_height(&mut app, 9500);
+    (app, lottery, beacon, admin)
+}
+
+fn query_result(app: &App, lottery: &Addr) -> LotteryResultResponse {
+    app.wrap()
+        .query_wasm_smart(lottery, &QueryMsg::GetLotteryResult { session_id: "session_0".to_string() })
+        .unwrap()
+}
+
+fn query_participants(app: &App, lottery: &Addr) -> Vec<Participant> {
+    let res: ParticipantsResponse = app.wrap()
+        .query_wasm_smart(lottery, &QueryMsg::GetParticipants { start_after: None, limit: None, order: None })
+        .unwrap();
+    res.participants
+}
+
+#[test]
+fn test_beacon_randomness_settlement() {
+    let (mut app, lottery, beacon, admin) = setup_until_settlement(RandomnessSource::Beacon);
+    let randomness = HexBinary::from([7u8; 32]);
+
+    // 首次结算向信标请求结算时间之后的下一轮：57000 / 30 + 2
+    app.execute_contract(admin.clone(), lottery.clone(), &ExecuteMsg::SettleLottery { limit: None }, &[]).unwrap();
+    let request: Option<(Addr, String, u64)> = app.wrap()
+        .query_wasm_smart(&beacon, &mock_beacon::QueryMsg::LastRequest {})
+        .unwrap();
+    assert_eq!(request, Some((lottery.clone(), "session_0".to_string(), 1902)));
+    assert!(query_result(&app, &lottery).result.is_none());
+
+    // 随机数送达前无法完成结算
+    let err = app.execute_contract(admin.clone(), lottery.clone(), &ExecuteMsg::SettleLottery { limit: None }, &[]).unwrap_err();
+    assert_eq!(err.root_cause().to_string(), ContractError::RandomnessNotReady { round: 1902 }.to_string());
+
+    // 只有配置的信标可以回调
+    let forged = ExecuteMsg::BeaconCallback {
+        job_id: "session_0".to_string(),
+        round: 1902,
+        randomness: randomness.clone(),
+    };
+    let err = app.execute_contract(admin.clone(), lottery.clone(), &forged, &[]).unwrap_err();
+    assert_eq!(err.root_cause().to_string(), ContractError::Unauthorized.to_string());
+
+    // 轮次必须与请求一致
+    let err = app.execute_contract(admin.clone(), beacon.clone(), &mock_beacon::ExecuteMsg::Deliver {
+        round: 1901,
+        randomness: randomness.clone(),
+    }, &[]).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        ContractError::UnexpectedBeaconRound { expected: 1902, actual: 1901 }.to_string()
+    );
+
+    app.execute_contract(admin.clone(), beacon, &mock_beacon::ExecuteMsg::Deliver {
+        round: 1902,
+        randomness: randomness.clone(),
+    }, &[]).unwrap();
+    app.execute_contract(admin, lottery.clone(), &ExecuteMsg::SettleLottery { limit: None }, &[]).unwrap();
+
+    // 中奖号码只由信标随机数决定
+    let participants = query_participants(&app, &lottery);
+    let result = query_result(&app, &lottery).result.unwrap();
+    assert_eq!(result.beacon_round, Some(1902));
+    assert_eq!(result.post_reveal_entropy, randomness.to_hex());
+    assert_eq!(
+        result.winning_number,
+        LotteryLogic::calculate_winning_number_from_beacon(&participants, &randomness.to_hex()).unwrap()
+    );
+}
+
+#[test]
+fn test_hybrid_randomness_mixes_participant_seeds() {
+    let (mut app, lottery, beacon, admin) = setup_until_settlement(RandomnessSource::Hybrid);
+    let randomness = HexBinary::from([42u8; 32]);
+
+    app.execute_contract(admin.clone(), lottery.clone(), &ExecuteMsg::SettleLottery { limit: None }, &[]).unwrap();
+    app.execute_contract(admin.clone(), beacon, &mock_beacon::ExecuteMsg::Deliver {
+        round: 1902,
+        randomness: randomness.clone(),
+    }, &[]).unwrap();
+    app.execute_contract(admin, lottery.clone(), &ExecuteMsg::SettleLottery { limit: None }, &[]).unwrap();
+
+    let participants = query_participants(&app, &lottery);
+    let result = query_result(&app, &lottery).result.unwrap();
+    assert_eq!(
+        result.winning_number,
+        LotteryLogic::calculate_winning_number_with_entropy(&participants, Some(&randomness.to_hex())).unwrap()
+    );
+}
@@ -0,0 +1,254 @@
+use cosmwasm_std::{coins, to_json_binary, Addr, Decimal, Timestamp, Uint128};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+use cw_multi_test::{App, AppResponse, ContractWrapper, Executor};
+use std::str::FromStr;
+
+use dd_3d_lottery::{
+    contract::instantiate,
+    execute::execute,
+    query::query,
+    msg::{InstantiateMsg, ExecuteMsg, QueryMsg, ConfigResponse, Cw20HookMsg},
+    state::BetAsset,
+    error::ContractError,
+};
+
+const DENOM: &str = "uusd";
+
+/// 模拟 cw20-base 代币合约，只实现投注所需的 Transfer / Send / Balance
+mod mock_cw20 {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{
+        to_json_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Uint128,
+    };
+    use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
+    use cw_storage_plus::Map;
+
+    const BALANCES: Map<&Addr, Uint128> = Map::new("balances");
+
+    #[cw_serde]
+    pub struct InstantiateMsg {
+        pub initial_balances: Vec<(String, Uint128)>,
+    }
+
+    pub fn instantiate(deps: DepsMut, _env: Env, _info: MessageInfo, msg: InstantiateMsg) -> StdResult<Response> {
+        for (address, amount) in msg.initial_balances {
+            BALANCES.save(deps.storage, &deps.api.addr_validate(&address)?, &amount)?;
+        }
+        Ok(Response::new())
+    }
+
+    fn move_tokens(deps: DepsMut, from: &Addr, to: &Addr, amount: Uint128) -> StdResult<()> {
+        BALANCES.update(deps.storage, from, |balance| -> StdResult<_> {
+            Ok(balance.unwrap_or_default().checked_sub(amount)?)
+        })?;
+        BALANCES.update(deps.storage, to, |balance| -> StdResult<_> {
+            Ok(balance.unwrap_or_default() + amount)
+        })?;
+        Ok(())
+    }
+
+    pub fn execute(deps: DepsMut, _env: Env, info: MessageInfo, msg: Cw20ExecuteMsg) -> StdResult<Response> {
+        match msg {
+            Cw20ExecuteMsg::Transfer { recipient, amount } => {
+                let recipient = deps.api.addr_validate(&recipient)?;
+                move_tokens(deps, &info.sender, &recipient, amount)?;
+                Ok(Response::new())
+            }
+            Cw20ExecuteMsg::Send { contract, amount, msg } => {
+                let contract = deps.api.addr_validate(&contract)?;
+                move_tokens(deps, &info.sender, &contract, amount)?;
+                let receive = Cw20ReceiveMsg {
+                    sender: info.sender.to_string(),
+                    amount,
+                    msg,
+                };
+                Ok(Response::new().add_message(receive.into_cosmos_msg(contract)?))
+            }
+            _ => Err(StdError::generic_err("unsupported")),
+        }
+    }
+
+    pub fn query(deps: Deps, _env: Env, msg: Cw20QueryMsg) -> StdResult<Binary> {
+        match msg {
+            Cw20QueryMsg::Balance { address } => {
+                let balance = BALANCES
+                    .may_load(deps.storage, &deps.api.addr_validate(&address)?)?
+                    .unwrap_or_default();
+                to_json_binary(&BalanceResponse { balance })
+            }
+            _ => Err(StdError::generic_err("unsupported")),
+        }
+    }
+}
+
+/// 生成承诺哈希的辅助函数
+fn generate_commitment_hash(bet_amount: u128, lucky_numbers: &[u16], random_seed: &str) -> String {
+    use sha2::{Sha256, Digest};
+
+    let numbers_str = lucky_numbers.iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let commitment_data = format!("{}|{}|{}", bet_amount, numbers_str, random_seed);
+
+    hex::encode(Sha256::digest(commitment_data.as_bytes()))
+}
+
+fn mock_instantiate_msg(admin: &Addr, token: &Addr) -> InstantiateMsg {
+    InstantiateMsg {
+        admin: admin.to_string(),
+        service_fee_rate: Decimal::from_str("0.1").unwrap(),
+        min_bet_amount: Uint128::from(1000u128),
+        max_bet_amount: Uint128::from(1000000u128),
+        bet_denom: DENOM.to_string(),
+        pause_requested: Some(false),
+        claim_expiry_blocks: None,
+        commitment_blocks: None,
+        reveal_blocks: None,
+        settlement_blocks: None,
+        genesis_height: None,
+        schedule_mode: None,
+        commitment_seconds: None,
+        reveal_seconds: None,
+        settlement_seconds: None,
+        genesis_time: None,
+        non_reveal_policy: None,
+        commitment_deposit: None,
+        randomness_source: None,
+        prize_structure: None,
+        prize_tiers: None,
+        ticket_prices: None,
+        cw20_token: Some(token.to_string()),
+        extra_funds_policy: None,
+        fee_recipients: None,
+        fee_distribution: None,
+        referral_fee_share: None,
+    }
+}
+
+fn set_height(app: &mut App, height: u64) {
+    app.update_block(|block| {
+        block.height = height;
+        block.time = Timestamp::from_seconds(height * 6);
+    });
+}
+
+fn token_balance(app: &App, token: &Addr, address: &Addr) -> Uint128 {
+    let res: BalanceResponse = app.wrap()
+        .query_wasm_smart(token, &Cw20QueryMsg::Balance { address: address.to_string() })
+        .unwrap();
+    res.balance
+}
+
+fn send_bet(app: &mut App, token: &Addr, lottery: &Addr, user: &Addr, amount: u128, seed: &str) -> AppResponse {
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    let hook = Cw20HookMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(amount, &all_numbers, seed),
+        referrer: None,
+    };
+    app.execute_contract(user.clone(), token.clone(), &Cw20ExecuteMsg::Send {
+        contract: lottery.to_string(),
+        amount: Uint128::from(amount),
+        msg: to_json_binary(&hook).unwrap(),
+    }, &[]).unwrap()
+}
+
+/// 部署模拟CW20代币和以该代币投注的彩票合约
+/// 返回 (app, 彩票合约, 代币合约, 管理员, 用户)
+fn setup() -> (App, Addr, Addr, Addr, Vec<Addr>) {
+    let api = cosmwasm_std::testing::MockApi::default();
+    let admin = api.addr_make("admin");
+    let users = vec![api.addr_make("user1"), api.addr_make("user2")];
+
+    let mut app = App::new(|router, _api, storage| {
+        router.bank.init_balance(storage, &users[0], coins(1000, DENOM)).unwrap();
+    });
+    set_height(&mut app, 1000);
+
+    let token_code = app.store_code(Box::new(ContractWrapper::new(
+        mock_cw20::execute,
+        mock_cw20::instantiate,
+        mock_cw20::query,
+    )));
+    let token = app
+        .instantiate_contract(token_code, admin.clone(), &mock_cw20::InstantiateMsg {
+            initial_balances: users.iter().map(|user| (user.to_string(), Uint128::from(1000u128))).collect(),
+        }, &[], "token", None)
+        .unwrap();
+
+    let lottery_code = app.store_code(Box::new(ContractWrapper::new(execute, instantiate, query)));
+    let lottery = app
+        .instantiate_contract(lottery_code, admin.clone(), &mock_instantiate_msg(&admin, &token), &[], "lottery", None)
+        .unwrap();
+
+    (app, lottery, token, admin, users)
+}
+
+#[test]
+fn test_cw20_config() {
+    let (app, lottery, token, _, _) = setup();
+
+    let res: ConfigResponse = app.wrap().query_wasm_smart(&lottery, &QueryMsg::GetConfig {}).unwrap();
+    assert_eq!(res.config.bet_asset, BetAsset::Cw20 { address: token });
+}
+
+#[test]
+fn test_cw20_bet_rejects_native_and_foreign_tokens() {
+    let (mut app, lottery, _, admin, users) = setup();
+
+    // CW20 投注模式下不接受原生代币投注
+    let bet_msg = ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &[1], "seed"),
+        referrer: None,
+    };
+    let err = app.execute_contract(users[0].clone(), lottery.clone(), &bet_msg, &coins(1000, DENOM)).unwrap_err();
+    assert_eq!(err.root_cause().to_string(), ContractError::InvalidBetDenom.to_string());
+
+    // 非配置代币合约的 Receive 被拒绝
+    let forged = ExecuteMsg::Receive(cw20::Cw20ReceiveMsg {
+        sender: users[0].to_string(),
+        amount: Uint128::from(1000u128),
+        msg: to_json_binary(&Cw20HookMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &[1], "seed"),
+            referrer: None,
+        }).unwrap(),
+    });
+    let err = app.execute_contract(admin, lottery, &forged, &[]).unwrap_err();
+    assert_eq!(err.root_cause().to_string(), ContractError::InvalidBetDenom.to_string());
+}
+
+#[test]
+fn test_cw20_bet_settle_and_claim() {
+    let (mut app, lottery, token, admin, users) = setup();
+
+    for (i, user) in users.iter().enumerate() {
+        send_bet(&mut app, &token, &lottery, user, 1000, &format!("user{}_seed", i));
+        assert_eq!(token_balance(&app, &token, user), Uint128::zero());
+    }
+    assert_eq!(token_balance(&app, &token, &lottery), Uint128::from(2000u128));
+
+    set_height(&mut app, 7000);
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    for (i, user) in users.iter().enumerate() {
+        let reveal_msg = ExecuteMsg::RevealRandom {
+            lucky_numbers: all_numbers.clone(),
+            random_seed: format!("user{}_seed", i),
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        };
+        app.execute_contract(user.clone(), lottery.clone(), &reveal_msg, &[]).unwrap();
+    }
+
+    set_height(&mut app, 9500);
+    app.execute_contract(admin.clone(), lottery.clone(), &ExecuteMsg::SettleLottery { limit: None }, &[]).unwrap();
+
+    // 每人都押注了全部号码，奖金以CW20转账领取
+    let mut claimed = Uint128::zero();
+    for user in &users {
+        app.execute_contract(user.clone(), lottery.clone(), &ExecuteMsg::ClaimReward { session_id: None }, &[]).unwrap();
+        let balance = token_balance(&app, &token, user);
+        assert!(!balance.is_zero());
+        claimed += balance;
+    }
+    assert_eq!(claimed + token_balance(&app, &token, &lottery), Uint128::from(2000u128));
+}
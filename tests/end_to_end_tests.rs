@@ -1,6 +1,6 @@
 use cosmwasm_std::{
     testing::{mock_dependencies, mock_env},
-    Addr, Uint128, Decimal, Timestamp, Coin, MessageInfo,
+    Addr, Uint128, Decimal, Timestamp, Coin, MessageInfo, BankMsg, CosmosMsg, Deps, DepsMut, Order,
 };
 use std::str::FromStr;
 
@@ -8,8 +8,9 @@ use dd_3d_lottery::{
     contract::instantiate,
     execute::execute,
     query,
-    msg::{InstantiateMsg, ExecuteMsg, QueryMsg, CurrentSessionResponse},
-    state::{CURRENT_SESSION, LOTTERY_HISTORY, STATS},
+    msg::{InstantiateMsg, ExecuteMsg, QueryMsg, CurrentSessionResponse, ClaimableResponse, SettlementProgressResponse, NumberPopularity, NumberPopularityResponse, LotteryHistoryResponse, ParticipantsResponse, UserHistoryResponse, UserSessionRecord, UserWinning, WinnersResponse, ClaimStatus, TreasuryResponse, ReferrerStatsResponse},
+    lottery_logic::LotteryLogic,
+    state::{NonRevealPolicy, FeeRecipient, FeeDistribution, CONFIG, BetType, Ticket, TicketPrices, PrizeTier, TierPrize, CURRENT_SESSION, SESSION_DETAILS, COMMITMENTS, CLAIMABLE, LOTTERY_HISTORY, STATS, JACKPOT, WINNINGS, Winner, SettlementStage, participants, load_session_participants},
     error::ContractError,
 };
 
@@ -19,6 +20,14 @@ const USER2: &str = "cosmwasm1ghi789jkl012mno345pqr678stu901vwx234yzabc123def456
 const USER3: &str = "cosmwasm1jkl012mno345pqr678stu901vwx234yzabc123def456ghi789";
 const DENOM: &str = "uusd";
 
+/// 读取会话的全部中奖记录
+fn session_winners(deps: Deps, session_id: &str) -> Vec<Winner> {
+    WINNINGS.sub_prefix(session_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.unwrap().1)
+        .collect()
+}
+
 /// 生成承诺哈希的辅助函数
 fn generate_commitment_hash(bet_amount: u128, lucky_numbers: &[u16], random_seed: &str) -> String {
     use sha2::{Sha256, Digest};
@@ -50,6 +59,27 @@ fn mock_instantiate_msg() -> InstantiateMsg {
         max_bet_amount: Uint128::from(1000000u128),
         bet_denom: DENOM.to_string(),
         pause_requested: Some(false),
+        claim_expiry_blocks: None,
+        commitment_blocks: None,
+        reveal_blocks: None,
+        settlement_blocks: None,
+        genesis_height: None,
+        schedule_mode: None,
+        commitment_seconds: None,
+        reveal_seconds: None,
+        settlement_seconds: None,
+        genesis_time: None,
+        non_reveal_policy: None,
+        commitment_deposit: None,
+        randomness_source: None,
+        prize_structure: None,
+        prize_tiers: None,
+        ticket_prices: None,
+        cw20_token: None,
+        extra_funds_policy: None,
+        fee_recipients: None,
+        fee_distribution: None,
+        referral_fee_share: None,
     }
 }
 
@@ -79,6 +109,7 @@ fn test_complete_lottery_cycle() {
 
     let bet_msg1 = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info1, bet_msg1).unwrap();
@@ -91,6 +122,7 @@ fn test_complete_lottery_cycle() {
 
     let bet_msg2 = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![456; 1000], "user2_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info2, bet_msg2).unwrap();
@@ -103,13 +135,14 @@ fn test_complete_lottery_cycle() {
 
     let bet_msg3 = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![789; 1000], "user3_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info3, bet_msg3).unwrap();
 
     // 验证当前会话
     let session = CURRENT_SESSION.load(&deps.storage).unwrap();
-    assert_eq!(session.participants.len(), 3);
+    assert_eq!(session.participant_count, 3);
     assert_eq!(session.total_pool, Uint128::from(3000u128)); // 1000 + 1000 + 1000
     assert_eq!(session.service_fee, Uint128::from(300u128)); // 3000 * 0.1
 
@@ -121,6 +154,8 @@ fn test_complete_lottery_cycle() {
     let reveal_msg1 = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123; 1000],
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     execute(deps.as_mut(), env.clone(), reveal_info1, reveal_msg1).unwrap();
@@ -130,6 +165,8 @@ fn test_complete_lottery_cycle() {
     let reveal_msg2 = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![456; 1000],
         random_seed: "user2_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     execute(deps.as_mut(), env.clone(), reveal_info2, reveal_msg2).unwrap();
@@ -139,13 +176,17 @@ fn test_complete_lottery_cycle() {
     let reveal_msg3 = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![789; 1000],
         random_seed: "user3_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     execute(deps.as_mut(), env.clone(), reveal_info3, reveal_msg3).unwrap();
 
     // 验证揭秘状态
     let session = CURRENT_SESSION.load(&deps.storage).unwrap();
-    for participant in &session.participants {
+    assert_eq!(session.revealed_count, 3);
+    assert!(session.pending_deposits.is_zero());
+    for participant in load_session_participants(&deps.storage, &session.session_id).unwrap() {
         assert!(participant.revealed);
         assert!(participant.random_seed.is_some());
     }
@@ -154,7 +195,7 @@ fn test_complete_lottery_cycle() {
     let env = mock_env_with_height(9500);
 
     // 10. 结算彩票
-    let settle_msg = ExecuteMsg::SettleLottery {};
+    let settle_msg = ExecuteMsg::SettleLottery { limit: None };
     execute(deps.as_mut(), env.clone(), MessageInfo { sender: Addr::unchecked(ADMIN), funds: vec![] }, settle_msg).unwrap();
 
     // 验证结算结果
@@ -199,7 +240,8 @@ fn test_multiple_users_concurrent_betting() {
         }]);
 
         let bet_msg = ExecuteMsg::PlaceBet {
-            commitment_hash: generate_commitment_hash(1000 + i as u128 * 100, &vec![123; 1000 + i as usize * 100], &format!("user{}_seed", i)),
+            commitment_hash: generate_commitment_hash(1000 + i as u128 * 100, &vec![123; 1000 + i * 100], &format!("user{}_seed", i)),
+            referrer: None,
         };
 
         execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -207,7 +249,7 @@ fn test_multiple_users_concurrent_betting() {
 
     // 验证所有用户都成功投注
     let session = CURRENT_SESSION.load(&deps.storage).unwrap();
-    assert_eq!(session.participants.len(), 10);
+    assert_eq!(session.participant_count, 10);
 
     // 验证总投注金额
     let total_bet: u128 = (1000..=1900).step_by(100).sum();
@@ -233,6 +275,7 @@ fn test_phase_transition_edge_cases() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     // 应该成功
@@ -244,6 +287,8 @@ fn test_phase_transition_edge_cases() {
     let reveal_msg = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123; 1000],
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     // 应该成功
@@ -251,7 +296,7 @@ fn test_phase_transition_edge_cases() {
 
     // 测试结算阶段开始时刻
     let env_settlement_start = mock_env_with_height(9000);
-    let settle_msg = ExecuteMsg::SettleLottery {};
+    let settle_msg = ExecuteMsg::SettleLottery { limit: None };
     
     // 应该成功
     execute(deps.as_mut(), env_settlement_start, MessageInfo { sender: Addr::unchecked(ADMIN), funds: vec![] }, settle_msg).unwrap();
@@ -268,8 +313,8 @@ fn test_lottery_with_no_winners() {
     instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
 
     // 用户投注，但都投注不同的号码
-    let users = vec![USER1, USER2, USER3];
-    let lucky_numbers = vec![vec![111; 1000], vec![222; 1000], vec![333; 1000]];
+    let users = [USER1, USER2, USER3];
+    let lucky_numbers = [vec![111; 1000], vec![222; 1000], vec![333; 1000]];
 
     for (i, (user, numbers)) in users.iter().zip(lucky_numbers.iter()).enumerate() {
         let bet_info = mock_info(user, &[Coin {
@@ -279,6 +324,7 @@ fn test_lottery_with_no_winners() {
 
         let bet_msg = ExecuteMsg::PlaceBet {
             commitment_hash: generate_commitment_hash(1000, numbers, &format!("user{}_seed", i)),
+            referrer: None,
         };
 
         execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -292,6 +338,8 @@ fn test_lottery_with_no_winners() {
         let reveal_msg = ExecuteMsg::RevealRandom {
             lucky_numbers: numbers.clone(),
             random_seed: format!("user{}_seed", i),
+            tickets: vec![],
+            lucky_number_counts: vec![],
         };
 
         execute(deps.as_mut(), env.clone(), reveal_info, reveal_msg).unwrap();
@@ -299,7 +347,7 @@ fn test_lottery_with_no_winners() {
 
     // 切换到结算阶段
     let env = mock_env_with_height(9500);
-    let settle_msg = ExecuteMsg::SettleLottery {};
+    let settle_msg = ExecuteMsg::SettleLottery { limit: None };
     execute(deps.as_mut(), env.clone(), MessageInfo { sender: Addr::unchecked(ADMIN), funds: vec![] }, settle_msg).unwrap();
 
     // 验证结算结果
@@ -308,7 +356,8 @@ fn test_lottery_with_no_winners() {
     assert!(session.winning_number.is_some());
 
     let lottery_result = LOTTERY_HISTORY.load(&deps.storage, session.session_id.clone()).unwrap();
-    assert_eq!(lottery_result.winners.len(), 0); // 无人中奖
+    assert_eq!(lottery_result.winners_count, 0); // 无人中奖
+    assert!(session_winners(deps.as_ref(), &session.session_id).is_empty());
 }
 
 #[test]
@@ -322,8 +371,8 @@ fn test_lottery_with_multiple_winners() {
     instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
 
     // 所有用户都投注相同的号码123
-    let users = vec![USER1, USER2, USER3];
-    let lucky_numbers = vec![vec![123; 1000], vec![123; 1000], vec![123; 1000]];
+    let users = [USER1, USER2, USER3];
+    let lucky_numbers = [vec![123; 1000], vec![123; 1000], vec![123; 1000]];
 
     for (i, (user, numbers)) in users.iter().zip(lucky_numbers.iter()).enumerate() {
         let bet_info = mock_info(user, &[Coin {
@@ -333,6 +382,7 @@ fn test_lottery_with_multiple_winners() {
 
         let bet_msg = ExecuteMsg::PlaceBet {
             commitment_hash: generate_commitment_hash(1000, numbers, &format!("user{}_seed", i)),
+            referrer: None,
         };
 
         execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -346,6 +396,8 @@ fn test_lottery_with_multiple_winners() {
         let reveal_msg = ExecuteMsg::RevealRandom {
             lucky_numbers: numbers.clone(),
             random_seed: format!("user{}_seed", i),
+            tickets: vec![],
+            lucky_number_counts: vec![],
         };
 
         execute(deps.as_mut(), env.clone(), reveal_info, reveal_msg).unwrap();
@@ -353,7 +405,7 @@ fn test_lottery_with_multiple_winners() {
 
     // 切换到结算阶段
     let env = mock_env_with_height(9500);
-    let settle_msg = ExecuteMsg::SettleLottery {};
+    let settle_msg = ExecuteMsg::SettleLottery { limit: None };
     execute(deps.as_mut(), env.clone(), MessageInfo { sender: Addr::unchecked(ADMIN), funds: vec![] }, settle_msg).unwrap();
 
     // 验证结算结果
@@ -364,18 +416,869 @@ fn test_lottery_with_multiple_winners() {
     
     // 验证中奖者数量（每个用户投注123号码1000次，如果中奖号码是123，则每个用户中奖1000次）
     if lottery_result.winning_number == 123 {
-        assert!(lottery_result.winners.len() > 0);
+        let winners = session_winners(deps.as_ref(), &session.session_id);
+        assert_eq!(winners.len(), 3);
+        assert_eq!(lottery_result.winners_count, 3);
         
         // 验证每个中奖者都获得相同的奖金
-        if !lottery_result.winners.is_empty() {
-            let first_reward = lottery_result.winners[0].reward_amount;
-            for winner in &lottery_result.winners {
-                assert_eq!(winner.reward_amount, first_reward);
-            }
+        let first_reward = winners[0].reward_amount;
+        for winner in &winners {
+            assert_eq!(winner.reward_amount, first_reward);
         }
     }
 }
 
+/// 两名用户各自覆盖全部1000个号码并完成揭秘和结算，保证每人恰好中奖一次
+fn settle_with_guaranteed_winners(mut deps: DepsMut, users: &[&str]) {
+    let all_numbers: Vec<u16> = (0..1000).collect();
+
+    let env = mock_env_with_height(1000);
+    for (i, user) in users.iter().enumerate() {
+        let bet_info = mock_info(user, &[Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(1000u128),
+        }]);
+
+        let bet_msg = ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, &format!("user{}_seed", i)),
+            referrer: None,
+        };
+
+        execute(deps.branch(), env.clone(), bet_info, bet_msg).unwrap();
+    }
+
+    let env = mock_env_with_height(7000);
+    for (i, user) in users.iter().enumerate() {
+        let reveal_msg = ExecuteMsg::RevealRandom {
+            lucky_numbers: all_numbers.clone(),
+            random_seed: format!("user{}_seed", i),
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        };
+
+        execute(deps.branch(), env.clone(), mock_info(user, &[]), reveal_msg).unwrap();
+    }
+
+    let env = mock_env_with_height(9500);
+    execute(deps.branch(), env, mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+}
+
+#[test]
+fn test_settlement_records_claimable_rewards() {
+    // 测试结算后中奖者可以领取奖金
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+    settle_with_guaranteed_winners(deps.as_mut(), &[USER1, USER2]);
+
+    // 奖金池1800，两名中奖者各获得固定奖金800，结算时不直接转账
+    let session = CURRENT_SESSION.load(&deps.storage).unwrap();
+    let lottery_result = LOTTERY_HISTORY.load(&deps.storage, session.session_id.clone()).unwrap();
+    assert_eq!(lottery_result.winners_count, 2);
+    assert_eq!(lottery_result.tiers[0].count, 2);
+    assert_eq!(lottery_result.total_paid, Uint128::zero());
+    assert_eq!(lottery_result.total_pending, Uint128::from(1600u128));
+
+    let env = mock_env_with_height(9600);
+    let res_binary = query::query(deps.as_ref(), env.clone(), QueryMsg::GetClaimable {
+        address: USER1.to_string(),
+    }).unwrap();
+    let res: ClaimableResponse = cosmwasm_std::from_json(&res_binary).unwrap();
+    assert_eq!(res.claims.len(), 1);
+    assert_eq!(res.total, Uint128::from(800u128));
+
+    // USER1 领取全部奖金
+    let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::ClaimReward {
+        session_id: None,
+    }).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: USER1.to_string(),
+        amount: vec![Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(800u128),
+        }],
+    }));
+
+    // 重复领取失败
+    let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::ClaimReward {
+        session_id: Some(session.session_id.clone()),
+    });
+    assert!(matches!(res, Err(ContractError::NothingToClaim)));
+
+    let lottery_result = LOTTERY_HISTORY.load(&deps.storage, session.session_id).unwrap();
+    assert_eq!(lottery_result.total_paid, Uint128::from(800u128));
+    assert_eq!(lottery_result.total_pending, Uint128::from(800u128));
+}
+
+#[test]
+fn test_get_winners() {
+    // 测试按 (中奖者, 奖级) 分页查询已结算会话的中奖记录
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    let winners = |deps: Deps, start_after: Option<(String, u8)>, limit: Option<u32>| -> WinnersResponse {
+        let res = query::query(deps, mock_env_with_height(9600), QueryMsg::GetWinners {
+            session_id: "session_0".to_string(),
+            start_after,
+            limit,
+        }).unwrap();
+        cosmwasm_std::from_json(&res).unwrap()
+    };
+
+    // 结算前没有中奖记录
+    assert!(winners(deps.as_ref(), None, None).winners.is_empty());
+
+    settle_with_guaranteed_winners(deps.as_mut(), &[USER1, USER2, USER3]);
+    let all = winners(deps.as_ref(), None, None);
+    assert_eq!(all.winners_count, 3);
+    assert_eq!(all.winners, session_winners(deps.as_ref(), "session_0"));
+    assert_eq!(all.winners.iter().map(|w| w.address.as_str()).collect::<Vec<_>>(), vec![USER1, USER2, USER3]);
+    assert!(all.winners.iter().all(|w| w.level == 1 && w.count == 1 && w.reward_amount == Uint128::from(800u128)));
+
+    let first = winners(deps.as_ref(), None, Some(2));
+    assert_eq!(first.winners, all.winners[..2].to_vec());
+    let last = &first.winners[1];
+    let rest = winners(deps.as_ref(), Some((last.address.to_string(), last.level)), Some(2));
+    assert_eq!(rest.winners, all.winners[2..].to_vec());
+}
+
+#[test]
+fn test_expired_rewards_return_to_pool() {
+    // 测试过期未领取的奖金退回累积奖池
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.claim_expiry_blocks = Some(100);
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+    settle_with_guaranteed_winners(deps.as_mut(), &[USER1, USER2]);
+
+    let session = CURRENT_SESSION.load(&deps.storage).unwrap();
+    assert_eq!(JACKPOT.load(&deps.storage).unwrap(), Uint128::from(200u128));
+
+    // 截止高度之前不能回收
+    let res = execute(deps.as_mut(), mock_env_with_height(9550), mock_info(USER3, &[]), ExecuteMsg::ExpireRewards {
+        session_id: session.session_id.clone(),
+    });
+    assert!(matches!(res, Err(ContractError::ClaimNotExpired { deadline: 9600 })));
+
+    // USER1 在截止前领取
+    execute(deps.as_mut(), mock_env_with_height(9600), mock_info(USER1, &[]), ExecuteMsg::ClaimReward {
+        session_id: Some(session.session_id.clone()),
+    }).unwrap();
+
+    // USER2 过期后无法领取
+    let env = mock_env_with_height(9601);
+    let res = execute(deps.as_mut(), env.clone(), mock_info(USER2, &[]), ExecuteMsg::ClaimReward {
+        session_id: Some(session.session_id.clone()),
+    });
+    assert!(matches!(res, Err(ContractError::ClaimExpired { deadline: 9600 })));
+
+    execute(deps.as_mut(), env, mock_info(USER3, &[]), ExecuteMsg::ExpireRewards {
+        session_id: session.session_id.clone(),
+    }).unwrap();
+
+    let lottery_result = LOTTERY_HISTORY.load(&deps.storage, session.session_id).unwrap();
+    assert_eq!(lottery_result.total_paid, Uint128::from(800u128));
+    assert_eq!(lottery_result.total_pending, Uint128::zero());
+    assert_eq!(lottery_result.total_expired, Uint128::from(800u128));
+    assert_eq!(JACKPOT.load(&deps.storage).unwrap(), Uint128::from(1000u128));
+
+    // 回收后过期的待领取余额仍不能领取，一键领取时删除过期条目，不发放奖金
+    let res = execute(deps.as_mut(), mock_env_with_height(9700), mock_info(USER2, &[]), ExecuteMsg::ClaimReward {
+        session_id: None,
+    }).unwrap();
+    assert!(res.messages.is_empty());
+    assert!(res.attributes.iter().any(|attr| attr.key == "expired_sessions" && attr.value == lottery_result.session_id));
+    assert!(!CLAIMABLE.has(&deps.storage, (&Addr::unchecked(USER2), lottery_result.session_id.as_str())));
+    let res = execute(deps.as_mut(), mock_env_with_height(9700), mock_info(USER2, &[]), ExecuteMsg::ClaimReward {
+        session_id: None,
+    });
+    assert!(matches!(res, Err(ContractError::NothingToClaim)));
+
+    // 删除条目后投注记录仍显示为过期，已领取的显示为已领取
+    let status = |deps: Deps, address: &str| -> ClaimStatus {
+        let res = query::query(deps, mock_env_with_height(9700), QueryMsg::GetUserHistory {
+            address: address.to_string(),
+            start_after: None,
+            limit: None,
+        }).unwrap();
+        let res: UserHistoryResponse = cosmwasm_std::from_json(&res).unwrap();
+        res.records[0].claim_status.clone()
+    };
+    assert_eq!(status(deps.as_ref(), USER2), ClaimStatus::Expired);
+    assert_eq!(status(deps.as_ref(), USER1), ClaimStatus::Claimed);
+
+    // 不能重复回收
+    let res = execute(deps.as_mut(), mock_env_with_height(9700), mock_info(USER3, &[]), ExecuteMsg::ExpireRewards {
+        session_id: lottery_result.session_id.clone(),
+    });
+    assert!(matches!(res, Err(ContractError::NothingToClaim)));
+}
+
+#[test]
+fn test_claim_expiry_does_not_overflow() {
+    // 测试领取期限极大时截止高度不溢出，奖金永不过期
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.claim_expiry_blocks = Some(u64::MAX);
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+    settle_with_guaranteed_winners(deps.as_mut(), &[USER1]);
+
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    assert_eq!(result.claim_deadline_height, u64::MAX);
+    execute(deps.as_mut(), mock_env_with_height(1_000_000_000), mock_info(USER1, &[]), ExecuteMsg::ClaimReward {
+        session_id: None,
+    }).unwrap();
+}
+
+#[test]
+fn test_jackpot_rolls_into_next_session() {
+    // 测试奖金分配后的剩余滚入下一期奖金池
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    // 第0期：奖金池900，唯一中奖者获得800，剩余100滚入下一期
+    settle_with_guaranteed_winners(deps.as_mut(), &[USER1]);
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    assert_eq!(result.jackpot_in, Uint128::zero());
+    assert_eq!(result.reward_pool, Uint128::from(900u128));
+    assert_eq!(result.jackpot_out, Uint128::from(100u128));
+
+    // 第1期：两名中奖者，奖金池 1800 + 100
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    for (i, user) in [USER2, USER3].iter().enumerate() {
+        let seed = format!("next_seed_{}", i);
+        execute(deps.as_mut(), mock_env_with_height(11000), mock_info(user, &[Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(1000u128),
+        }]), ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, &seed),
+            referrer: None,
+        }).unwrap();
+        execute(deps.as_mut(), mock_env_with_height(17000), mock_info(user, &[]), ExecuteMsg::RevealRandom {
+            lucky_numbers: all_numbers.clone(),
+            random_seed: seed,
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        }).unwrap();
+    }
+
+    let res_binary = query::query(deps.as_ref(), mock_env_with_height(17000), QueryMsg::GetCurrentSession {}).unwrap();
+    let res: CurrentSessionResponse = cosmwasm_std::from_json(&res_binary).unwrap();
+    assert_eq!(res.jackpot, Uint128::from(100u128));
+
+    execute(deps.as_mut(), mock_env_with_height(19500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_1".to_string()).unwrap();
+    assert_eq!(result.jackpot_in, Uint128::from(100u128));
+    assert_eq!(result.reward_pool, Uint128::from(1900u128));
+    assert_eq!(result.jackpot_out, Uint128::from(300u128));
+    assert_eq!(JACKPOT.load(&deps.storage).unwrap(), Uint128::from(300u128));
+}
+
+/// 用户投注但不揭秘
+fn bet_without_reveal(deps: DepsMut, user: &str) {
+    let bet_info = mock_info(user, &[Coin {
+        denom: DENOM.to_string(),
+        amount: Uint128::from(1000u128),
+    }]);
+    let bet_msg = ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &vec![456; 1000], "silent_seed"),
+        referrer: None,
+    };
+    execute(deps, mock_env_with_height(1000), bet_info, bet_msg).unwrap();
+}
+
+#[test]
+fn test_non_revealer_partial_refund() {
+    // 测试未揭秘者扣除罚金后退还剩余投注
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.non_reveal_policy = Some(NonRevealPolicy::PartialRefund {
+        penalty_rate: Decimal::from_str("0.2").unwrap(),
+    });
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+
+    bet_without_reveal(deps.as_mut(), USER3);
+    settle_with_guaranteed_winners(deps.as_mut(), &[USER1, USER2]);
+
+    // 总投注3000，服务费300；USER3 净投注900，罚金180留在奖金池，退还720
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    assert_eq!(result.non_reveal.non_revealed_count, 1);
+    assert_eq!(result.non_reveal.non_revealed_amount, Uint128::from(1000u128));
+    assert_eq!(result.non_reveal.forfeited_to_pool, Uint128::from(180u128));
+    assert_eq!(result.non_reveal.refunded, Uint128::from(720u128));
+    assert_eq!(result.reward_pool, Uint128::from(1980u128));
+    assert_eq!(result.total_pending, Uint128::from(1600u128 + 720u128));
+
+    // 退款通过 ClaimReward 领取
+    let res = execute(deps.as_mut(), mock_env_with_height(9600), mock_info(USER3, &[]), ExecuteMsg::ClaimReward {
+        session_id: None,
+    }).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: USER3.to_string(),
+        amount: vec![Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(720u128),
+        }],
+    }));
+}
+
+#[test]
+fn test_non_revealer_forfeit_to_treasury() {
+    // 测试未揭秘者的投注没收至服务费
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.non_reveal_policy = Some(NonRevealPolicy::ForfeitToTreasury);
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+
+    bet_without_reveal(deps.as_mut(), USER3);
+    settle_with_guaranteed_winners(deps.as_mut(), &[USER1, USER2]);
+
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    assert_eq!(result.non_reveal.policy, NonRevealPolicy::ForfeitToTreasury);
+    assert_eq!(result.non_reveal.forfeited_to_treasury, Uint128::from(900u128));
+    assert_eq!(result.non_reveal.refunded, Uint128::zero());
+    assert_eq!(result.reward_pool, Uint128::from(1800u128));
+    assert!(!CLAIMABLE.has(&deps.storage, (&Addr::unchecked(USER3), "session_0")));
+
+    let stats = STATS.load(&deps.storage).unwrap();
+    assert_eq!(stats.total_service_fee, Uint128::from(1200u128));
+}
+
+fn query_treasury(deps: Deps) -> TreasuryResponse {
+    let res = query::query(deps, mock_env_with_height(9600), QueryMsg::GetTreasury {}).unwrap();
+    cosmwasm_std::from_json(&res).unwrap()
+}
+
+#[test]
+fn test_treasury_ledger_and_fee_withdrawal() {
+    // 测试服务费在结算时计提，提取不能超过已计提金额，合约余额与各项应付资金一致
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.non_reveal_policy = Some(NonRevealPolicy::ForfeitToTreasury);
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+    let contract = mock_env().contract.address;
+
+    // 投注时尚未计提服务费，管理员不能提取投注资金
+    bet_without_reveal(deps.as_mut(), USER3);
+    deps.querier.bank.update_balance(&contract, vec![Coin::new(1000u128, DENOM)]);
+    let treasury = query_treasury(deps.as_ref());
+    assert_eq!(treasury.active_pool, Uint128::from(1000u128));
+    assert_eq!(treasury.accrued_fees, Uint128::zero());
+    let res = execute(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), ExecuteMsg::WithdrawServiceFee {
+        amount: Uint128::from(100u128),
+    });
+    assert_eq!(res.unwrap_err(), ContractError::InsufficientAccruedFees { available: Uint128::zero() });
+
+    settle_with_guaranteed_winners(deps.as_mut(), &[USER1, USER2]);
+    deps.querier.bank.update_balance(&contract, vec![Coin::new(3000u128, DENOM)]);
+
+    // 服务费300加没收的900计提为服务费，其余为待领取奖金和累积奖池
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    let treasury = query_treasury(deps.as_ref());
+    assert_eq!(treasury.active_pool, Uint128::zero());
+    assert_eq!(treasury.accrued_fees, Uint128::from(1200u128));
+    assert_eq!(STATS.load(&deps.storage).unwrap().total_service_fee, treasury.accrued_fees);
+    assert_eq!(treasury.unclaimed_prizes, result.total_pending);
+    assert_eq!(treasury.jackpot, result.jackpot_out);
+    assert_eq!(treasury.liabilities, Uint128::from(3000u128));
+    assert_eq!(treasury.balance, treasury.liabilities);
+    assert!(treasury.solvent);
+
+    let res = execute(deps.as_mut(), mock_env_with_height(9600), mock_info(ADMIN, &[]), ExecuteMsg::WithdrawServiceFee {
+        amount: Uint128::from(1201u128),
+    });
+    assert_eq!(res.unwrap_err(), ContractError::InsufficientAccruedFees { available: Uint128::from(1200u128) });
+    execute(deps.as_mut(), mock_env_with_height(9600), mock_info(ADMIN, &[]), ExecuteMsg::WithdrawServiceFee {
+        amount: Uint128::from(1200u128),
+    }).unwrap();
+
+    // 领取奖金后台账同步减少
+    let res = execute(deps.as_mut(), mock_env_with_height(9600), mock_info(USER1, &[]), ExecuteMsg::ClaimReward {
+        session_id: None,
+    }).unwrap();
+    let claimed = res.attributes.iter().find(|attr| attr.key == "amount").unwrap().value.parse::<u128>().unwrap();
+    deps.querier.bank.update_balance(&contract, vec![Coin::new(1800u128 - claimed, DENOM)]);
+    let treasury = query_treasury(deps.as_ref());
+    assert_eq!(treasury.accrued_fees, Uint128::zero());
+    assert_eq!(treasury.unclaimed_prizes, result.total_pending - Uint128::from(claimed));
+    assert_eq!(treasury.balance, treasury.liabilities);
+}
+
+#[test]
+fn test_service_fee_split_on_withdraw() {
+    // 测试提取服务费时按分成表权重发放
+    let mut deps = mock_dependencies();
+    let dao = deps.api.addr_make("dao_treasury");
+    let operator = deps.api.addr_make("node_operator");
+    let dev_fund = deps.api.addr_make("dev_fund");
+    let mut msg = mock_instantiate_msg();
+    msg.non_reveal_policy = Some(NonRevealPolicy::ForfeitToTreasury);
+    msg.fee_recipients = Some(vec![
+        FeeRecipient { address: dao.clone(), weight: 5 },
+        FeeRecipient { address: operator.clone(), weight: 3 },
+        FeeRecipient { address: dev_fund.clone(), weight: 2 },
+    ]);
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+
+    bet_without_reveal(deps.as_mut(), USER3);
+    settle_with_guaranteed_winners(deps.as_mut(), &[USER1, USER2]);
+    deps.querier.bank.update_balance(mock_env().contract.address, vec![Coin::new(3000u128, DENOM)]);
+
+    let res = execute(deps.as_mut(), mock_env_with_height(9600), mock_info(ADMIN, &[]), ExecuteMsg::WithdrawServiceFee {
+        amount: Uint128::from(1200u128),
+    }).unwrap();
+    let sends: Vec<_> = res.messages.iter().map(|msg| msg.msg.clone()).collect();
+    assert_eq!(sends, [(dao, 600u128), (operator, 360), (dev_fund, 240)].map(|(address, amount)| {
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: address.to_string(),
+            amount: vec![Coin::new(amount, DENOM)],
+        })
+    }));
+
+    // 分成表不能有重复收款人或零权重
+    let config = CONFIG.load(&deps.storage).unwrap();
+    for recipients in [
+        vec![FeeRecipient { address: config.fee_recipients[0].address.clone(), weight: 1 }; 2],
+        vec![FeeRecipient { address: config.fee_recipients[0].address.clone(), weight: 0 }],
+    ] {
+        let res = execute(deps.as_mut(), mock_env_with_height(9600), mock_info(ADMIN, &[]), ExecuteMsg::UpdateConfig {
+            service_fee_rate: None,
+            min_bet_amount: None,
+            max_bet_amount: None,
+            bet_denom: None,
+            pause_requested: None,
+            claim_expiry_blocks: None,
+            commitment_blocks: None,
+            reveal_blocks: None,
+            settlement_blocks: None,
+            commitment_seconds: None,
+            reveal_seconds: None,
+            settlement_seconds: None,
+            non_reveal_policy: None,
+            commitment_deposit: None,
+            randomness_source: None,
+            prize_structure: None,
+            prize_tiers: None,
+            ticket_prices: None,
+            extra_funds_policy: None,
+            fee_recipients: Some(recipients),
+            fee_distribution: None,
+            referral_fee_share: None,
+        });
+        assert_eq!(res.unwrap_err(), ContractError::InvalidConfiguration);
+    }
+}
+
+#[test]
+fn test_service_fee_split_at_settlement() {
+    // 测试结算时直接按分成表发放服务费，不再计提待提取
+    let mut deps = mock_dependencies();
+    let dao = deps.api.addr_make("dao_treasury");
+    let operator = deps.api.addr_make("node_operator");
+    let mut msg = mock_instantiate_msg();
+    msg.fee_recipients = Some(vec![
+        FeeRecipient { address: dao.clone(), weight: 2 },
+        FeeRecipient { address: operator.clone(), weight: 1 },
+    ]);
+    msg.fee_distribution = Some(FeeDistribution::AtSettlement);
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    for (i, user) in [USER1, USER2].iter().enumerate() {
+        execute(deps.as_mut(), mock_env_with_height(1000), mock_info(user, &[Coin::new(1000u128, DENOM)]), ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, &format!("user{}_seed", i)),
+            referrer: None,
+        }).unwrap();
+    }
+    for (i, user) in [USER1, USER2].iter().enumerate() {
+        execute(deps.as_mut(), mock_env_with_height(7000), mock_info(user, &[]), ExecuteMsg::RevealRandom {
+            lucky_numbers: all_numbers.clone(),
+            random_seed: format!("user{}_seed", i),
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        }).unwrap();
+    }
+    let res = execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+
+    // 服务费200按2:1拆分，向下取整的余数归第一个收款人
+    let sends: Vec<_> = res.messages.iter().map(|msg| msg.msg.clone()).collect();
+    assert_eq!(sends, [(dao, 134u128), (operator, 66)].map(|(address, amount)| {
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: address.to_string(),
+            amount: vec![Coin::new(amount, DENOM)],
+        })
+    }));
+    assert_eq!(query_treasury(deps.as_ref()).accrued_fees, Uint128::zero());
+}
+
+#[test]
+fn test_referral_rewards() {
+    // 测试推荐人按比例分得推荐投注的服务费，会话结算后领取
+    let mut deps = mock_dependencies();
+    let referrer = deps.api.addr_make("referrer");
+    let mut msg = mock_instantiate_msg();
+    msg.referral_fee_share = Some(Decimal::from_str("0.5").unwrap());
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+
+    let referrer_stats = |deps: Deps| -> ReferrerStatsResponse {
+        let res = query::query(deps, mock_env_with_height(9600), QueryMsg::GetReferrerStats {
+            address: referrer.to_string(),
+        }).unwrap();
+        cosmwasm_std::from_json(&res).unwrap()
+    };
+
+    // 不能推荐自己，推荐人地址必须有效
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    for (sender, bad_referrer) in [(referrer.as_str(), referrer.to_string()), (USER1, "not_an_address".to_string())] {
+        let res = execute(deps.as_mut(), mock_env_with_height(1000), mock_info(sender, &[Coin::new(1000u128, DENOM)]), ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, "self_seed"),
+            referrer: Some(bad_referrer),
+        });
+        assert_eq!(res.unwrap_err(), ContractError::InvalidReferrer);
+    }
+
+    for (i, user) in [USER1, USER2].iter().enumerate() {
+        execute(deps.as_mut(), mock_env_with_height(1000), mock_info(user, &[Coin::new(1000u128, DENOM)]), ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, &format!("user{}_seed", i)),
+            referrer: Some(referrer.to_string()),
+        }).unwrap();
+    }
+
+    // 每笔服务费100，推荐人分得一半；结算前不能领取
+    let res = referrer_stats(deps.as_ref());
+    assert_eq!(res.stats.referred_bets, 2);
+    assert_eq!(res.stats.referred_volume, Uint128::from(2000u128));
+    assert_eq!(res.stats.earned_fees, Uint128::from(100u128));
+    assert_eq!(res.pending, Uint128::from(100u128));
+    assert_eq!(res.claimable, Uint128::zero());
+    let res = execute(deps.as_mut(), mock_env_with_height(1000), mock_info(referrer.as_str(), &[]), ExecuteMsg::ClaimReferralRewards {});
+    assert_eq!(res.unwrap_err(), ContractError::NothingToClaim);
+
+    for (i, user) in [USER1, USER2].iter().enumerate() {
+        execute(deps.as_mut(), mock_env_with_height(7000), mock_info(user, &[]), ExecuteMsg::RevealRandom {
+            lucky_numbers: all_numbers.clone(),
+            random_seed: format!("user{}_seed", i),
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        }).unwrap();
+    }
+    execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+
+    // 推荐奖励从服务费中扣除
+    let treasury = query_treasury(deps.as_ref());
+    assert_eq!(treasury.accrued_fees, Uint128::from(100u128));
+    assert_eq!(treasury.referral_rewards, Uint128::from(100u128));
+    assert_eq!(referrer_stats(deps.as_ref()).claimable, Uint128::from(100u128));
+    assert_eq!(STATS.load(&deps.storage).unwrap().total_service_fee, treasury.accrued_fees);
+
+    let res = execute(deps.as_mut(), mock_env_with_height(9600), mock_info(referrer.as_str(), &[]), ExecuteMsg::ClaimReferralRewards {}).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: referrer.to_string(),
+        amount: vec![Coin::new(100u128, DENOM)],
+    }));
+
+    let res = referrer_stats(deps.as_ref());
+    assert_eq!(res.stats.claimed_fees, Uint128::from(100u128));
+    assert_eq!(res.claimable, Uint128::zero());
+    assert_eq!(query_treasury(deps.as_ref()).referral_rewards, Uint128::zero());
+}
+
+#[test]
+fn test_treasury_tracks_cancelled_session_refunds() {
+    // 测试取消的会话不计提服务费，投注转为待退款
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    bet_without_reveal(deps.as_mut(), USER1);
+    bet_without_reveal(deps.as_mut(), USER2);
+    execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+
+    let treasury = query_treasury(deps.as_ref());
+    assert_eq!(treasury.active_pool, Uint128::zero());
+    assert_eq!(treasury.pending_refunds, Uint128::from(2000u128));
+    assert_eq!(treasury.accrued_fees, Uint128::zero());
+
+    execute(deps.as_mut(), mock_env_with_height(9600), mock_info(USER1, &[]), ExecuteMsg::Refund {
+        session_id: "session_0".to_string(),
+    }).unwrap();
+    assert_eq!(query_treasury(deps.as_ref()).pending_refunds, Uint128::from(1000u128));
+}
+
+#[test]
+fn test_bet_denom_change_requires_empty_ledgers() {
+    // 测试台账和当前会话仍有原面额资金时不能切换投注面额
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    let update_denom = |deps: DepsMut, height: u64| {
+        execute(deps, mock_env_with_height(height), mock_info(ADMIN, &[]), ExecuteMsg::UpdateConfig {
+            service_fee_rate: None,
+            min_bet_amount: None,
+            max_bet_amount: None,
+            bet_denom: Some("unew".to_string()),
+            pause_requested: None,
+            claim_expiry_blocks: None,
+            commitment_blocks: None,
+            reveal_blocks: None,
+            settlement_blocks: None,
+            commitment_seconds: None,
+            reveal_seconds: None,
+            settlement_seconds: None,
+            non_reveal_policy: None,
+            commitment_deposit: None,
+            randomness_source: None,
+            prize_structure: None,
+            prize_tiers: None,
+            ticket_prices: None,
+            extra_funds_policy: None,
+            fee_recipients: None,
+            fee_distribution: None,
+            referral_fee_share: None,
+        })
+    };
+
+    // 当前会话持有投注
+    bet_without_reveal(deps.as_mut(), USER1);
+    assert_eq!(update_denom(deps.as_mut(), 1000).unwrap_err(), ContractError::OutstandingBalances);
+
+    // 会话取消后投注转为待退款
+    execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    assert_eq!(update_denom(deps.as_mut(), 9600).unwrap_err(), ContractError::OutstandingBalances);
+
+    // 退款完成、台账清空后可以切换
+    execute(deps.as_mut(), mock_env_with_height(9600), mock_info(USER1, &[]), ExecuteMsg::Refund {
+        session_id: "session_0".to_string(),
+    }).unwrap();
+    update_denom(deps.as_mut(), 9600).unwrap();
+    assert_eq!(CONFIG.load(&deps.storage).unwrap().bet_asset.to_string(), "unew");
+}
+
+#[test]
+fn test_zero_reveals_cancels_session_and_refunds() {
+    // 测试无人揭秘时会话取消，投注者可以退款
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    bet_without_reveal(deps.as_mut(), USER1);
+    bet_without_reveal(deps.as_mut(), USER2);
+
+    // 会话取消前不能退款
+    let refund_msg = ExecuteMsg::Refund { session_id: "session_0".to_string() };
+    let res = execute(deps.as_mut(), mock_env_with_height(9000), mock_info(USER1, &[]), refund_msg.clone());
+    assert!(matches!(res, Err(ContractError::SessionNotCancelled)));
+
+    let res = execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    assert!(res.attributes.iter().any(|attr| attr.key == "status" && attr.value == "cancelled"));
+
+    let session = CURRENT_SESSION.load(&deps.storage).unwrap();
+    assert!(session.cancelled);
+    assert!(!session.settled);
+    assert!(!LOTTERY_HISTORY.has(&deps.storage, "session_0".to_string()));
+
+    // 取消的会话不收取服务费
+    let stats = STATS.load(&deps.storage).unwrap();
+    assert_eq!(stats.total_service_fee, Uint128::zero());
+
+    let res = execute(deps.as_mut(), mock_env_with_height(9600), mock_info(USER1, &[]), refund_msg.clone()).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: USER1.to_string(),
+        amount: vec![Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(1000u128),
+        }],
+    }));
+
+    // 重复退款和非投注者退款失败
+    let res = execute(deps.as_mut(), mock_env_with_height(9600), mock_info(USER1, &[]), refund_msg.clone());
+    assert!(matches!(res, Err(ContractError::AlreadyRefunded)));
+    let res = execute(deps.as_mut(), mock_env_with_height(9600), mock_info(USER3, &[]), refund_msg);
+    assert!(matches!(res, Err(ContractError::ParticipantNotFound)));
+
+    // 已取消的会话不能再结算
+    let res = execute(deps.as_mut(), mock_env_with_height(9700), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None });
+    assert!(matches!(res, Err(ContractError::SessionCancelled)));
+}
+
+#[test]
+fn test_missed_settlement_window_refund() {
+    // 测试结算阶段结束仍未结算时，投注者可以退款
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    bet_without_reveal(deps.as_mut(), USER1);
+    execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER1, &[]), ExecuteMsg::RevealRandom {
+        lucky_numbers: vec![456; 1000],
+        random_seed: "silent_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
+    }).unwrap();
+
+    // 下一周期中，上一期会话在退款时取消
+    let refund_msg = ExecuteMsg::Refund { session_id: "session_0".to_string() };
+    execute(deps.as_mut(), mock_env_with_height(10500), mock_info(USER1, &[]), refund_msg).unwrap();
+    assert!(CURRENT_SESSION.load(&deps.storage).unwrap().cancelled);
+
+    // 下一期投注时归档已取消的会话
+    let res = execute(deps.as_mut(), mock_env_with_height(11000), mock_info(USER2, &[Coin {
+        denom: DENOM.to_string(),
+        amount: Uint128::from(1000u128),
+    }]), ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &vec![456; 1000], "next_seed"),
+        referrer: None,
+    });
+    assert!(res.is_ok());
+    let archived = SESSION_DETAILS.load(&deps.storage, "session_0".to_string()).unwrap();
+    assert!(archived.cancelled);
+    assert_eq!(CURRENT_SESSION.load(&deps.storage).unwrap().session_id, "session_1");
+}
+
+#[test]
+fn test_referral_rewards_voided_by_cancelled_session() {
+    // 测试会话取消后推荐奖励作废，统计在领取前后都不计入作废部分
+    let mut deps = mock_dependencies();
+    let referrer = deps.api.addr_make("referrer");
+    let mut msg = mock_instantiate_msg();
+    msg.referral_fee_share = Some(Decimal::from_str("0.5").unwrap());
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+
+    let referrer_stats = |deps: Deps| -> ReferrerStatsResponse {
+        let res = query::query(deps, mock_env_with_height(9600), QueryMsg::GetReferrerStats {
+            address: referrer.to_string(),
+        }).unwrap();
+        cosmwasm_std::from_json(&res).unwrap()
+    };
+
+    execute(deps.as_mut(), mock_env_with_height(1000), mock_info(USER1, &[Coin::new(1000u128, DENOM)]), ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &vec![456; 1000], "silent_seed"),
+        referrer: Some(referrer.to_string()),
+    }).unwrap();
+    assert_eq!(referrer_stats(deps.as_ref()).stats.earned_fees, Uint128::from(50u128));
+
+    // 无人揭秘，会话取消
+    execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    let res = referrer_stats(deps.as_ref());
+    assert_eq!(res.stats.earned_fees, Uint128::zero());
+    assert_eq!(res.stats.referred_bets, 1);
+    assert_eq!(res.pending, Uint128::zero());
+    assert_eq!(res.claimable, Uint128::zero());
+
+    // 领取时清除作废的推荐奖励，不发放资金
+    let res = execute(deps.as_mut(), mock_env_with_height(9600), mock_info(referrer.as_str(), &[]), ExecuteMsg::ClaimReferralRewards {}).unwrap();
+    assert!(res.messages.is_empty());
+    assert!(res.attributes.iter().any(|attr| attr.key == "voided" && attr.value == "50"));
+    assert_eq!(referrer_stats(deps.as_ref()).stats.earned_fees, Uint128::zero());
+    assert_eq!(query_treasury(deps.as_ref()).referral_rewards, Uint128::zero());
+}
+
+#[test]
+fn test_cancel_during_settlement_voids_partial_entries() {
+    // 测试结算中途错过结算窗口取消会话后，已写入的中奖记录和待领取余额作废并在退款或一键领取时删除
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    for (i, user) in [USER1, USER2, USER3].iter().enumerate() {
+        execute(deps.as_mut(), mock_env_with_height(1000), mock_info(user, &[Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(1000u128),
+        }]), ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, &format!("seed_{}", i)),
+            referrer: None,
+        }).unwrap();
+        execute(deps.as_mut(), mock_env_with_height(7000), mock_info(user, &[]), ExecuteMsg::RevealRandom {
+            lucky_numbers: all_numbers.clone(),
+            random_seed: format!("seed_{}", i),
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        }).unwrap();
+    }
+
+    // 奖金只记入了前两名中奖者，结算未完成
+    let (user1, user2) = (Addr::unchecked(USER1), Addr::unchecked(USER2));
+    while !CLAIMABLE.has(&deps.storage, (&user2, "session_0")) {
+        execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: Some(1) }).unwrap();
+    }
+    assert!(!LOTTERY_HISTORY.has(&deps.storage, "session_0".to_string()));
+    assert_eq!(session_winners(deps.as_ref(), "session_0").len(), 3);
+
+    // 下一周期退款时取消会话，中奖号码作废，退款者的中途记录被删除
+    execute(deps.as_mut(), mock_env_with_height(10500), mock_info(USER1, &[]), ExecuteMsg::Refund {
+        session_id: "session_0".to_string(),
+    }).unwrap();
+    let session = CURRENT_SESSION.load(&deps.storage).unwrap();
+    assert!(session.cancelled);
+    assert_eq!(session.winning_number, None);
+    assert!(!CLAIMABLE.has(&deps.storage, (&user1, "session_0")));
+    assert!(WINNINGS.prefix(("session_0", &user1)).keys(&deps.storage, None, None, Order::Ascending).next().is_none());
+    assert_eq!(session_winners(deps.as_ref(), "session_0").len(), 2);
+
+    // 作废的待领取余额不能领取，一键领取时删除
+    let res = execute(deps.as_mut(), mock_env_with_height(10500), mock_info(USER2, &[]), ExecuteMsg::ClaimReward {
+        session_id: Some("session_0".to_string()),
+    });
+    assert!(matches!(res, Err(ContractError::SessionCancelled)));
+    let res = execute(deps.as_mut(), mock_env_with_height(10500), mock_info(USER2, &[]), ExecuteMsg::ClaimReward {
+        session_id: None,
+    }).unwrap();
+    assert!(res.messages.is_empty());
+    assert!(res.attributes.iter().any(|attr| attr.key == "cancelled_sessions" && attr.value == "session_0"));
+    assert!(!CLAIMABLE.has(&deps.storage, (&user2, "session_0")));
+
+    // 投注全额退还，待领取奖金台账不受影响
+    let treasury = query_treasury(deps.as_ref());
+    assert_eq!(treasury.unclaimed_prizes, Uint128::zero());
+    assert_eq!(treasury.pending_refunds, Uint128::from(2000u128));
+}
+
+#[test]
+fn test_session_rollover_to_next_cycle() {
+    // 测试结算后进入下一周期时自动开启新会话
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+    settle_with_guaranteed_winners(deps.as_mut(), &[USER1, USER2]);
+
+    let settled_session = CURRENT_SESSION.load(&deps.storage).unwrap();
+    assert_eq!(settled_session.cycle, 0);
+    assert_eq!(settled_session.session_id, "session_0");
+    assert!(settled_session.settled);
+
+    // 下一周期的承诺阶段，USER1 可以再次投注
+    let env = mock_env_with_height(11000);
+    let bet_info = mock_info(USER1, &[Coin {
+        denom: DENOM.to_string(),
+        amount: Uint128::from(1000u128),
+    }]);
+    let bet_msg = ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_new_seed"),
+        referrer: None,
+    };
+    execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
+
+    // 上一期会话已归档，承诺按会话隔离
+    let archived = SESSION_DETAILS.load(&deps.storage, "session_0".to_string()).unwrap();
+    assert_eq!(archived, settled_session);
+    assert!(COMMITMENTS.has(&deps.storage, ("session_0", &Addr::unchecked(USER2))));
+    assert!(!COMMITMENTS.has(&deps.storage, ("session_1", &Addr::unchecked(USER2))));
+    assert!(COMMITMENTS.has(&deps.storage, ("session_1", &Addr::unchecked(USER1))));
+
+    let session = CURRENT_SESSION.load(&deps.storage).unwrap();
+    assert_eq!(session.cycle, 1);
+    assert_eq!(session.session_id, "session_1");
+    assert!(!session.settled);
+    assert_eq!(session.participant_count, 1);
+    assert_eq!(session.total_pool, Uint128::from(1000u128));
+
+    // 新会话可以正常揭秘
+    let env = mock_env_with_height(17000);
+    let reveal_msg = ExecuteMsg::RevealRandom {
+        lucky_numbers: vec![123; 1000],
+        random_seed: "user1_new_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
+    };
+    execute(deps.as_mut(), env, mock_info(USER1, &[]), reveal_msg).unwrap();
+}
+
 #[test]
 fn test_query_operations_during_lottery() {
     // 测试彩票过程中的查询操作
@@ -394,6 +1297,7 @@ fn test_query_operations_during_lottery() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -405,7 +1309,7 @@ fn test_query_operations_during_lottery() {
     
     assert!(res.session.is_some());
     let session = res.session.unwrap();
-    assert_eq!(session.participants.len(), 1);
+    assert_eq!(session.participant_count, 1);
     assert_eq!(session.total_pool, Uint128::from(1000u128));
 
     // 查询参与者信息
@@ -439,6 +1343,7 @@ fn test_error_recovery_scenarios() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     // 第一次投注应该成功
@@ -457,9 +1362,510 @@ fn test_error_recovery_scenarios() {
 
     let bet_msg2 = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![456; 1000], "user2_seed"),
+        referrer: None,
     };
 
     // 在错误阶段投注应该失败
     let result = execute(deps.as_mut(), env_wrong_phase, bet_info2, bet_msg2);
     assert!(matches!(result, Err(ContractError::InvalidPhase { .. })));
 }
+
+#[test]
+fn test_reveal_with_typed_tickets() {
+    // 测试投注票：按本期单价计价，计入承诺哈希，按类型兑奖
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.ticket_prices = Some(TicketPrices {
+        straight_price: Uint128::from(250u128),
+        box_price: Uint128::from(500u128),
+        combination_price: Uint128::one(),
+    });
+    msg.prize_tiers = Some(vec![
+        TierPrize { tier: PrizeTier::Straight, pool_share: Decimal::percent(60), multiplier: None },
+        TierPrize { tier: PrizeTier::GroupThree, pool_share: Decimal::percent(20), multiplier: None },
+        TierPrize { tier: PrizeTier::GroupSix, pool_share: Decimal::percent(20), multiplier: None },
+    ]);
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+
+    // 组选 500 + 直选 250 + 复式 250 × 1 = 1000
+    let tickets = vec![
+        Ticket { bet_type: BetType::Box, numbers: vec![123] },
+        Ticket { bet_type: BetType::Straight, numbers: vec![456] },
+        Ticket { bet_type: BetType::Combination, numbers: (1..=250).collect() },
+    ];
+    let combination = (1..=250).map(|n: u16| n.to_string()).collect::<Vec<_>>().join("-");
+    let commitment_data = format!("1000||box:123,straight:456,combination:{}|ticket_seed", combination);
+    let commitment_hash = {
+        use sha2::{Sha256, Digest};
+        hex::encode(Sha256::digest(commitment_data.as_bytes()))
+    };
+    assert_eq!(
+        LotteryLogic::generate_ticket_commitment_hash(1000, &[], &tickets, "ticket_seed").unwrap(),
+        commitment_hash
+    );
+
+    execute(deps.as_mut(), mock_env_with_height(1000), mock_info(USER1, &[Coin {
+        denom: DENOM.to_string(),
+        amount: Uint128::from(1000u128),
+    }]), ExecuteMsg::PlaceBet { commitment_hash, referrer: None }).unwrap();
+
+    // 投注票总价与投注金额不一致
+    let res = execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER1, &[]), ExecuteMsg::RevealRandom {
+        lucky_numbers: vec![],
+        random_seed: "ticket_seed".to_string(),
+        tickets: tickets[..2].to_vec(),
+        lucky_number_counts: vec![],
+    });
+    assert!(matches!(res, Err(ContractError::InvalidLuckyNumbers { .. })));
+
+    // 投注类型不同时承诺哈希不一致
+    let mut wrong_type = tickets.clone();
+    wrong_type[1] = Ticket { bet_type: BetType::Box, numbers: vec![456] };
+    wrong_type[0] = Ticket { bet_type: BetType::Straight, numbers: vec![123] };
+    let res = execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER1, &[]), ExecuteMsg::RevealRandom {
+        lucky_numbers: vec![],
+        random_seed: "ticket_seed".to_string(),
+        tickets: wrong_type,
+        lucky_number_counts: vec![],
+    });
+    assert!(matches!(res, Err(ContractError::CommitmentHashMismatch)));
+
+    execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER1, &[]), ExecuteMsg::RevealRandom {
+        lucky_numbers: vec![],
+        random_seed: "ticket_seed".to_string(),
+        tickets: tickets.clone(),
+        lucky_number_counts: vec![],
+    }).unwrap();
+    let participant = participants().load(&deps.storage, ("session_0", &Addr::unchecked(USER1))).unwrap();
+    assert_eq!(participant.tickets, tickets);
+
+    execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    let enabled = [PrizeTier::Straight, PrizeTier::GroupThree, PrizeTier::GroupSix];
+    // 中奖投注额按投注类型的单价计
+    let expected: Vec<(u8, Uint128)> = tickets.iter()
+        .flat_map(|ticket| LotteryLogic::ticket_tiers(ticket, result.winning_number, &enabled)
+            .into_iter()
+            .map(|tier| (tier.level(), result.ticket_prices.unit_price(ticket.bet_type))))
+        .collect();
+    let winners = session_winners(deps.as_ref(), "session_0");
+    assert_eq!(winners.iter().map(|w| (w.level, w.stake)).collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn test_reveal_with_lucky_number_counts() {
+    // 测试大额投注者以直方图揭秘：10万个投注码只需100个条目
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    let counts: Vec<(u16, u32)> = (0..100).map(|number| (number, 1000)).collect();
+    let commitment_data = format!("100000|{}|whale_seed", LotteryLogic::encode_number_counts(&counts));
+    let commitment_hash = {
+        use sha2::{Sha256, Digest};
+        hex::encode(Sha256::digest(commitment_data.as_bytes()))
+    };
+    assert!(commitment_data.starts_with("100000|0:1000,1:1000,"));
+    assert_eq!(LotteryLogic::generate_histogram_commitment_hash(100000, &counts, &[], "whale_seed"), commitment_hash);
+
+    execute(deps.as_mut(), mock_env_with_height(1000), mock_info(USER1, &[Coin {
+        denom: DENOM.to_string(),
+        amount: Uint128::from(100000u128),
+    }]), ExecuteMsg::PlaceBet { commitment_hash, referrer: None }).unwrap();
+
+    let reveal = |lucky_numbers: Vec<u16>, lucky_number_counts: Vec<(u16, u32)>| ExecuteMsg::RevealRandom {
+        lucky_numbers,
+        random_seed: "whale_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts,
+    };
+
+    // 投注码列表和直方图不能同时提供
+    let res = execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER1, &[]), reveal(vec![1], counts.clone()));
+    assert!(matches!(res, Err(ContractError::InvalidLuckyNumbers { .. })));
+
+    // 直方图必须按号码升序且注数不为0
+    let mut unsorted = counts.clone();
+    unsorted.swap(0, 1);
+    let res = execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER1, &[]), reveal(vec![], unsorted));
+    assert!(matches!(res, Err(ContractError::InvalidLuckyNumbers { .. })));
+    let mut zero_count = counts.clone();
+    zero_count.push((500, 0));
+    let res = execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER1, &[]), reveal(vec![], zero_count));
+    assert!(matches!(res, Err(ContractError::InvalidLuckyNumbers { .. })));
+
+    // 注数总和与投注金额不一致
+    let mut short = counts.clone();
+    short[0].1 = 999;
+    let res = execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER1, &[]), reveal(vec![], short));
+    assert!(matches!(res, Err(ContractError::InvalidLuckyNumbers { .. })));
+
+    execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER1, &[]), reveal(vec![], counts.clone())).unwrap();
+    let participant = participants().load(&deps.storage, ("session_0", &Addr::unchecked(USER1))).unwrap();
+    assert_eq!(participant.lucky_numbers, counts);
+
+    // 同一奖级的中奖投注码合并为一条记录，记录中奖注数
+    execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    let expected_winners = if result.winning_number < 100 { 1000 } else { 0 };
+    let winners = session_winners(deps.as_ref(), "session_0");
+    assert!(winners.len() <= 1);
+    assert_eq!(winners.iter().map(|w| w.count).sum::<u32>(), expected_winners);
+    assert_eq!(result.tiers[0].count, expected_winners as u64);
+}
+
+#[test]
+fn test_participants_indexed_by_number() {
+    // 测试参与者按 (会话, 地址) 存储并按号码建立索引，会话只保存汇总数据
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    let bets = [(USER1, all_numbers), (USER2, vec![123; 1000])];
+    for (user, numbers) in bets.iter() {
+        execute(deps.as_mut(), mock_env_with_height(1000), mock_info(user, &[Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(1000u128),
+        }]), ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, numbers, &format!("{}_seed", user)),
+            referrer: None,
+        }).unwrap();
+    }
+    bet_without_reveal(deps.as_mut(), USER3);
+
+    for (user, numbers) in bets {
+        execute(deps.as_mut(), mock_env_with_height(7000), mock_info(user, &[]), ExecuteMsg::RevealRandom {
+            lucky_numbers: numbers,
+            random_seed: format!("{}_seed", user),
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        }).unwrap();
+    }
+
+    let session = CURRENT_SESSION.load(&deps.storage).unwrap();
+    assert_eq!(session.participant_count, 3);
+    assert_eq!(session.revealed_count, 2);
+    assert_eq!(load_session_participants(&deps.storage, "session_0").unwrap().len(), 3);
+
+    // 未揭秘的参与者没有号码索引
+    let index = participants().idx.number;
+    let mut expected = vec![(Addr::unchecked(USER1), 1), (Addr::unchecked(USER2), 1000)];
+    expected.sort();
+    assert_eq!(index.participants(&deps.storage, "session_0", 123).unwrap(), expected);
+    assert_eq!(index.participants(&deps.storage, "session_0", 456).unwrap(), vec![(Addr::unchecked(USER1), 1)]);
+    assert!(index.participants(&deps.storage, "session_1", 123).unwrap().is_empty());
+
+    // 结算只读取可能中奖号码的参与者
+    execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    let user2_wins = if result.winning_number == 123 { 1000 } else { 0 };
+    let winners = session_winners(deps.as_ref(), "session_0");
+    let wins = |user: &str| winners.iter()
+        .filter(|w| w.address == Addr::unchecked(user))
+        .map(|w| w.count)
+        .sum::<u32>();
+    assert_eq!(wins(USER1), 1);
+    assert_eq!(wins(USER2), user2_wins);
+    assert_eq!(result.non_reveal.non_revealed_count, 1);
+}
+
+/// 5名投注全部号码的参与者和1名未揭秘者
+fn setup_session_for_paginated_settlement(mut deps: DepsMut) {
+    instantiate(deps.branch(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    let players: Vec<String> = (0..5).map(|i| format!("cosmwasm1player{}", i)).collect();
+    for player in &players {
+        execute(deps.branch(), mock_env_with_height(1000), mock_info(player, &[Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(1000u128),
+        }]), ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, player),
+            referrer: None,
+        }).unwrap();
+    }
+    bet_without_reveal(deps.branch(), USER3);
+
+    for player in &players {
+        execute(deps.branch(), mock_env_with_height(7000), mock_info(player, &[]), ExecuteMsg::RevealRandom {
+            lucky_numbers: all_numbers.clone(),
+            random_seed: player.clone(),
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        }).unwrap();
+    }
+}
+
+#[test]
+fn test_paginated_settlement() {
+    // 测试分批结算：多次调用后的结果与一次完成结算的结果一致
+    let mut single = mock_dependencies();
+    setup_session_for_paginated_settlement(single.as_mut());
+    execute(single.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    let expected = LOTTERY_HISTORY.load(&single.storage, "session_0".to_string()).unwrap();
+    assert_eq!(expected.winners_count, 5);
+
+    let mut deps = mock_dependencies();
+    setup_session_for_paginated_settlement(deps.as_mut());
+
+    let progress = |deps: Deps| -> SettlementProgressResponse {
+        let res = query::query(deps, mock_env_with_height(9500), QueryMsg::GetSettlementProgress {}).unwrap();
+        cosmwasm_std::from_json(&res).unwrap()
+    };
+    let status = |res: &cosmwasm_std::Response| res.attributes.iter()
+        .find(|attr| attr.key == "status")
+        .map(|attr| attr.value.clone());
+
+    // 第一批只处理2名参与者，会话尚未结算
+    let res = execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: Some(2) }).unwrap();
+    assert_eq!(status(&res), Some("in_progress".to_string()));
+    let state = progress(deps.as_ref());
+    assert_eq!(state.stage, Some(SettlementStage::Drawing));
+    assert_eq!(state.processed_participants, 2);
+    assert_eq!(state.participant_count, 6);
+    assert!(!state.settled);
+    assert!(!CURRENT_SESSION.load(&deps.storage).unwrap().settled);
+    assert!(!LOTTERY_HISTORY.has(&deps.storage, "session_0".to_string()));
+
+    // 后续批次可以在之后的区块继续，中奖号码仍由首个结算交易的区块决定
+    let mut calls = 1;
+    let mut stages = vec![];
+    while !progress(deps.as_ref()).settled {
+        execute(deps.as_mut(), mock_env_with_height(9500 + calls), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: Some(2) }).unwrap();
+        stages.extend(progress(deps.as_ref()).stage);
+        calls += 1;
+        assert!(calls < 20);
+    }
+    assert!(calls > 3);
+    // 中奖记录的奖金分多批计算
+    assert!(stages.iter().filter(|&&stage| stage == SettlementStage::Payouts).count() > 1);
+
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    assert_eq!(result.winning_number, expected.winning_number);
+    assert_eq!(result.tiers, expected.tiers);
+    assert_eq!(result.winners_count, expected.winners_count);
+    assert_eq!(result.total_pending, expected.total_pending);
+    assert_eq!(result.non_reveal, expected.non_reveal);
+    assert_eq!(result.jackpot_out, expected.jackpot_out);
+    assert_eq!(session_winners(deps.as_ref(), "session_0"), session_winners(single.as_ref(), "session_0"));
+    for i in 0..5 {
+        let player = Addr::unchecked(format!("cosmwasm1player{}", i));
+        let claimable = |storage: &dyn cosmwasm_std::Storage| CLAIMABLE.load(storage, (&player, "session_0")).unwrap();
+        assert_eq!(claimable(&deps.storage), claimable(&single.storage));
+    }
+
+    let state = progress(deps.as_ref());
+    assert_eq!(state.stage, None);
+    assert_eq!(state.winning_number, Some(expected.winning_number));
+    assert_eq!(state.winners_count, 5);
+
+    let res = execute(deps.as_mut(), mock_env_with_height(9600), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: Some(2) });
+    assert!(matches!(res, Err(ContractError::LotteryAlreadySettled)));
+}
+
+#[test]
+fn test_number_popularity() {
+    // 测试揭秘时按号码汇总投注数，结算的中奖注数与中奖号码的投注数一致
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    let counts = vec![(123, 500), (456, 500)];
+    execute(deps.as_mut(), mock_env_with_height(1000), mock_info(USER1, &[Coin {
+        denom: DENOM.to_string(),
+        amount: Uint128::from(1000u128),
+    }]), ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
+    }).unwrap();
+    execute(deps.as_mut(), mock_env_with_height(1000), mock_info(USER2, &[Coin {
+        denom: DENOM.to_string(),
+        amount: Uint128::from(1000u128),
+    }]), ExecuteMsg::PlaceBet {
+        commitment_hash: LotteryLogic::generate_histogram_commitment_hash(1000, &counts, &[], "user2_seed"),
+        referrer: None,
+    }).unwrap();
+    bet_without_reveal(deps.as_mut(), USER3);
+
+    let popularity = |deps: Deps, session_id: &str| -> NumberPopularityResponse {
+        let res = query::query(deps, mock_env_with_height(7000), QueryMsg::GetNumberPopularity {
+            session_id: session_id.to_string(),
+        }).unwrap();
+        cosmwasm_std::from_json(&res).unwrap()
+    };
+
+    // 承诺阶段号码尚未公开
+    assert!(popularity(deps.as_ref(), "session_0").numbers.is_empty());
+
+    execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER1, &[]), ExecuteMsg::RevealRandom {
+        lucky_numbers: vec![123; 1000],
+        random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
+    }).unwrap();
+    execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER2, &[]), ExecuteMsg::RevealRandom {
+        lucky_numbers: vec![],
+        random_seed: "user2_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: counts,
+    }).unwrap();
+
+    let res = popularity(deps.as_ref(), "session_0");
+    assert_eq!(res.numbers, vec![
+        NumberPopularity { number: 123, bets: 1500 },
+        NumberPopularity { number: 456, bets: 500 },
+    ]);
+    assert_eq!(res.total_bets, 2000);
+    assert!(popularity(deps.as_ref(), "session_1").numbers.is_empty());
+
+    execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    let expected = res.numbers.iter()
+        .find(|popularity| popularity.number == result.winning_number)
+        .map(|popularity| popularity.bets)
+        .unwrap_or(0);
+    assert_eq!(result.tiers[0].count, expected as u64);
+    assert_eq!(session_winners(deps.as_ref(), "session_0").iter().map(|w| w.count).sum::<u32>(), expected);
+}
+
+#[test]
+fn test_paginated_history_and_participants() {
+    // 测试历史按周期编号分页（session_10 排在 session_9 之后），参与者按地址分页
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    for cycle in 0..12u64 {
+        let seed = format!("cycle{}_seed", cycle);
+        execute(deps.as_mut(), mock_env_with_height(cycle * 10000 + 1000), mock_info(USER1, &[Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(1000u128),
+        }]), ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, &seed),
+            referrer: None,
+        }).unwrap();
+        execute(deps.as_mut(), mock_env_with_height(cycle * 10000 + 7000), mock_info(USER1, &[]), ExecuteMsg::RevealRandom {
+            lucky_numbers: all_numbers.clone(),
+            random_seed: seed,
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        }).unwrap();
+        execute(deps.as_mut(), mock_env_with_height(cycle * 10000 + 9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    }
+
+    let history = |deps: Deps, start_after: Option<u64>, limit: u32, order: Option<Order>| -> (Vec<u64>, u32) {
+        let res = query::query(deps, mock_env_with_height(120000), QueryMsg::GetLotteryHistory {
+            limit: Some(limit),
+            start_after,
+            order,
+        }).unwrap();
+        let res: LotteryHistoryResponse = cosmwasm_std::from_json(&res).unwrap();
+        (res.results.iter().map(|result| result.cycle).collect(), res.total)
+    };
+
+    assert_eq!(history(deps.as_ref(), None, 5, None), (vec![11, 10, 9, 8, 7], 12));
+    assert_eq!(history(deps.as_ref(), Some(7), 5, None), (vec![6, 5, 4, 3, 2], 12));
+    assert_eq!(history(deps.as_ref(), Some(1), 5, None), (vec![0], 12));
+    assert_eq!(history(deps.as_ref(), None, 3, Some(Order::Ascending)), (vec![0, 1, 2], 12));
+    assert_eq!(history(deps.as_ref(), Some(9), 5, Some(Order::Ascending)), (vec![10, 11], 12));
+
+    // 第12期的参与者分页
+    let players: Vec<String> = (0..5).map(|i| format!("cosmwasm1player{}", i)).collect();
+    for player in &players {
+        execute(deps.as_mut(), mock_env_with_height(121000), mock_info(player, &[Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(1000u128),
+        }]), ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, player),
+            referrer: None,
+        }).unwrap();
+    }
+
+    let page = |deps: Deps, start_after: Option<&str>, order: Option<Order>| -> (Vec<String>, u32) {
+        let res = query::query(deps, mock_env_with_height(121000), QueryMsg::GetParticipants {
+            start_after: start_after.map(|address| address.to_string()),
+            limit: Some(2),
+            order,
+        }).unwrap();
+        let res: ParticipantsResponse = cosmwasm_std::from_json(&res).unwrap();
+        (res.participants.iter().map(|p| p.address.to_string()).collect(), res.total)
+    };
+
+    assert_eq!(page(deps.as_ref(), None, None), (players[0..2].to_vec(), 5));
+    assert_eq!(page(deps.as_ref(), Some(&players[1]), None), (players[2..4].to_vec(), 5));
+    assert_eq!(page(deps.as_ref(), Some(&players[3]), None), (players[4..].to_vec(), 5));
+    assert_eq!(page(deps.as_ref(), None, Some(Order::Descending)), (vec![players[4].clone(), players[3].clone()], 5));
+}
+
+#[test]
+fn test_user_history() {
+    // 测试按地址记录每期的投注、揭秘、奖金和领取状态
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    let history = |deps: Deps, height: u64, address: &str, start_after: Option<u64>, limit: Option<u32>| -> Vec<UserSessionRecord> {
+        let res = query::query(deps, mock_env_with_height(height), QueryMsg::GetUserHistory {
+            address: address.to_string(),
+            start_after,
+            limit,
+        }).unwrap();
+        let res: UserHistoryResponse = cosmwasm_std::from_json(&res).unwrap();
+        res.records
+    };
+
+    // 第0期中奖
+    settle_with_guaranteed_winners(deps.as_mut(), &[USER1]);
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    let records = history(deps.as_ref(), 9500, USER1, None, None);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].session_id, "session_0");
+    assert_eq!(records[0].bet_amount, Uint128::from(1000u128));
+    assert_eq!(records[0].lucky_numbers.len(), 1000);
+    assert!(records[0].revealed && records[0].settled && !records[0].cancelled);
+    assert_eq!(records[0].winning_number, Some(result.winning_number));
+    assert_eq!(records[0].prize, result.total_pending);
+    assert_eq!(records[0].claim_status, ClaimStatus::Pending);
+    assert_eq!(records[0].winnings, vec![UserWinning {
+        tier: PrizeTier::Straight,
+        count: 1,
+        reward_amount: result.total_pending,
+    }]);
+
+    // 过期后显示为过期，领取后显示为已领取
+    let expired = history(deps.as_ref(), result.claim_deadline_height + 1, USER1, None, None);
+    assert_eq!(expired[0].claim_status, ClaimStatus::Expired);
+    execute(deps.as_mut(), mock_env_with_height(9600), mock_info(USER1, &[]), ExecuteMsg::ClaimReward {
+        session_id: Some("session_0".to_string()),
+    }).unwrap();
+    assert_eq!(history(deps.as_ref(), 9600, USER1, None, None)[0].claim_status, ClaimStatus::Claimed);
+
+    // 第1期未揭秘，会话取消后可退款
+    execute(deps.as_mut(), mock_env_with_height(11000), mock_info(USER1, &[Coin {
+        denom: DENOM.to_string(),
+        amount: Uint128::from(1000u128),
+    }]), ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &vec![456; 1000], "silent_seed"),
+        referrer: None,
+    }).unwrap();
+    let records = history(deps.as_ref(), 11000, USER1, None, None);
+    assert_eq!(records.iter().map(|r| r.cycle).collect::<Vec<_>>(), vec![1, 0]);
+    assert!(!records[0].revealed);
+    assert!(records[0].lucky_numbers.is_empty());
+    assert_eq!(records[0].claim_status, ClaimStatus::None);
+    assert!(records[0].winnings.is_empty());
+
+    execute(deps.as_mut(), mock_env_with_height(19500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    let records = history(deps.as_ref(), 19500, USER1, None, Some(1));
+    assert_eq!(records.len(), 1);
+    assert!(records[0].cancelled);
+    assert_eq!(records[0].prize, Uint128::from(1000u128));
+    assert_eq!(records[0].claim_status, ClaimStatus::Refundable);
+
+    execute(deps.as_mut(), mock_env_with_height(19500), mock_info(USER1, &[]), ExecuteMsg::Refund {
+        session_id: "session_1".to_string(),
+    }).unwrap();
+    assert_eq!(history(deps.as_ref(), 19500, USER1, None, None)[0].claim_status, ClaimStatus::Refunded);
+
+    // 分页
+    let records = history(deps.as_ref(), 19500, USER1, Some(1), None);
+    assert_eq!(records.iter().map(|r| r.session_id.as_str()).collect::<Vec<_>>(), vec!["session_0"]);
+    assert!(history(deps.as_ref(), 19500, USER2, None, None).is_empty());
+}
@@ -1,16 +1,16 @@
 use cosmwasm_std::{
     testing::{mock_dependencies, mock_env},
-    Addr, Uint128, Decimal, Timestamp, Coin, MessageInfo,
+    Addr, Uint128, Decimal, Timestamp, Coin, MessageInfo, Storage, CosmosMsg, BankMsg,
 };
+use cw_storage_plus::Map;
 use sha2::{Sha256, Digest};
-use hex;
 
 use dd_3d_lottery::{
-    contract::instantiate,
+    contract::{instantiate, migrate},
     execute::execute,
     query,
-    msg::{InstantiateMsg, ExecuteMsg, QueryMsg, PhaseResponse, ParticipantResponse, LotteryResultResponse, ConfigResponse, LotteryHistoryResponse, ParticipantsResponse, StatsResponse},
-    state::{LotteryPhase, CONFIG, CURRENT_SESSION, REENTRANCY_LOCK, STATS},
+    msg::{InstantiateMsg, ExecuteMsg, QueryMsg, PhaseResponse, ParticipantResponse, LotteryResultResponse, ConfigResponse, LotteryHistoryResponse, ParticipantsResponse, StatsResponse, MigrateMsg, PendingAdminResponse, WinnersResponse},
+    state::{LotteryPhase, ScheduleMode, BetAsset, FeeRecipient, CONFIG, CURRENT_SESSION, SESSION_DETAILS, REENTRANCY_LOCK, STATS, JACKPOT, ACCRUED_FEES, PENDING_REFUNDS, UNCLAIMED_PRIZES, LOTTERY_HISTORY, PENDING_ADMIN, participants},
     error::ContractError,
 };
 use std::str::FromStr;
@@ -49,6 +49,27 @@ fn mock_instantiate_msg() -> InstantiateMsg {
         max_bet_amount: Uint128::from(1000000u128), // 1000 * 1000 = 1,000,000
         bet_denom: DENOM.to_string(),
         pause_requested: Some(false),
+        claim_expiry_blocks: None,
+        commitment_blocks: None,
+        reveal_blocks: None,
+        settlement_blocks: None,
+        genesis_height: None,
+        schedule_mode: None,
+        commitment_seconds: None,
+        reveal_seconds: None,
+        settlement_seconds: None,
+        genesis_time: None,
+        non_reveal_policy: None,
+        commitment_deposit: None,
+        randomness_source: None,
+        prize_structure: None,
+        prize_tiers: None,
+        ticket_prices: None,
+        cw20_token: None,
+        extra_funds_policy: None,
+        fee_recipients: None,
+        fee_distribution: None,
+        referral_fee_share: None,
     }
 }
 
@@ -71,7 +92,7 @@ fn test_instantiate() {
 
     // 检查配置是否正确保存
     let config = CONFIG.load(&deps.storage).unwrap();
-    assert_eq!(config.admin, Addr::unchecked(ADMIN));
+    assert_eq!(config.admin, Some(Addr::unchecked(ADMIN)));
     assert_eq!(config.service_fee_rate, Decimal::from_str("0.1").unwrap());
     assert_eq!(config.min_bet_amount, Uint128::from(1000u128));
     assert_eq!(config.max_bet_amount, Uint128::from(1000000u128)); // 1000 * 1000 = 1,000,000
@@ -96,6 +117,189 @@ fn test_phase_detection() {
     assert!(matches!(phase, LotteryPhase::Settlement));
 }
 
+fn query_phase(deps: cosmwasm_std::Deps, height: u64) -> PhaseResponse {
+    let res_binary = query::query(deps, mock_env_with_height(height), QueryMsg::GetCurrentPhase {}).unwrap();
+    cosmwasm_std::from_json(&res_binary).unwrap()
+}
+
+#[test]
+fn test_configurable_phase_schedule() {
+    let mut deps = mock_dependencies();
+    let env = mock_env_with_height(1000);
+    let mut msg = mock_instantiate_msg();
+    msg.commitment_blocks = Some(100);
+    msg.reveal_blocks = Some(50);
+    msg.settlement_blocks = Some(10);
+    msg.genesis_height = Some(1000);
+
+    instantiate(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), msg).unwrap();
+
+    let res = query_phase(deps.as_ref(), 1050);
+    assert_eq!(res.phase, LotteryPhase::Commitment);
+    assert_eq!(res.phase_mod, 50);
+    assert_eq!(res.remaining_blocks, Some(50));
+    // 按实例化以来的平均出块时间（6秒）估算剩余秒数
+    assert_eq!(res.remaining_seconds, Some(300));
+    assert_eq!(res.cycle, 0);
+
+    assert_eq!(query_phase(deps.as_ref(), 1120).phase, LotteryPhase::Reveal);
+
+    let res = query_phase(deps.as_ref(), 1155);
+    assert_eq!(res.phase, LotteryPhase::Settlement);
+    assert_eq!(res.remaining_blocks, Some(5));
+
+    let res = query_phase(deps.as_ref(), 1160);
+    assert_eq!(res.phase, LotteryPhase::Commitment);
+    assert_eq!(res.cycle, 1);
+
+    // 投注只能在配置的承诺阶段内进行
+    let bet_msg = ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
+    };
+    let bet_info = mock_info(USER1, &[Coin {
+        denom: DENOM.to_string(),
+        amount: Uint128::from(1000u128),
+    }]);
+    let res = execute(deps.as_mut(), mock_env_with_height(1100), bet_info.clone(), bet_msg.clone());
+    assert!(matches!(res, Err(ContractError::InvalidPhase { .. })));
+    execute(deps.as_mut(), mock_env_with_height(1050), bet_info, bet_msg).unwrap();
+}
+
+fn query_phase_at_time(deps: cosmwasm_std::Deps, seconds: u64) -> PhaseResponse {
+    let mut env = mock_env();
+    env.block.time = Timestamp::from_seconds(seconds);
+    let res_binary = query::query(deps, env, QueryMsg::GetCurrentPhase {}).unwrap();
+    cosmwasm_std::from_json(&res_binary).unwrap()
+}
+
+#[test]
+fn test_timestamp_phase_schedule() {
+    // 2024-01-01 20:00:00 UTC，每天 20:00 开奖
+    const GENESIS: u64 = 1_704_139_200;
+    const DAY: u64 = 86_400;
+
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.schedule_mode = Some(ScheduleMode::Timestamp);
+    msg.genesis_time = Some(GENESIS);
+
+    let mut env = mock_env();
+    env.block.time = Timestamp::from_seconds(GENESIS);
+    instantiate(deps.as_mut(), env, mock_info(ADMIN, &[]), msg).unwrap();
+
+    // 阶段只取决于区块时间，与区块高度无关
+    let res = query_phase_at_time(deps.as_ref(), GENESIS + 3_600);
+    assert_eq!(res.mode, ScheduleMode::Timestamp);
+    assert_eq!(res.phase, LotteryPhase::Commitment);
+    assert_eq!(res.remaining_seconds, Some(72_000 - 3_600));
+    // 实例化后尚未出块，无法估算平均出块时间
+    assert_eq!(res.remaining_blocks, None);
+
+    // 实例化后出块720个、平均5秒一块，剩余区块数按平均出块时间估算
+    let mut env = mock_env();
+    env.block.height += 720;
+    env.block.time = Timestamp::from_seconds(GENESIS + 3_600);
+    let res: PhaseResponse = cosmwasm_std::from_json(query::query(deps.as_ref(), env, QueryMsg::GetCurrentPhase {}).unwrap()).unwrap();
+    assert_eq!(res.remaining_seconds, Some(68_400));
+    assert_eq!(res.remaining_blocks, Some(13_680));
+
+    assert_eq!(query_phase_at_time(deps.as_ref(), GENESIS + 72_000).phase, LotteryPhase::Reveal);
+
+    let res = query_phase_at_time(deps.as_ref(), GENESIS + DAY - 60);
+    assert_eq!(res.phase, LotteryPhase::Settlement);
+    assert_eq!(res.remaining_seconds, Some(60));
+
+    let res = query_phase_at_time(deps.as_ref(), GENESIS + 3 * DAY);
+    assert_eq!(res.phase, LotteryPhase::Commitment);
+    assert_eq!(res.cycle, 3);
+
+    // 按时间调度时不能以区块数修改阶段长度
+    let update_msg = ExecuteMsg::UpdateConfig {
+        service_fee_rate: None,
+        min_bet_amount: None,
+        max_bet_amount: None,
+        bet_denom: None,
+        pause_requested: None,
+        claim_expiry_blocks: None,
+        commitment_blocks: Some(100),
+        reveal_blocks: None,
+        settlement_blocks: None,
+        commitment_seconds: None,
+        reveal_seconds: None,
+        settlement_seconds: None,
+        non_reveal_policy: None,
+        commitment_deposit: None,
+        randomness_source: None,
+        prize_structure: None,
+        prize_tiers: None,
+        ticket_prices: None,
+        extra_funds_policy: None,
+        fee_recipients: None,
+        fee_distribution: None,
+        referral_fee_share: None,
+    };
+    let mut env = mock_env();
+    env.block.time = Timestamp::from_seconds(GENESIS + 3_600);
+    let res = execute(deps.as_mut(), env, mock_info(ADMIN, &[]), update_msg);
+    assert!(matches!(res, Err(ContractError::InvalidConfiguration)));
+}
+
+#[test]
+fn test_phase_schedule_update_applies_to_next_cycle() {
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.commitment_blocks = Some(100);
+    msg.reveal_blocks = Some(50);
+    msg.settlement_blocks = Some(10);
+    msg.genesis_height = Some(1000);
+
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+
+    // 在周期0内延长承诺阶段
+    let update_msg = ExecuteMsg::UpdateConfig {
+        service_fee_rate: None,
+        min_bet_amount: None,
+        max_bet_amount: None,
+        bet_denom: None,
+        pause_requested: None,
+        claim_expiry_blocks: None,
+        commitment_blocks: Some(200),
+        reveal_blocks: None,
+        settlement_blocks: None,
+        commitment_seconds: None,
+        reveal_seconds: None,
+        settlement_seconds: None,
+        non_reveal_policy: None,
+        commitment_deposit: None,
+        randomness_source: None,
+        prize_structure: None,
+        prize_tiers: None,
+        ticket_prices: None,
+        extra_funds_policy: None,
+        fee_recipients: None,
+        fee_distribution: None,
+        referral_fee_share: None,
+    };
+    execute(deps.as_mut(), mock_env_with_height(1050), mock_info(ADMIN, &[]), update_msg).unwrap();
+
+    let config = CONFIG.load(&deps.storage).unwrap();
+    let pending = config.pending_schedule.unwrap();
+    assert_eq!(pending.genesis, 1160);
+    assert_eq!(pending.first_cycle, 1);
+    assert_eq!(pending.commitment_length, 200);
+
+    // 当前周期不受影响
+    assert_eq!(query_phase(deps.as_ref(), 1100).phase, LotteryPhase::Reveal);
+
+    // 下一周期使用新的阶段长度
+    let res = query_phase(deps.as_ref(), 1300);
+    assert_eq!(res.phase, LotteryPhase::Commitment);
+    assert_eq!(res.cycle, 1);
+    assert_eq!(query_phase(deps.as_ref(), 1360).phase, LotteryPhase::Reveal);
+    assert_eq!(query_phase(deps.as_ref(), 1420).cycle, 2);
+}
+
 #[test]
 fn test_place_bet_commitment_phase() {
     let mut deps = mock_dependencies();
@@ -114,6 +318,7 @@ fn test_place_bet_commitment_phase() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(5000, &[123, 456, 789], "user1_seed"),
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env, bet_info, bet_msg).unwrap();
@@ -121,7 +326,7 @@ fn test_place_bet_commitment_phase() {
 
     // 检查当前会话
     let session = CURRENT_SESSION.load(&deps.storage).unwrap();
-    assert_eq!(session.participants.len(), 1);
+    assert_eq!(session.participant_count, 1);
     assert_eq!(session.total_pool, Uint128::from(5000u128));
     assert_eq!(session.service_fee, Uint128::from(500u128));
 }
@@ -144,6 +349,7 @@ fn test_place_bet_wrong_phase() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(5000, &[123, 456, 789], "user1_seed"),
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env, bet_info, bet_msg);
@@ -167,6 +373,7 @@ fn test_place_bet_with_commitment_hash() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(5000, &[123, 456, 789], "user1_seed"),
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env.clone(), bet_info, bet_msg);
@@ -180,6 +387,7 @@ fn test_place_bet_with_commitment_hash() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(3000, &[111, 222, 333], "user2_seed"),
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env, bet_info, bet_msg);
@@ -203,6 +411,7 @@ fn test_invalid_bet_amount() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(500, &[123], "user1_seed"),
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env.clone(), bet_info, bet_msg);
@@ -216,6 +425,7 @@ fn test_invalid_bet_amount() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(2000000, &[123], "user1_seed"),
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env, bet_info, bet_msg);
@@ -239,6 +449,7 @@ fn test_reveal_random() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"), // 投注123号码1000次
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -251,6 +462,8 @@ fn test_reveal_random() {
     let reveal_msg = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123; 1000],
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let res = execute(deps.as_mut(), env, reveal_info, reveal_msg);
@@ -274,6 +487,7 @@ fn test_reveal_random_count_mismatch() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -286,6 +500,8 @@ fn test_reveal_random_count_mismatch() {
     let reveal_msg = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123; 1000], // 投注123号码1000次，投注倍数之和=1000，与K=1000匹配
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let res = execute(deps.as_mut(), env, reveal_info, reveal_msg);
@@ -312,6 +528,7 @@ fn test_lucky_number_count_exceeds_limit() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1001, &vec![123; 1001], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -324,6 +541,8 @@ fn test_lucky_number_count_exceeds_limit() {
     let reveal_msg = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123; 1001], // 123号码投注1001次，超过1000次限制
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let res = execute(deps.as_mut(), env, reveal_info, reveal_msg);
@@ -347,6 +566,7 @@ fn test_lucky_number_count_at_limit() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env.clone(), bet_info, bet_msg);
@@ -360,6 +580,8 @@ fn test_lucky_number_count_at_limit() {
     let reveal_msg = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123; 1000], // 123号码投注1000次，达到限制
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let res = execute(deps.as_mut(), env, reveal_info, reveal_msg);
@@ -402,6 +624,7 @@ fn test_new_betting_mechanism() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"), // 1000个代币=1000次投注机会，全部投注123号码
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env.clone(), bet_info, bet_msg);
@@ -415,15 +638,16 @@ fn test_new_betting_mechanism() {
     let reveal_msg = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123; 1000], // 123号码投注1000次
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let res = execute(deps.as_mut(), env, reveal_info, reveal_msg);
     assert!(res.is_ok());
 
     // 检查参与者信息
-    let session = CURRENT_SESSION.load(&deps.storage).unwrap();
-    let participant = &session.participants[0];
-    assert_eq!(participant.lucky_numbers, vec![123; 1000]);
+    let participant = participants().load(&deps.storage, ("session_0", &Addr::unchecked(USER1))).unwrap();
+    assert_eq!(participant.lucky_numbers, vec![(123, 1000)]);
     assert_eq!(participant.bet_amount, Uint128::from(1000u128));
     assert!(participant.revealed);
 }
@@ -445,6 +669,7 @@ fn test_betting_amount_must_equal_multiplier() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &[123], "user1_seed"), // 投注金额K=1000 != 投注倍数之和=1
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env.clone(), bet_info, bet_msg);
@@ -458,6 +683,8 @@ fn test_betting_amount_must_equal_multiplier() {
     let reveal_msg = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123], // 仅投注123号码1次，投注倍数之和=1，与K=1000不匹配
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let res = execute(deps.as_mut(), env, reveal_info, reveal_msg);
@@ -481,6 +708,7 @@ fn test_query_participant_info() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &[123], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -496,7 +724,7 @@ fn test_query_participant_info() {
     let participant = res.participant.unwrap();
     assert_eq!(participant.address, Addr::unchecked(USER1));
     assert_eq!(participant.bet_amount, Uint128::from(1000u128));
-    assert_eq!(participant.lucky_numbers, Vec::<u16>::new()); // 在承诺阶段还未设置
+    assert!(participant.lucky_numbers.is_empty()); // 在承诺阶段还未设置
     assert!(!participant.revealed);
 }
 
@@ -552,11 +780,11 @@ fn test_query_config() {
     let res_binary = query::query(deps.as_ref(), env, query_msg).unwrap();
     let res: ConfigResponse = cosmwasm_std::from_json(&res_binary).unwrap();
     
-    assert_eq!(res.config.admin, Addr::unchecked(ADMIN));
+    assert_eq!(res.config.admin, Some(Addr::unchecked(ADMIN)));
     assert_eq!(res.config.service_fee_rate, Decimal::from_str("0.1").unwrap());
     assert_eq!(res.config.min_bet_amount, Uint128::from(1000u128));
     assert_eq!(res.config.max_bet_amount, Uint128::from(1000000u128));
-    assert_eq!(res.config.bet_denom, DENOM.to_string());
+    assert_eq!(res.config.bet_asset, BetAsset::Native { denom: DENOM.to_string() });
     assert!(!res.config.paused);
 }
 
@@ -573,6 +801,7 @@ fn test_query_lottery_history() {
     let query_msg = QueryMsg::GetLotteryHistory {
         limit: Some(10),
         start_after: None,
+        order: None,
     };
     let res_binary = query::query(deps.as_ref(), env, query_msg).unwrap();
     let res: LotteryHistoryResponse = cosmwasm_std::from_json(&res_binary).unwrap();
@@ -591,7 +820,7 @@ fn test_query_participants() {
     instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
 
     // 查询参与者列表（空）
-    let query_msg = QueryMsg::GetParticipants {};
+    let query_msg = QueryMsg::GetParticipants { start_after: None, limit: None, order: None };
     let res_binary = query::query(deps.as_ref(), env, query_msg).unwrap();
     let res: ParticipantsResponse = cosmwasm_std::from_json(&res_binary).unwrap();
     
@@ -616,12 +845,13 @@ fn test_query_participants_with_data() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &[123], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
 
     // 查询参与者列表
-    let query_msg = QueryMsg::GetParticipants {};
+    let query_msg = QueryMsg::GetParticipants { start_after: None, limit: None, order: None };
     let res_binary = query::query(deps.as_ref(), env, query_msg).unwrap();
     let res: ParticipantsResponse = cosmwasm_std::from_json(&res_binary).unwrap();
     
@@ -670,6 +900,7 @@ fn test_reentrancy_protection() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &[123], "user1_seed"),
+        referrer: None,
     };
 
     // 这次调用应该失败（重入保护）
@@ -694,6 +925,7 @@ fn test_reentrancy_lock_release() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &[123], "user1_seed"),
+        referrer: None,
     };
 
     // 第一次调用应该成功
@@ -712,6 +944,7 @@ fn test_reentrancy_lock_release() {
 
     let bet_msg2 = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &[456], "user2_seed"),
+        referrer: None,
     };
 
     let env2 = mock_env_with_height(1001);
@@ -776,6 +1009,7 @@ fn test_commitment_consistency_validation() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: valid_hash,
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env.clone(), bet_info, bet_msg);
@@ -789,6 +1023,7 @@ fn test_commitment_consistency_validation() {
 
     let bet_msg2 = ExecuteMsg::PlaceBet {
         commitment_hash: "invalid_hash".to_string(), // 无效格式
+        referrer: None,
     };
 
     let res2 = execute(deps.as_mut(), env.clone(), bet_info2, bet_msg2);
@@ -802,6 +1037,7 @@ fn test_commitment_consistency_validation() {
 
     let bet_msg3 = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(2000000, &[123], "test_seed"),
+        referrer: None,
     };
 
     let res3 = execute(deps.as_mut(), env, bet_info3, bet_msg3);
@@ -825,6 +1061,7 @@ fn test_commitment_full_consistency_validation() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -837,6 +1074,8 @@ fn test_commitment_full_consistency_validation() {
     let reveal_msg = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123; 1000], // 与承诺阶段一致
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let res = execute(deps.as_mut(), env.clone(), reveal_info, reveal_msg);
@@ -847,6 +1086,8 @@ fn test_commitment_full_consistency_validation() {
     let reveal_msg2 = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![456; 1000], // 与承诺阶段不一致
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let res2 = execute(deps.as_mut(), env, reveal_info2, reveal_msg2);
@@ -870,6 +1111,7 @@ fn test_session_phase_compatibility() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -881,6 +1123,8 @@ fn test_session_phase_compatibility() {
     let reveal_msg = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123; 1000],
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let res = execute(deps.as_mut(), env, reveal_info, reveal_msg);
@@ -907,6 +1151,7 @@ fn test_session_creation_only_in_commitment_phase() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &[123], "user1_seed"),
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env, bet_info, bet_msg);
@@ -930,6 +1175,7 @@ fn test_session_already_settled() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -940,12 +1186,14 @@ fn test_session_already_settled() {
     let reveal_msg = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123; 1000],
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
     execute(deps.as_mut(), env.clone(), reveal_info, reveal_msg).unwrap();
 
     // 切换到结算阶段并结算
     let env = mock_env_with_height(9500);
-    let settle_msg = ExecuteMsg::SettleLottery {};
+    let settle_msg = ExecuteMsg::SettleLottery { limit: None };
     execute(deps.as_mut(), env.clone(), MessageInfo { sender: Addr::unchecked(ADMIN), funds: vec![] }, settle_msg).unwrap();
 
     // 尝试在结算阶段投注（应该失败，因为新会话只能在承诺阶段创建）
@@ -956,6 +1204,7 @@ fn test_session_already_settled() {
 
     let bet_msg2 = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![456; 1000], "user2_seed"),
+        referrer: None,
     };
 
     let res = execute(deps.as_mut(), env, bet_info2, bet_msg2);
@@ -978,10 +1227,8 @@ fn test_reward_distribution_fixed_amount_detailed() {
     // 奖金池：10,000个代币，中奖者：5人
     // 计算：5 × 800 = 4,000 < 10,000 ✅
     // 期望：每人获得800个代币
-    
-    // 这里可以添加具体的测试逻辑
-    // 由于需要完整的投注和结算流程，暂时跳过具体实现
-    assert!(true); // 占位测试
+
+    // 具体分配逻辑见 reward_distribution_tests
 }
 
 #[test]
@@ -997,10 +1244,8 @@ fn test_reward_distribution_equal_split_detailed() {
     // 奖金池：1,500个代币，中奖者：3人
     // 计算：3 × 800 = 2,400 > 1,500 ❌
     // 期望：每人获得500个代币（1,500 ÷ 3 = 500）
-    
-    // 这里可以添加具体的测试逻辑
-    // 由于需要完整的投注和结算流程，暂时跳过具体实现
-    assert!(true); // 占位测试
+
+    // 具体分配逻辑见 reward_distribution_tests
 }
 
 #[test]
@@ -1016,8 +1261,276 @@ fn test_reward_distribution_with_remainder() {
     // 奖金池：1,000个代币，中奖者：3人
     // 计算：3 × 800 = 2,400 > 1,000 ❌
     // 期望：每人获得333个代币（1,000 ÷ 3 = 333），余数1个代币保留在资金池
-    
-    // 这里可以添加具体的测试逻辑
-    // 由于需要完整的投注和结算流程，暂时跳过具体实现
-    assert!(true); // 占位测试
+
+    // 具体分配逻辑见 reward_distribution_tests
+}
+
+fn query_pending_admin(deps: cosmwasm_std::Deps) -> Option<Addr> {
+    let res = query::query(deps, mock_env(), QueryMsg::GetPendingAdmin {}).unwrap();
+    cosmwasm_std::from_json::<PendingAdminResponse>(&res).unwrap().pending_admin
+}
+
+#[test]
+fn test_two_step_admin_transfer() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    instantiate(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+    let new_admin = deps.api.addr_make("new_admin");
+
+    // 非管理员不能提名
+    let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::ProposeAdmin {
+        new_admin: new_admin.to_string(),
+    }).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+
+    // 没有提名时不能接受
+    let err = execute(deps.as_mut(), env.clone(), mock_info(new_admin.as_str(), &[]), ExecuteMsg::AcceptAdmin {}).unwrap_err();
+    assert_eq!(err, ContractError::NoAdminProposal);
+
+    execute(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), ExecuteMsg::ProposeAdmin {
+        new_admin: new_admin.to_string(),
+    }).unwrap();
+    assert_eq!(query_pending_admin(deps.as_ref()), Some(new_admin.clone()));
+
+    // 提名期间管理权不变，其他地址不能接受
+    let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::AcceptAdmin {}).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+    let config = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(config.admin, Some(Addr::unchecked(ADMIN)));
+
+    execute(deps.as_mut(), env.clone(), mock_info(new_admin.as_str(), &[]), ExecuteMsg::AcceptAdmin {}).unwrap();
+    let config = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(config.admin, Some(new_admin.clone()));
+    assert_eq!(query_pending_admin(deps.as_ref()), None);
+
+    // 原管理员失去权限，新管理员可以操作
+    let err = execute(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), ExecuteMsg::EmergencyPause { paused: true }).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+    execute(deps.as_mut(), env, mock_info(new_admin.as_str(), &[]), ExecuteMsg::EmergencyPause { paused: true }).unwrap();
+    assert!(CONFIG.load(&deps.storage).unwrap().paused);
+}
+
+#[test]
+fn test_cancel_admin_proposal() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    instantiate(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+    let new_admin = deps.api.addr_make("new_admin");
+
+    let err = execute(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), ExecuteMsg::CancelAdminProposal {}).unwrap_err();
+    assert_eq!(err, ContractError::NoAdminProposal);
+
+    execute(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), ExecuteMsg::ProposeAdmin {
+        new_admin: new_admin.to_string(),
+    }).unwrap();
+    let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::CancelAdminProposal {}).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+    execute(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), ExecuteMsg::CancelAdminProposal {}).unwrap();
+    assert_eq!(query_pending_admin(deps.as_ref()), None);
+
+    // 撤销后的提名不能再接受
+    let err = execute(deps.as_mut(), env, mock_info(new_admin.as_str(), &[]), ExecuteMsg::AcceptAdmin {}).unwrap_err();
+    assert_eq!(err, ContractError::NoAdminProposal);
+}
+
+#[test]
+fn test_renounce_admin() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    instantiate(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+    let new_admin = deps.api.addr_make("new_admin");
+
+    execute(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), ExecuteMsg::ProposeAdmin {
+        new_admin: new_admin.to_string(),
+    }).unwrap();
+
+    let err = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::RenounceAdmin {}).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+
+    // 未配置分成表时不能放弃，否则已计提的服务费无处发放
+    let err = execute(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), ExecuteMsg::RenounceAdmin {}).unwrap_err();
+    assert_eq!(err, ContractError::FeeRecipientsRequired);
+
+    let treasury = deps.api.addr_make("treasury");
+    let mut config = CONFIG.load(&deps.storage).unwrap();
+    config.fee_recipients = vec![FeeRecipient { address: treasury.clone(), weight: 1 }];
+    CONFIG.save(&mut deps.storage, &config).unwrap();
+    execute(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), ExecuteMsg::RenounceAdmin {}).unwrap();
+
+    let config = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(config.admin, None);
+    assert!(!PENDING_ADMIN.exists(&deps.storage));
+
+    // 放弃后管理员操作都不可用
+    let err = execute(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), ExecuteMsg::EmergencyPause { paused: true }).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+    let err = execute(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), ExecuteMsg::ProposeAdmin {
+        new_admin: new_admin.to_string(),
+    }).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+    let err = execute(deps.as_mut(), env.clone(), mock_info(new_admin.as_str(), &[]), ExecuteMsg::AcceptAdmin {}).unwrap_err();
+    assert_eq!(err, ContractError::NoAdminProposal);
+
+    // 已计提的服务费任何人都可以触发提取，按分成表发放
+    ACCRUED_FEES.save(&mut deps.storage, &Uint128::from(300u128)).unwrap();
+    deps.querier.bank.update_balance(env.contract.address.clone(), vec![Coin::new(300u128, DENOM)]);
+    let res = execute(deps.as_mut(), env, mock_info(USER1, &[]), ExecuteMsg::WithdrawServiceFee {
+        amount: Uint128::from(300u128),
+    }).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: treasury.to_string(),
+        amount: vec![Coin::new(300u128, DENOM)],
+    }));
+    assert_eq!(ACCRUED_FEES.load(&deps.storage).unwrap(), Uint128::zero());
+}
+
+#[test]
+fn test_migrate_version_checks() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    instantiate(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    // 同版本迁移
+    let res = migrate(deps.as_mut(), env.clone(), MigrateMsg { limit: None }).unwrap();
+    assert!(res.attributes.iter().any(|a| a.key == "contract_version" && a.value == env!("CARGO_PKG_VERSION")));
+
+    // 拒绝其他合约
+    cw2::set_contract_version(&mut deps.storage, "other-contract", "0.1.0").unwrap();
+    let err = migrate(deps.as_mut(), env.clone(), MigrateMsg { limit: None }).unwrap_err();
+    assert_eq!(err, ContractError::ContractUpgradeNotAllowed);
+
+    // 拒绝降级
+    cw2::set_contract_version(&mut deps.storage, "dd-3d-lottery", "99.0.0").unwrap();
+    let err = migrate(deps.as_mut(), env, MigrateMsg { limit: None }).unwrap_err();
+    assert_eq!(err, ContractError::ContractUpgradeNotAllowed);
+}
+
+#[test]
+fn test_migrate_from_v0_1() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    instantiate(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    // 写入 0.1.x 的存储结构
+    cw2::set_contract_version(&mut deps.storage, "dd-3d-lottery", "0.1.0").unwrap();
+    let legacy_config = format!(
+        r#"{{"admin":"{}","service_fee_rate":"0.05","min_bet_amount":"2000","max_bet_amount":"2000000","bet_denom":"{}","paused":false,"pause_requested":true}}"#,
+        ADMIN, DENOM,
+    );
+    deps.storage.set(b"config", legacy_config.as_bytes());
+    let legacy_participant = |address: &str, amount: u128| format!(
+        r#"{{"address":"{}","bet_amount":"{}","lucky_numbers":[],"random_seed":null,"revealed":false,"commitment_hash":"a","bet_time":"600000000000","reveal_time":null}}"#,
+        address, amount,
+    );
+    let (participant1, participant2) = (legacy_participant(USER1, 1000), legacy_participant(USER2, 2000));
+    let legacy_session = format!(
+        r#"{{"session_id":"global_session_20100","phase":"commitment","total_pool":"3000","service_fee":"150","participants":[{},{}],"created_height":20100,"winning_number":null,"settled":false}}"#,
+        participant1, participant2,
+    );
+    deps.storage.set(b"current_session", legacy_session.as_bytes());
+    deps.storage.remove(b"jackpot");
+    deps.storage.remove(b"accrued_fees");
+
+    // 0.1.x 的开奖记录：每个中奖号码一条中奖记录，奖金未发放
+    let legacy_winner = |address: &str, amount: u128| format!(
+        r#"{{"address":"{}","level":1,"match_count":3,"reward_amount":"{}"}}"#,
+        address, amount,
+    );
+    let winners = [legacy_winner(USER1, 800), legacy_winner(USER2, 500), legacy_winner(USER1, 800)].join(",");
+    let legacy_result = |session_id: &str, height: u64, winners: &str| format!(
+        r#"{{"session_id":"{}","winning_number":123,"total_pool":"4000","service_fee":"200","reward_pool":"3800","winners":[{}],"settled_at":"{}","settled_height":{}}}"#,
+        session_id, winners, height * 1_000_000_000, height,
+    );
+    let results = [
+        ("global_session_50", legacy_result("global_session_50", 9500, &winners)),
+        ("global_session_10050", legacy_result("global_session_10050", 19500, "")),
+    ];
+    let legacy_history = Map::<&str, String>::new("lottery_history");
+    for (session_id, result) in results {
+        deps.storage.set(&legacy_history.key(session_id), result.as_bytes());
+    }
+    let mut stats = STATS.load(&deps.storage).unwrap();
+    stats.total_sessions = 7;
+    STATS.save(&mut deps.storage, &stats).unwrap();
+    deps.querier.bank.update_balance(env.contract.address.clone(), vec![Coin::new(6000u128, DENOM)]);
+
+    let res = migrate(deps.as_mut(), env.clone(), MigrateMsg { limit: Some(1) }).unwrap();
+    assert!(res.attributes.iter().any(|a| a.key == "from_version" && a.value == "0.1.0"));
+    assert!(res.attributes.iter().any(|a| a.key == "legacy_migration" && a.value == "in_progress"));
+    assert!(!res.attributes.iter().any(|a| a.key == "legacy_fees"));
+
+    let config = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(config.admin, Some(Addr::unchecked(ADMIN)));
+    assert_eq!(config.service_fee_rate, Decimal::from_str("0.05").unwrap());
+    assert_eq!(config.min_bet_amount, Uint128::from(2000u128));
+    assert_eq!(config.bet_asset, BetAsset::Native { denom: DENOM.to_string() });
+    assert!(config.pause_requested);
+    assert!(CURRENT_SESSION.may_load(&deps.storage).unwrap().is_none());
+    assert_eq!(JACKPOT.load(&deps.storage).unwrap(), Uint128::zero());
+
+    // 未结算的旧会话取消，投注转为待退款
+    let cancelled = SESSION_DETAILS.load(&deps.storage, "global_session_20100".to_string()).unwrap();
+    assert!(cancelled.cancelled && !cancelled.settled);
+    assert_eq!(cancelled.participant_count, 2);
+    assert!(res.attributes.iter().any(|a| a.key == "legacy_refunds" && a.value == "3000"));
+    assert_eq!(PENDING_REFUNDS.load(&deps.storage).unwrap(), Uint128::from(3000u128));
+
+    // 以相同版本再次迁移，转换剩余的开奖记录，中奖者的奖金记入待领取余额，其余余额计入服务费
+    let res = migrate(deps.as_mut(), env.clone(), MigrateMsg { limit: Some(1) }).unwrap();
+    assert!(res.attributes.iter().any(|a| a.key == "legacy_migration" && a.value == "completed"));
+    assert!(res.attributes.iter().any(|a| a.key == "legacy_prizes" && a.value == "2100"));
+    assert_eq!(UNCLAIMED_PRIZES.load(&deps.storage).unwrap(), Uint128::from(2100u128));
+    assert!(res.attributes.iter().any(|a| a.key == "legacy_fees" && a.value == "900"));
+    assert_eq!(ACCRUED_FEES.load(&deps.storage).unwrap(), Uint128::from(900u128));
+    let res = migrate(deps.as_mut(), env.clone(), MigrateMsg { limit: None }).unwrap();
+    assert!(!res.attributes.iter().any(|a| a.key == "legacy_migration"));
+    assert_eq!(ACCRUED_FEES.load(&deps.storage).unwrap(), Uint128::from(900u128));
+
+    let res = execute(deps.as_mut(), env.clone(), mock_info(USER2, &[]), ExecuteMsg::Refund {
+        session_id: "global_session_20100".to_string(),
+    }).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: USER2.to_string(),
+        amount: vec![Coin::new(2000u128, DENOM)],
+    }));
+    assert_eq!(PENDING_REFUNDS.load(&deps.storage).unwrap(), Uint128::from(1000u128));
+
+    let res = execute(deps.as_mut(), env.clone(), mock_info(USER1, &[]), ExecuteMsg::ClaimReward {
+        session_id: Some("global_session_50".to_string()),
+    }).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: USER1.to_string(),
+        amount: vec![Coin::new(1600u128, DENOM)],
+    }));
+    assert_eq!(UNCLAIMED_PRIZES.load(&deps.storage).unwrap(), Uint128::from(500u128));
+
+    // 彩票历史总数与可分页读取的记录一致
+    let res = query::query(deps.as_ref(), env.clone(), QueryMsg::GetLotteryHistory {
+        limit: None,
+        start_after: None,
+        order: None,
+    }).unwrap();
+    let history: LotteryHistoryResponse = cosmwasm_std::from_json(&res).unwrap();
+    assert_eq!(history.total, 2);
+    assert_eq!(history.results.len(), 2);
+    let result = LOTTERY_HISTORY.load(&deps.storage, "global_session_50".to_string()).unwrap();
+    assert_eq!((result.cycle, result.winning_number, result.winners_count), (0, 123, 2));
+    assert_eq!(result.total_paid, Uint128::from(1600u128));
+    assert_eq!(result.total_pending, Uint128::from(500u128));
+
+    let res = query::query(deps.as_ref(), env.clone(), QueryMsg::GetWinners {
+        session_id: "global_session_50".to_string(),
+        start_after: None,
+        limit: None,
+    }).unwrap();
+    let winners: WinnersResponse = cosmwasm_std::from_json(&res).unwrap();
+    assert_eq!(winners.winners_count, 2);
+    assert_eq!(
+        winners.winners.iter().map(|w| (w.address.as_str(), w.level, w.count, w.reward_amount.u128())).collect::<Vec<_>>(),
+        vec![(USER1, 1, 2, 1600), (USER2, 1, 1, 500)],
+    );
+
+    let version = cw2::get_contract_version(&deps.storage).unwrap();
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
 }
@@ -50,6 +50,27 @@ fn mock_instantiate_msg() -> InstantiateMsg {
         max_bet_amount: Uint128::from(1000000u128),
         bet_denom: DENOM.to_string(),
         pause_requested: Some(false),
+        claim_expiry_blocks: None,
+        commitment_blocks: None,
+        reveal_blocks: None,
+        settlement_blocks: None,
+        genesis_height: None,
+        schedule_mode: None,
+        commitment_seconds: None,
+        reveal_seconds: None,
+        settlement_seconds: None,
+        genesis_time: None,
+        non_reveal_policy: None,
+        commitment_deposit: None,
+        randomness_source: None,
+        prize_structure: None,
+        prize_tiers: None,
+        ticket_prices: None,
+        cw20_token: None,
+        extra_funds_policy: None,
+        fee_recipients: None,
+        fee_distribution: None,
+        referral_fee_share: None,
     }
 }
 
@@ -82,6 +103,7 @@ fn test_large_scale_betting_performance() {
 
         let bet_msg = ExecuteMsg::PlaceBet {
             commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], &format!("user{}_seed", i)),
+            referrer: None,
         };
 
         execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -95,7 +117,7 @@ fn test_large_scale_betting_performance() {
 
     // 验证数据完整性
     let session = CURRENT_SESSION.load(&deps.storage).unwrap();
-    assert_eq!(session.participants.len(), 100);
+    assert_eq!(session.participant_count, 100);
     assert_eq!(session.total_pool, Uint128::from(100000u128)); // 100 * 1000
 }
 
@@ -122,6 +144,7 @@ fn test_large_lucky_numbers_performance() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(max_bet_amount, &max_lucky_numbers, "user_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -134,7 +157,7 @@ fn test_large_lucky_numbers_performance() {
 
     // 验证数据完整性
     let session = CURRENT_SESSION.load(&deps.storage).unwrap();
-    assert_eq!(session.participants.len(), 1);
+    assert_eq!(session.participant_count, 1);
     assert_eq!(session.total_pool, Uint128::from(max_bet_amount));
 }
 
@@ -149,7 +172,8 @@ fn test_reward_calculation_performance() {
         winners.push(Winner {
             address: Addr::unchecked(format!("cosmwasm1winner{:04}", i)),
             level: 1,
-            match_count: 1,
+            count: 1,
+            stake: Uint128::one(),
             reward_amount: Uint128::zero(),
         });
     }
@@ -186,12 +210,14 @@ fn test_random_number_calculation_performance() {
         participants.push(Participant {
             address: Addr::unchecked(format!("cosmwasm1user{:03}", i)),
             bet_amount: Uint128::from(1000u128),
-            lucky_numbers: vec![123; 1000],
+            lucky_numbers: vec![(123, 1000)],
             random_seed: Some(format!("user{}_seed", i)),
             revealed: true,
             commitment_hash: Some(format!("hash{}", i)),
             bet_time: Timestamp::from_seconds(1000),
             reveal_time: Some(Timestamp::from_seconds(7000)),
+            deposit: Uint128::zero(),
+            tickets: vec![],
         });
     }
 
@@ -228,6 +254,7 @@ fn test_query_performance() {
 
         let bet_msg = ExecuteMsg::PlaceBet {
             commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], &format!("user{}_seed", i)),
+            referrer: None,
         };
 
         execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -239,7 +266,7 @@ fn test_query_performance() {
         ("GetCurrentPhase", QueryMsg::GetCurrentPhase {}),
         ("GetConfig", QueryMsg::GetConfig {}),
         ("GetStats", QueryMsg::GetStats {}),
-        ("GetParticipants", QueryMsg::GetParticipants {}),
+        ("GetParticipants", QueryMsg::GetParticipants { start_after: None, limit: None, order: None }),
     ];
 
     for (query_name, query_msg) in queries {
@@ -281,6 +308,7 @@ fn test_memory_usage_large_data() {
 
         let bet_msg = ExecuteMsg::PlaceBet {
             commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], &format!("user{}_seed", i)),
+            referrer: None,
         };
 
         execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -294,7 +322,7 @@ fn test_memory_usage_large_data() {
 
     // 验证数据完整性
     let session = CURRENT_SESSION.load(&deps.storage).unwrap();
-    assert_eq!(session.participants.len(), 1000);
+    assert_eq!(session.participant_count, 1000);
     assert_eq!(session.total_pool, Uint128::from(1000000u128)); // 1000 * 1000
 
     // 验证统计信息
@@ -324,6 +352,7 @@ fn test_gas_consumption_analysis() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user_seed"),
+        referrer: None,
     };
 
     let bet_result = execute(deps.as_mut(), env.clone(), bet_info, bet_msg);
@@ -372,6 +401,7 @@ fn test_concurrent_operations() {
 
         let bet_msg = ExecuteMsg::PlaceBet {
             commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], &format!("user{}_seed", i)),
+            referrer: None,
         };
 
         match execute(deps.as_mut(), env.clone(), bet_info, bet_msg) {
@@ -419,6 +449,7 @@ fn test_storage_efficiency() {
 
         let bet_msg = ExecuteMsg::PlaceBet {
             commitment_hash: generate_commitment_hash(bet_amount, &vec![123; lucky_count], "user_seed"),
+            referrer: None,
         };
 
         execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
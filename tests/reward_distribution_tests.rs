@@ -1,13 +1,16 @@
 use cosmwasm_std::{
-    testing::mock_env,
+    testing::{mock_env, mock_dependencies},
     Addr, Uint128, Decimal, Timestamp, Coin, MessageInfo,
 };
 use std::str::FromStr;
 
 use dd_3d_lottery::{
+    contract::instantiate,
+    execute::execute,
     reward_system::RewardSystem,
-    state::{Participant, Winner},
-    msg::InstantiateMsg,
+    lottery_logic::LotteryLogic,
+    state::{Participant, Winner, PrizeStructure, PrizeTier, TierPrize, BetType, Ticket, TicketPrices, LOTTERY_HISTORY},
+    msg::{InstantiateMsg, ExecuteMsg},
 };
 
 #[allow(dead_code)]
@@ -52,6 +55,27 @@ fn mock_instantiate_msg() -> InstantiateMsg {
         max_bet_amount: Uint128::from(1000000u128),
         bet_denom: DENOM.to_string(),
         pause_requested: Some(false),
+        claim_expiry_blocks: None,
+        commitment_blocks: None,
+        reveal_blocks: None,
+        settlement_blocks: None,
+        genesis_height: None,
+        schedule_mode: None,
+        commitment_seconds: None,
+        reveal_seconds: None,
+        settlement_seconds: None,
+        genesis_time: None,
+        non_reveal_policy: None,
+        commitment_deposit: None,
+        randomness_source: None,
+        prize_structure: None,
+        prize_tiers: None,
+        ticket_prices: None,
+        cw20_token: None,
+        extra_funds_policy: None,
+        fee_recipients: None,
+        fee_distribution: None,
+        referral_fee_share: None,
     }
 }
 
@@ -69,32 +93,38 @@ fn create_test_participants() -> Vec<Participant> {
         Participant {
             address: Addr::unchecked(USER1),
             bet_amount: Uint128::from(1000u128),
-            lucky_numbers: vec![123, 123, 123], // 3次投注123
+            lucky_numbers: vec![(123, 3)], // 3次投注123
             random_seed: Some("user1_seed".to_string()),
             revealed: true,
             commitment_hash: Some("hash1".to_string()),
             bet_time: Timestamp::from_seconds(1000),
             reveal_time: Some(Timestamp::from_seconds(7000)),
+            deposit: Uint128::zero(),
+            tickets: vec![],
         },
         Participant {
             address: Addr::unchecked(USER2),
             bet_amount: Uint128::from(2000u128),
-            lucky_numbers: vec![456, 456, 456, 456], // 4次投注456
+            lucky_numbers: vec![(456, 4)], // 4次投注456
             random_seed: Some("user2_seed".to_string()),
             revealed: true,
             commitment_hash: Some("hash2".to_string()),
             bet_time: Timestamp::from_seconds(1000),
             reveal_time: Some(Timestamp::from_seconds(7000)),
+            deposit: Uint128::zero(),
+            tickets: vec![],
         },
         Participant {
             address: Addr::unchecked(USER3),
             bet_amount: Uint128::from(1500u128),
-            lucky_numbers: vec![789, 789, 789], // 3次投注789
+            lucky_numbers: vec![(789, 3)], // 3次投注789
             random_seed: Some("user3_seed".to_string()),
             revealed: true,
             commitment_hash: Some("hash3".to_string()),
             bet_time: Timestamp::from_seconds(1000),
             reveal_time: Some(Timestamp::from_seconds(7000)),
+            deposit: Uint128::zero(),
+            tickets: vec![],
         },
     ]
 }
@@ -112,20 +142,18 @@ fn test_reward_distribution_fixed_amount_detailed() {
     // 计算中奖者
     let mut winners = RewardSystem::calculate_winners(&participants, winning_number).unwrap();
     
-    // 验证中奖者数量
-    assert_eq!(winners.len(), 3); // USER1投注123号码3次，中奖3次
+    // USER1投注123号码3次，合并为一条中奖3注的记录
+    assert_eq!(winners.len(), 1);
+    assert_eq!(winners[0].count, 3);
+    assert_eq!(winners[0].stake, Uint128::from(3u128));
     
     // 测试固定奖金分配
     let total_reward_pool = Uint128::from(10000u128); // 奖金池10,000代币
     let distributed_winners = RewardSystem::distribute_rewards(&mut winners, total_reward_pool).unwrap();
     
-    // 验证分配结果
-    assert_eq!(distributed_winners.len(), 3);
-    
-    // 验证每个中奖者获得800个代币（固定奖金）
-    for winner in &distributed_winners {
-        assert_eq!(winner.reward_amount, Uint128::from(800u128));
-    }
+    // 验证每注获得800个代币（固定奖金），按注数计入记录
+    assert_eq!(distributed_winners.len(), 1);
+    assert_eq!(distributed_winners[0].reward_amount, Uint128::from(2400u128));
     
     // 验证总分配金额
     let total_distributed: Uint128 = distributed_winners.iter()
@@ -151,13 +179,9 @@ fn test_reward_distribution_equal_split_detailed() {
     let total_reward_pool = Uint128::from(1500u128); // 奖金池1,500代币
     let distributed_winners = RewardSystem::distribute_rewards(&mut winners, total_reward_pool).unwrap();
     
-    // 验证分配结果
-    assert_eq!(distributed_winners.len(), 3);
-    
-    // 验证每个中奖者获得500个代币（平分）
-    for winner in &distributed_winners {
-        assert_eq!(winner.reward_amount, Uint128::from(500u128));
-    }
+    // 验证分配结果：每注500个代币（平分），3注合计1,500
+    assert_eq!(distributed_winners.len(), 1);
+    assert_eq!(distributed_winners[0].reward_amount, Uint128::from(1500u128));
     
     // 验证总分配金额
     let total_distributed: Uint128 = distributed_winners.iter()
@@ -173,8 +197,11 @@ fn test_reward_distribution_with_remainder() {
     // 计算：3 × 800 = 2,400 > 1,000 ❌
     // 期望：每人获得333个代币（1,000 ÷ 3 = 333），余数1个代币保留在资金池
     
-    let participants = create_test_participants();
-    let winning_number = 123; // 中奖号码123，USER1中奖3次
+    let mut participants = create_test_participants();
+    for participant in participants.iter_mut() {
+        participant.lucky_numbers = vec![(123, 1)];
+    }
+    let winning_number = 123; // 中奖号码123，三人各中奖1次
     
     // 计算中奖者
     let mut winners = RewardSystem::calculate_winners(&participants, winning_number).unwrap();
@@ -251,9 +278,9 @@ fn test_reward_distribution_large_pool() {
     let total_reward_pool = Uint128::from(1000000u128);
     let distributed_winners = RewardSystem::distribute_rewards(&mut winners, total_reward_pool).unwrap();
     
-    // 验证固定奖金分配
+    // 验证固定奖金分配：3注 × 800
     for winner in &distributed_winners {
-        assert_eq!(winner.reward_amount, Uint128::from(800u128));
+        assert_eq!(winner.reward_amount, Uint128::from(800u128) * Uint128::from(winner.count));
     }
 }
 
@@ -264,12 +291,14 @@ fn test_reward_distribution_edge_case_winner_count() {
         Participant {
             address: Addr::unchecked(USER1),
             bet_amount: Uint128::from(1000u128),
-            lucky_numbers: vec![123],
+            lucky_numbers: vec![(123, 1)],
             random_seed: Some("user1_seed".to_string()),
             revealed: true,
             commitment_hash: Some("hash1".to_string()),
             bet_time: Timestamp::from_seconds(1000),
             reveal_time: Some(Timestamp::from_seconds(7000)),
+            deposit: Uint128::zero(),
+            tickets: vec![],
         },
     ];
     
@@ -322,7 +351,7 @@ fn test_winner_statistics() {
     
     // 验证统计信息
     assert_eq!(stats.total_winners, 3);
-    assert_eq!(stats.get_first_prize_count(), 3); // 所有中奖者都是一等奖
+    assert_eq!(stats.get_first_prize_count(), 3); // 所有中奖者都是直选
     assert_eq!(stats.total_rewards, Uint128::from(2400u128)); // 3 × 800 = 2,400
 }
 
@@ -335,13 +364,15 @@ fn test_reward_calculation_edge_cases() {
         Winner {
             address: Addr::unchecked(USER1),
             level: 1,
-            match_count: 1,
+            count: 1,
+            stake: Uint128::one(),
             reward_amount: Uint128::zero(),
         },
         Winner {
             address: Addr::unchecked(USER2),
             level: 1,
-            match_count: 1,
+            count: 1,
+            stake: Uint128::one(),
             reward_amount: Uint128::zero(),
         },
     ];
@@ -359,13 +390,15 @@ fn test_reward_calculation_edge_cases() {
         Winner {
             address: Addr::unchecked(USER1),
             level: 1,
-            match_count: 1,
+            count: 1,
+            stake: Uint128::one(),
             reward_amount: Uint128::zero(),
         },
         Winner {
             address: Addr::unchecked(USER2),
             level: 1,
-            match_count: 1,
+            count: 1,
+            stake: Uint128::one(),
             reward_amount: Uint128::zero(),
         },
     ];
@@ -378,3 +411,365 @@ fn test_reward_calculation_edge_cases() {
         assert_eq!(winner.reward_amount, Uint128::from(799u128)); // 1,599 ÷ 2 = 799
     }
 }
+
+/// 创建指定数量的中奖记录
+fn create_winners(count: usize) -> Vec<Winner> {
+    (0..count)
+        .map(|_| Winner {
+            address: Addr::unchecked(USER1),
+            level: 1,
+            count: 1,
+            stake: Uint128::one(),
+            reward_amount: Uint128::zero(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_prize_structure_fixed_multiplier() {
+    // 测试固定倍数：每注中奖获得 倍数 × 投注单位，奖金池不足时平分
+    let structure = PrizeStructure::FixedMultiplier { multiplier: Uint128::from(500u128) };
+    
+    let mut winners = create_winners(3);
+    let distributed = RewardSystem::distribute_rewards_with_structure(&mut winners, Uint128::from(10000u128), &structure).unwrap();
+    for winner in &distributed {
+        assert_eq!(winner.reward_amount, Uint128::from(500u128));
+    }
+    
+    // 3 × 500 = 1,500 > 1,000，平分奖金池
+    let mut winners = create_winners(3);
+    let distributed = RewardSystem::distribute_rewards_with_structure(&mut winners, Uint128::from(1000u128), &structure).unwrap();
+    for winner in &distributed {
+        assert_eq!(winner.reward_amount, Uint128::from(333u128));
+    }
+    
+    // 默认奖金结构保持每注800
+    assert_eq!(PrizeStructure::default(), PrizeStructure::FixedMultiplier { multiplier: Uint128::from(800u128) });
+}
+
+#[test]
+fn test_prize_structure_pool_percentage() {
+    // 测试奖金池比例：奖金池的50%由所有中奖注平分
+    let structure = PrizeStructure::PoolPercentage { rate: Decimal::percent(50) };
+    
+    let mut winners = create_winners(3);
+    let distributed = RewardSystem::distribute_rewards_with_structure(&mut winners, Uint128::from(1000u128), &structure).unwrap();
+    for winner in &distributed {
+        assert_eq!(winner.reward_amount, Uint128::from(166u128)); // 500 ÷ 3 = 166
+    }
+    
+    let total: Uint128 = distributed.iter().map(|w| w.reward_amount).sum();
+    assert_eq!(total, Uint128::from(498u128));
+}
+
+#[test]
+fn test_prize_structure_pari_mutuel() {
+    // 测试彩池制：整个奖金池由所有中奖注平分，与奖金池大小无关
+    let structure = PrizeStructure::PariMutuel;
+    
+    let mut winners = create_winners(4);
+    let distributed = RewardSystem::distribute_rewards_with_structure(&mut winners, Uint128::from(1_000_000u128), &structure).unwrap();
+    for winner in &distributed {
+        assert_eq!(winner.reward_amount, Uint128::from(250_000u128));
+    }
+    
+    let mut winners = create_winners(3);
+    let distributed = RewardSystem::distribute_rewards_with_structure(&mut winners, Uint128::from(1000u128), &structure).unwrap();
+    for winner in &distributed {
+        assert_eq!(winner.reward_amount, Uint128::from(333u128));
+    }
+}
+
+#[test]
+fn test_prize_structure_validation() {
+    // 测试奖金结构参数验证
+    assert!(PrizeStructure::PariMutuel.is_valid());
+    assert!(PrizeStructure::PoolPercentage { rate: Decimal::one() }.is_valid());
+    assert!(!PrizeStructure::PoolPercentage { rate: Decimal::zero() }.is_valid());
+    assert!(!PrizeStructure::PoolPercentage { rate: Decimal::percent(150) }.is_valid());
+    assert!(!PrizeStructure::FixedMultiplier { multiplier: Uint128::zero() }.is_valid());
+    
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.prize_structure = Some(PrizeStructure::PoolPercentage { rate: Decimal::zero() });
+    assert!(instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).is_err());
+}
+
+/// 在第 `cycle` 周期投注全部号码，揭秘后保证中奖一次
+fn bet_all_numbers(deps: cosmwasm_std::DepsMut, user: &str, cycle: u64) {
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    let seed = format!("{}_seed_{}", user, cycle);
+    let base = cycle * 10000;
+    execute(deps, mock_env_with_height(base + 1000), mock_info(user, &[Coin {
+        denom: DENOM.to_string(),
+        amount: Uint128::from(1000u128),
+    }]), ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &all_numbers, &seed),
+        referrer: None,
+    }).unwrap();
+}
+
+#[test]
+fn test_prize_structure_change_applies_to_next_session() {
+    // 测试奖金结构的修改只从下一会话开始生效
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+    let all_numbers: Vec<u16> = (0..1000).collect();
+
+    for cycle in 0..2u64 {
+        let base = cycle * 10000;
+        bet_all_numbers(deps.as_mut(), USER1, cycle);
+
+        if cycle == 0 {
+            // 当前会话已创建，修改不影响本期
+            execute(deps.as_mut(), mock_env_with_height(base + 2000), mock_info(ADMIN, &[]), ExecuteMsg::UpdateConfig {
+                service_fee_rate: None,
+                min_bet_amount: None,
+                max_bet_amount: None,
+                bet_denom: None,
+                pause_requested: None,
+                claim_expiry_blocks: None,
+                commitment_blocks: None,
+                reveal_blocks: None,
+                settlement_blocks: None,
+                commitment_seconds: None,
+                reveal_seconds: None,
+                settlement_seconds: None,
+                non_reveal_policy: None,
+                commitment_deposit: None,
+                randomness_source: None,
+                prize_structure: Some(PrizeStructure::PariMutuel),
+                prize_tiers: None,
+                ticket_prices: None,
+                extra_funds_policy: None,
+                fee_recipients: None,
+                fee_distribution: None,
+                referral_fee_share: None,
+            }).unwrap();
+        }
+
+        execute(deps.as_mut(), mock_env_with_height(base + 7000), mock_info(USER1, &[]), ExecuteMsg::RevealRandom {
+            lucky_numbers: all_numbers.clone(),
+            random_seed: format!("{}_seed_{}", USER1, cycle),
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        }).unwrap();
+        execute(deps.as_mut(), mock_env_with_height(base + 9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    }
+
+    // 第0期仍按固定倍数：奖金池900，中奖800，剩余100滚入下一期
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    assert_eq!(result.prize_structure, PrizeStructure::default());
+    assert_eq!(result.tiers[0].distributed, Uint128::from(800u128));
+    assert_eq!(result.jackpot_out, Uint128::from(100u128));
+
+    // 第1期按彩池制：奖金池 900 + 100 全部发放
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_1".to_string()).unwrap();
+    assert_eq!(result.prize_structure, PrizeStructure::PariMutuel);
+    assert_eq!(result.reward_pool, Uint128::from(1000u128));
+    assert_eq!(result.tiers[0].distributed, Uint128::from(1000u128));
+    assert_eq!(result.jackpot_out, Uint128::zero());
+}
+
+#[test]
+fn test_prize_tier_matching() {
+    // 测试各奖级的中奖条件，每注只计最高奖级
+    let all = PrizeTier::ALL;
+    assert_eq!(LotteryLogic::match_tier(123, 123, &all), Some(PrizeTier::Straight));
+    // 投注码和直选号码不中组选奖级，组选奖级只能由组选票获得
+    assert_eq!(LotteryLogic::match_tier(132, 123, &all), None);
+    assert_eq!(LotteryLogic::match_tier(321, 123, &all), None);
+    assert_eq!(LotteryLogic::match_tier(121, 112, &all), None);
+    assert_eq!(LotteryLogic::match_tier(211, 112, &all), None);
+    assert!(LotteryLogic::matches_tier(PrizeTier::GroupSix, 132, 123));
+    assert!(LotteryLogic::matches_tier(PrizeTier::GroupThree, 121, 112));
+    assert_eq!(LotteryLogic::match_tier(129, 123, &all), Some(PrizeTier::FrontPair));
+    assert_eq!(LotteryLogic::match_tier(923, 123, &all), Some(PrizeTier::BackPair));
+    assert_eq!(LotteryLogic::match_tier(456, 123, &all), None);
+    // 豹子号没有组选奖级
+    assert_eq!(LotteryLogic::match_tier(777, 777, &all), Some(PrizeTier::Straight));
+    assert_eq!(LotteryLogic::match_tier(77, 777, &all), Some(PrizeTier::BackPair));
+    
+    // 未启用的奖级不中奖，直选号码在未启用直选时按前二计
+    assert_eq!(LotteryLogic::match_tier(132, 123, &[PrizeTier::Straight]), None);
+    assert_eq!(LotteryLogic::match_tier(123, 123, &[PrizeTier::FrontPair, PrizeTier::BackPair]), Some(PrizeTier::FrontPair));
+    
+    assert_eq!(LotteryLogic::determine_winner_level(923, 123).unwrap(), 5);
+    assert_eq!(LotteryLogic::get_winner_level_name(3), "组选六");
+}
+
+#[test]
+fn test_tiered_reward_distribution() {
+    // 测试分奖级分配：直选70%按固定倍数，组选六和后二各10%按设定倍数
+    let tiers = vec![
+        TierPrize { tier: PrizeTier::Straight, pool_share: Decimal::percent(70), multiplier: None },
+        TierPrize { tier: PrizeTier::GroupSix, pool_share: Decimal::percent(10), multiplier: Some(Uint128::from(130u128)) },
+        TierPrize { tier: PrizeTier::BackPair, pool_share: Decimal::percent(10), multiplier: Some(Uint128::from(80u128)) },
+    ];
+    assert!(TierPrize::validate_all(&tiers));
+    
+    let mut participants = create_test_participants();
+    participants[0].lucky_numbers = vec![(123, 1), (132, 1)]; // 直选，投注码132不中组选六
+    participants[0].tickets = vec![Ticket { bet_type: BetType::Box, numbers: vec![132] }]; // 组选六
+    participants[1].lucky_numbers = vec![(723, 1), (823, 1), (923, 1)]; // 后二 ×3
+    participants[2].lucky_numbers = vec![(129, 1)]; // 前二未启用
+    
+    let mut winners = RewardSystem::calculate_winners_with_tiers(&participants, 123, &tiers, &TicketPrices::default()).unwrap();
+    // USER2 的3注后二合并为一条记录
+    assert_eq!(winners.len(), 3);
+    
+    let total_reward_pool = Uint128::from(2000u128);
+    let distributed = RewardSystem::distribute_tiered_rewards(
+        &mut winners,
+        total_reward_pool,
+        &PrizeStructure::default(),
+        &tiers,
+    ).unwrap();
+    
+    let stats = RewardSystem::get_winner_statistics(&distributed);
+    assert_eq!(stats.get_first_prize_count(), 1);
+    assert_eq!(stats.get_third_prize_count(), 1);
+    assert_eq!(stats.get_level_count(PrizeTier::BackPair.level()), 3);
+    
+    // 直选：奖金池1,400足够支付800
+    assert_eq!(distributed[0].reward_amount, Uint128::from(800u128));
+    // 组选六：奖金池200足够支付130
+    assert_eq!(distributed[1].reward_amount, Uint128::from(130u128));
+    // 后二：3 × 80 = 240 > 200，3注分得整个奖级奖金池
+    assert_eq!(distributed[2].count, 3);
+    assert_eq!(distributed[2].reward_amount, Uint128::from(200u128));
+    assert!(RewardSystem::validate_reward_distribution(&distributed, total_reward_pool).unwrap());
+}
+
+#[test]
+fn test_prize_tiers_validation() {
+    // 测试奖级配置验证
+    let straight = |share: u64| TierPrize { tier: PrizeTier::Straight, pool_share: Decimal::percent(share), multiplier: None };
+    let back_pair = |share: u64| TierPrize { tier: PrizeTier::BackPair, pool_share: Decimal::percent(share), multiplier: None };
+    
+    assert!(TierPrize::validate_all(&TierPrize::default_tiers()));
+    assert!(TierPrize::validate_all(&[straight(60), back_pair(40)]));
+    assert!(!TierPrize::validate_all(&[]));
+    assert!(!TierPrize::validate_all(&[straight(60), back_pair(50)]));
+    assert!(!TierPrize::validate_all(&[straight(50), straight(10)]));
+    assert!(!TierPrize::validate_all(&[straight(0)]));
+    
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.prize_tiers = Some(vec![straight(80), back_pair(30)]);
+    assert!(instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).is_err());
+}
+
+#[test]
+fn test_ticket_settlement_by_type() {
+    // 测试投注票按类型兑奖
+    let all = PrizeTier::ALL;
+    let box_ticket = Ticket { bet_type: BetType::Box, numbers: vec![321] };
+    let straight_ticket = Ticket { bet_type: BetType::Straight, numbers: vec![321] };
+    let combination_ticket = Ticket { bet_type: BetType::Combination, numbers: vec![123, 129, 456] };
+    
+    // 组选票：任意排列中奖，包括原顺序
+    assert_eq!(LotteryLogic::ticket_tiers(&box_ticket, 123, &all), vec![PrizeTier::GroupSix]);
+    assert_eq!(LotteryLogic::ticket_tiers(&box_ticket, 321, &all), vec![PrizeTier::GroupSix]);
+    assert_eq!(LotteryLogic::box_tier(112, 211), Some(PrizeTier::GroupThree));
+    assert!(LotteryLogic::ticket_tiers(&box_ticket, 124, &all).is_empty());
+    // 未启用组选奖级时组选票不中奖
+    assert!(LotteryLogic::ticket_tiers(&box_ticket, 123, &[PrizeTier::Straight]).is_empty());
+    
+    // 直选票按位匹配
+    assert_eq!(LotteryLogic::ticket_tiers(&straight_ticket, 321, &all), vec![PrizeTier::Straight]);
+    
+    // 复式票每个号码分别兑奖
+    assert_eq!(
+        LotteryLogic::ticket_tiers(&combination_ticket, 123, &all),
+        vec![PrizeTier::Straight, PrizeTier::FrontPair]
+    );
+    
+    let mut participants = create_test_participants();
+    participants[0].tickets = vec![box_ticket, combination_ticket];
+    let winners = RewardSystem::calculate_winners_with_tiers(&participants, 123, &TierPrize::default_tiers(), &TicketPrices::default()).unwrap();
+    // USER1 的3个投注码123加上复式票中的123合并为4注直选，组选票在只启用直选时不中奖
+    assert_eq!(winners.len(), 1);
+    assert_eq!((winners[0].address.as_str(), winners[0].level, winners[0].count), (USER1, 1, 4));
+    
+    // 直选票和复式票的号码是中奖号码的排列时不中组选奖级
+    let permutations = Ticket { bet_type: BetType::Combination, numbers: vec![132, 213, 321] };
+    assert!(LotteryLogic::ticket_tiers(&straight_ticket, 123, &all).is_empty());
+    assert!(LotteryLogic::ticket_tiers(&permutations, 123, &all).is_empty());
+}
+
+#[test]
+fn test_payouts_scale_with_ticket_price() {
+    // 测试奖金按投注单价缩放：单价10的直选票中奖所得是1个基础代币投注码的10倍
+    let prices = TicketPrices {
+        straight_price: Uint128::from(10u128),
+        box_price: Uint128::from(5u128),
+        combination_price: Uint128::from(2u128),
+    };
+    let mut participants = create_test_participants();
+    participants[0].lucky_numbers = vec![(123, 1)];
+    participants[1].lucky_numbers = vec![];
+    participants[1].tickets = vec![Ticket { bet_type: BetType::Straight, numbers: vec![123] }];
+    participants[2].lucky_numbers = vec![];
+    participants[2].tickets = vec![Ticket { bet_type: BetType::Combination, numbers: vec![123, 456] }];
+    
+    let winners = RewardSystem::calculate_winners_with_tiers(&participants, 123, &TierPrize::default_tiers(), &prices).unwrap();
+    let stakes: Vec<u128> = winners.iter().map(|w| w.stake.u128()).collect();
+    assert_eq!(stakes, vec![1, 10, 2]);
+    
+    // 固定倍数：奖金池充足时每个基础代币的中奖投注获得800
+    let distributed = RewardSystem::distribute_rewards(&mut winners.clone(), Uint128::from(100_000u128)).unwrap();
+    let rewards: Vec<u128> = distributed.iter().map(|w| w.reward_amount.u128()).collect();
+    assert_eq!(rewards, vec![800, 8000, 1600]);
+    
+    // 奖金池不足时按投注额比例分配整个奖金池
+    let distributed = RewardSystem::distribute_rewards(&mut winners.clone(), Uint128::from(1300u128)).unwrap();
+    let rewards: Vec<u128> = distributed.iter().map(|w| w.reward_amount.u128()).collect();
+    assert_eq!(rewards, vec![100, 1000, 200]);
+    
+    // 彩池制同样按投注额比例分配
+    let distributed = RewardSystem::distribute_rewards_with_structure(
+        &mut winners.clone(),
+        Uint128::from(2600u128),
+        &PrizeStructure::PariMutuel,
+    ).unwrap();
+    let rewards: Vec<u128> = distributed.iter().map(|w| w.reward_amount.u128()).collect();
+    assert_eq!(rewards, vec![200, 2000, 400]);
+    
+    // 组选票按组选单价计投注额
+    participants[0].tickets = vec![Ticket { bet_type: BetType::Box, numbers: vec![321] }];
+    let tiers = vec![TierPrize { tier: PrizeTier::GroupSix, pool_share: Decimal::one(), multiplier: None }];
+    let winners = RewardSystem::calculate_winners_with_tiers(&participants, 123, &tiers, &prices).unwrap();
+    assert_eq!(winners.len(), 1);
+    assert_eq!(winners[0].stake, Uint128::from(5u128));
+}
+
+#[test]
+fn test_ticket_validation_and_pricing() {
+    // 测试投注票验证和计价
+    let prices = TicketPrices {
+        straight_price: Uint128::from(2u128),
+        box_price: Uint128::from(3u128),
+        combination_price: Uint128::from(2u128),
+    };
+    let tickets = vec![
+        Ticket { bet_type: BetType::Straight, numbers: vec![5] },
+        Ticket { bet_type: BetType::Box, numbers: vec![112] },
+        Ticket { bet_type: BetType::Combination, numbers: vec![1, 2, 3] },
+    ];
+    assert!(LotteryLogic::validate_tickets(&tickets).is_ok());
+    assert_eq!(LotteryLogic::tickets_cost(&tickets, &prices).unwrap(), Uint128::from(11u128));
+    
+    let invalid = [
+        Ticket { bet_type: BetType::Straight, numbers: vec![1, 2] },
+        Ticket { bet_type: BetType::Box, numbers: vec![777] },
+        Ticket { bet_type: BetType::Combination, numbers: vec![1] },
+        Ticket { bet_type: BetType::Combination, numbers: vec![1, 1] },
+        Ticket { bet_type: BetType::Straight, numbers: vec![1000] },
+    ];
+    for ticket in invalid {
+        assert!(LotteryLogic::validate_tickets(&[ticket]).is_err());
+    }
+    
+    assert!(!TicketPrices { straight_price: Uint128::zero(), ..prices }.is_valid());
+}
@@ -1,6 +1,6 @@
 use cosmwasm_std::{
     testing::{mock_dependencies, mock_env},
-    Addr, Uint128, Decimal, Timestamp, Coin, MessageInfo,
+    Addr, Uint128, Decimal, Timestamp, Coin, MessageInfo, BankMsg, CosmosMsg,
 };
 use std::str::FromStr;
 
@@ -8,7 +8,8 @@ use dd_3d_lottery::{
     contract::instantiate,
     execute::execute,
     msg::{InstantiateMsg, ExecuteMsg},
-    state::{CURRENT_SESSION, REENTRANCY_LOCK, STATS, LotteryPhase},
+    state::{CURRENT_SESSION, LOTTERY_HISTORY, REENTRANCY_LOCK, STATS, LotteryPhase, ExtraFundsPolicy},
+    lottery_logic::LotteryLogic,
     error::ContractError,
 };
 
@@ -51,6 +52,27 @@ fn mock_instantiate_msg() -> InstantiateMsg {
         max_bet_amount: Uint128::from(1000000u128),
         bet_denom: DENOM.to_string(),
         pause_requested: Some(false),
+        claim_expiry_blocks: None,
+        commitment_blocks: None,
+        reveal_blocks: None,
+        settlement_blocks: None,
+        genesis_height: None,
+        schedule_mode: None,
+        commitment_seconds: None,
+        reveal_seconds: None,
+        settlement_seconds: None,
+        genesis_time: None,
+        non_reveal_policy: None,
+        commitment_deposit: None,
+        randomness_source: None,
+        prize_structure: None,
+        prize_tiers: None,
+        ticket_prices: None,
+        cw20_token: None,
+        extra_funds_policy: None,
+        fee_recipients: None,
+        fee_distribution: None,
+        referral_fee_share: None,
     }
 }
 
@@ -81,6 +103,7 @@ fn test_reentrancy_attack_protection() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     // 尝试在重入锁状态下投注，应该失败
@@ -106,6 +129,23 @@ fn test_unauthorized_access_protection() {
         max_bet_amount: None,
         bet_denom: None,
         pause_requested: None,
+        claim_expiry_blocks: None,
+        commitment_blocks: None,
+        reveal_blocks: None,
+        settlement_blocks: None,
+        commitment_seconds: None,
+        reveal_seconds: None,
+        settlement_seconds: None,
+        non_reveal_policy: None,
+        commitment_deposit: None,
+        randomness_source: None,
+        prize_structure: None,
+        prize_tiers: None,
+        ticket_prices: None,
+        extra_funds_policy: None,
+        fee_recipients: None,
+        fee_distribution: None,
+        referral_fee_share: None,
     };
 
     let result = execute(deps.as_mut(), env, update_info, update_msg);
@@ -130,6 +170,7 @@ fn test_invalid_input_validation() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(500, &vec![123; 500], "user1_seed"),
+        referrer: None,
     };
 
     let result = execute(deps.as_mut(), env.clone(), bet_info.clone(), bet_msg);
@@ -143,6 +184,7 @@ fn test_invalid_input_validation() {
 
     let bet_msg2 = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(2000000, &vec![123; 2000000], "user1_seed"),
+        referrer: None,
     };
 
     let result2 = execute(deps.as_mut(), env.clone(), bet_info2, bet_msg2);
@@ -167,6 +209,7 @@ fn test_commitment_hash_manipulation() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: "invalid_hash".to_string(), // 无效格式
+        referrer: None,
     };
 
     let result = execute(deps.as_mut(), env.clone(), bet_info.clone(), bet_msg);
@@ -175,6 +218,7 @@ fn test_commitment_hash_manipulation() {
     // 测试承诺哈希长度验证
     let bet_msg2 = ExecuteMsg::PlaceBet {
         commitment_hash: "a1b2c3d4e5f6".to_string(), // 长度不足
+        referrer: None,
     };
 
     let result2 = execute(deps.as_mut(), env.clone(), bet_info.clone(), bet_msg2);
@@ -200,6 +244,7 @@ fn test_phase_manipulation_attack() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     // 在揭秘阶段尝试投注，应该失败
@@ -225,6 +270,7 @@ fn test_lucky_number_manipulation() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -237,6 +283,8 @@ fn test_lucky_number_manipulation() {
     let reveal_msg = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![1000; 1000], // 超出范围0-999
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let result = execute(deps.as_mut(), env.clone(), reveal_info.clone(), reveal_msg);
@@ -246,6 +294,8 @@ fn test_lucky_number_manipulation() {
     let reveal_msg2 = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![123; 500], // 数量不匹配
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let result2 = execute(deps.as_mut(), env.clone(), reveal_info.clone(), reveal_msg2);
@@ -270,6 +320,7 @@ fn test_duplicate_betting_attack() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info.clone(), bet_msg.clone()).unwrap();
@@ -282,6 +333,7 @@ fn test_duplicate_betting_attack() {
 
     let bet_msg2 = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     let result = execute(deps.as_mut(), env, bet_info2, bet_msg2);
@@ -306,6 +358,7 @@ fn test_commitment_consistency_attack() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
@@ -318,6 +371,8 @@ fn test_commitment_consistency_attack() {
     let reveal_msg2 = ExecuteMsg::RevealRandom {
         lucky_numbers: vec![456; 1000], // 与承诺阶段不一致
         random_seed: "user1_seed".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
     };
 
     let result = execute(deps.as_mut(), env, reveal_info2, reveal_msg2);
@@ -343,6 +398,7 @@ fn test_overflow_attack_protection() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000000, &vec![123; 1000000], "user1_seed"),
+        referrer: None,
     };
 
     // 应该成功处理最大投注金额
@@ -369,6 +425,7 @@ fn test_underflow_attack_protection() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     // 应该成功处理最小投注金额
@@ -433,6 +490,7 @@ fn test_timing_attack_protection() {
 
                 let bet_msg = ExecuteMsg::PlaceBet {
                     commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user_seed"),
+                    referrer: None,
                 };
 
                 let result = execute(deps.as_mut(), env, bet_info, bet_msg);
@@ -468,13 +526,14 @@ fn test_data_integrity_protection() {
 
     let bet_msg = ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
     };
 
     execute(deps.as_mut(), env.clone(), bet_info, bet_msg).unwrap();
 
     // 验证数据完整性
     let session = CURRENT_SESSION.load(&deps.storage).unwrap();
-    assert_eq!(session.participants.len(), 1);
+    assert_eq!(session.participant_count, 1);
     assert_eq!(session.total_pool, Uint128::from(1000u128));
     assert_eq!(session.service_fee, Uint128::from(100u128)); // 1000 * 0.1
 
@@ -482,7 +541,8 @@ fn test_data_integrity_protection() {
     let stats = STATS.load(&deps.storage).unwrap();
     assert_eq!(stats.total_participants, 1);
     assert_eq!(stats.total_pool, Uint128::from(1000u128));
-    assert_eq!(stats.total_service_fee, Uint128::from(100u128));
+    // 服务费在结算时计提，投注时不计入统计
+    assert_eq!(stats.total_service_fee, Uint128::zero());
 }
 
 #[test]
@@ -513,6 +573,7 @@ fn test_malicious_input_handling() {
 
         let bet_msg = ExecuteMsg::PlaceBet {
             commitment_hash: malicious_input.to_string(),
+            referrer: None,
         };
 
         let result = execute(deps.as_mut(), env.clone(), bet_info.clone(), bet_msg);
@@ -520,3 +581,180 @@ fn test_malicious_input_handling() {
         assert!(result.is_err(), "恶意输入被接受: {}", malicious_input);
     }
 }
+
+/// 揭秘阶段结束后结算一期由 USER1、USER2 参与的彩票，返回中奖号码和结算熵
+/// 首个结算交易在 `first_height`，之后的批次在 `next_height`
+fn settle_at_height(first_height: u64, next_height: u64) -> (u16, String) {
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    for (user, seed) in [(USER1, "seed_a"), (USER2, "seed_b")] {
+        let bet_info = mock_info(user, &[Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(1000u128),
+        }]);
+        let bet_msg = ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, seed),
+            referrer: None,
+        };
+        execute(deps.as_mut(), mock_env_with_height(1000), bet_info, bet_msg).unwrap();
+
+        let reveal_msg = ExecuteMsg::RevealRandom {
+            lucky_numbers: all_numbers.clone(),
+            random_seed: seed.to_string(),
+            tickets: vec![],
+            lucky_number_counts: vec![],
+        };
+        execute(deps.as_mut(), mock_env_with_height(7000), mock_info(user, &[]), reveal_msg).unwrap();
+    }
+
+    let env = mock_env_with_height(first_height);
+    execute(deps.as_mut(), env.clone(), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: Some(1) }).unwrap();
+    while !LOTTERY_HISTORY.has(&deps.storage, "session_0".to_string()) {
+        execute(deps.as_mut(), mock_env_with_height(next_height), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: Some(1) }).unwrap();
+    }
+
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    assert_eq!(result.post_reveal_entropy, LotteryLogic::post_reveal_entropy(&env.block.chain_id, "session_0", env.block.height, env.block.time));
+    (result.winning_number, result.post_reveal_entropy)
+}
+
+/// 最后揭秘者偏差与结算者偏差
+///
+/// 威胁模型：
+/// - 揭秘在链上公开进行，最后一名揭秘者可以在看到其他人的种子后计算出结果，
+///   再决定揭秘还是放弃，从而在两个结果中二选一（多人合谋时可选择的结果更多）；
+/// - 结算交易的发送者可以选择在结算阶段的哪个区块结算。
+///
+/// 缓解措施：
+/// - 只使用参与者种子时，结算熵由首个结算交易所在区块生成，揭秘阶段结束前无法预知，
+///   最后揭秘者无法在揭秘前算出结果；
+/// - 结算熵在首次结算时保存在进度中，分批结算的后续交易不能再改变结果；
+/// - 未揭秘者的承诺押金被罚没（见 `test_non_revealer_forfeits_commitment_deposit`），放弃揭秘有成本。
+///
+/// 残余风险：首个结算交易的发送者可以在结算阶段内选择提交区块，对结果有一次性的影响，
+/// 与最后揭秘者合谋时仍可产生偏差；只有信标或混合随机数来源能消除该偏差（见 beacon_tests）。
+#[test]
+fn test_settlement_caller_cannot_choose_outcome() {
+    // 揭秘完全相同，首个结算交易的区块相同时，后续批次在哪个区块提交都不改变结算熵和中奖号码
+    let outcome = settle_at_height(9000, 9001);
+    assert_eq!(settle_at_height(9000, 9500), outcome);
+    assert_eq!(settle_at_height(9000, 9999), outcome);
+
+    // 首个结算交易的区块不同则结算熵不同：这是结算者唯一一次能施加的影响
+    let (_, entropy) = settle_at_height(9500, 9501);
+    assert_ne!(entropy, outcome.1);
+}
+
+#[test]
+fn test_non_revealer_forfeits_commitment_deposit() {
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.commitment_deposit = Some(Uint128::from(100u128));
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+
+    let all_numbers: Vec<u16> = (0..1000).collect();
+    let env = mock_env_with_height(1000);
+
+    // 转账不足以支付押金
+    let res = execute(deps.as_mut(), env.clone(), mock_info(ATTACKER, &[Coin {
+        denom: DENOM.to_string(),
+        amount: Uint128::from(50u128),
+    }]), ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &all_numbers, "attacker_seed"),
+        referrer: None,
+    });
+    assert_eq!(res.unwrap_err(), ContractError::InsufficientDeposit { required: Uint128::from(100u128) });
+    assert!(!REENTRANCY_LOCK.load(&deps.storage).unwrap());
+
+    // 转账 = 投注金额 + 押金
+    for (user, seed) in [(USER1, "seed_a"), (ATTACKER, "attacker_seed")] {
+        execute(deps.as_mut(), env.clone(), mock_info(user, &[Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(1100u128),
+        }]), ExecuteMsg::PlaceBet {
+            commitment_hash: generate_commitment_hash(1000, &all_numbers, seed),
+            referrer: None,
+        }).unwrap();
+    }
+    let session = CURRENT_SESSION.load(&deps.storage).unwrap();
+    assert_eq!(session.total_pool, Uint128::from(2000u128));
+
+    // 揭秘者取回押金
+    let res = execute(deps.as_mut(), mock_env_with_height(7000), mock_info(USER1, &[]), ExecuteMsg::RevealRandom {
+        lucky_numbers: all_numbers.clone(),
+        random_seed: "seed_a".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
+    }).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: USER1.to_string(),
+        amount: vec![Coin {
+            denom: DENOM.to_string(),
+            amount: Uint128::from(100u128),
+        }],
+    }));
+
+    // 不能重复揭秘重复取回押金
+    let res = execute(deps.as_mut(), mock_env_with_height(7001), mock_info(USER1, &[]), ExecuteMsg::RevealRandom {
+        lucky_numbers: all_numbers,
+        random_seed: "seed_a".to_string(),
+        tickets: vec![],
+        lucky_number_counts: vec![],
+    });
+    assert_eq!(res.unwrap_err(), ContractError::RandomSeedAlreadyRevealed);
+
+    // 攻击者放弃揭秘：投注没收，押金罚没并入奖金池
+    execute(deps.as_mut(), mock_env_with_height(9500), mock_info(ADMIN, &[]), ExecuteMsg::SettleLottery { limit: None }).unwrap();
+    let result = LOTTERY_HISTORY.load(&deps.storage, "session_0".to_string()).unwrap();
+    assert_eq!(result.non_reveal.non_revealed_count, 1);
+    assert_eq!(result.non_reveal.slashed_deposits, Uint128::from(100u128));
+    assert_eq!(result.reward_pool, Uint128::from(1900u128));
+}
+
+#[test]
+fn test_extra_denoms_rejected_by_default() {
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), mock_instantiate_msg()).unwrap();
+
+    // 随投注附带其他面额的代币，投注被拒绝
+    let funds = [
+        Coin { denom: DENOM.to_string(), amount: Uint128::from(1000u128) },
+        Coin { denom: "uatom".to_string(), amount: Uint128::from(5u128) },
+    ];
+    let res = execute(deps.as_mut(), mock_env_with_height(1000), mock_info(USER1, &funds), ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
+    });
+    assert_eq!(res.unwrap_err(), ContractError::UnexpectedFunds { denom: "uatom".to_string() });
+    assert!(!REENTRANCY_LOCK.load(&deps.storage).unwrap());
+    assert!(CURRENT_SESSION.may_load(&deps.storage).unwrap().is_none());
+}
+
+#[test]
+fn test_extra_denoms_refunded_when_configured() {
+    let mut deps = mock_dependencies();
+    let mut msg = mock_instantiate_msg();
+    msg.extra_funds_policy = Some(ExtraFundsPolicy::Refund);
+    instantiate(deps.as_mut(), mock_env_with_height(1000), mock_info(ADMIN, &[]), msg).unwrap();
+
+    // 投注成功，多余代币在同一响应中退还，不计入奖金池
+    let funds = [
+        Coin { denom: DENOM.to_string(), amount: Uint128::from(1000u128) },
+        Coin { denom: "uatom".to_string(), amount: Uint128::from(5u128) },
+        Coin { denom: "uosmo".to_string(), amount: Uint128::from(7u128) },
+    ];
+    let res = execute(deps.as_mut(), mock_env_with_height(1000), mock_info(USER1, &funds), ExecuteMsg::PlaceBet {
+        commitment_hash: generate_commitment_hash(1000, &vec![123; 1000], "user1_seed"),
+        referrer: None,
+    }).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+        to_address: USER1.to_string(),
+        amount: funds[1..].to_vec(),
+    }));
+
+    let session = CURRENT_SESSION.load(&deps.storage).unwrap();
+    assert_eq!(session.total_pool, Uint128::from(1000u128));
+}
@@ -92,6 +92,27 @@ pub fn mock_instantiate_msg_with_config(config: &TestConfig) -> dd_3d_lottery::m
         max_bet_amount: config.max_bet_amount,
         bet_denom: config.bet_denom.clone(),
         pause_requested: Some(config.pause_requested),
+        claim_expiry_blocks: None,
+        commitment_blocks: None,
+        reveal_blocks: None,
+        settlement_blocks: None,
+        genesis_height: None,
+        schedule_mode: None,
+        commitment_seconds: None,
+        reveal_seconds: None,
+        settlement_seconds: None,
+        genesis_time: None,
+        non_reveal_policy: None,
+        commitment_deposit: None,
+        randomness_source: None,
+        prize_structure: None,
+        prize_tiers: None,
+        ticket_prices: None,
+        cw20_token: None,
+        extra_funds_policy: None,
+        fee_recipients: None,
+        fee_distribution: None,
+        referral_fee_share: None,
     }
 }
 
@@ -126,6 +147,7 @@ pub fn mock_env_with_height(height: u64) -> cosmwasm_std::Env {
 pub fn create_place_bet_msg(bet_amount: u128, lucky_numbers: &[u16], random_seed: &str) -> dd_3d_lottery::msg::ExecuteMsg {
     dd_3d_lottery::msg::ExecuteMsg::PlaceBet {
         commitment_hash: generate_commitment_hash(bet_amount, lucky_numbers, random_seed),
+        referrer: None,
     }
 }
 
@@ -141,12 +163,14 @@ pub fn create_reveal_msg(lucky_numbers: Vec<u16>, random_seed: String) -> dd_3d_
     dd_3d_lottery::msg::ExecuteMsg::RevealRandom {
         lucky_numbers,
         random_seed,
+        tickets: vec![],
+        lucky_number_counts: vec![],
     }
 }
 
 /// 创建测试用的结算消息
 pub fn create_settle_msg() -> dd_3d_lottery::msg::ExecuteMsg {
-    dd_3d_lottery::msg::ExecuteMsg::SettleLottery {}
+    dd_3d_lottery::msg::ExecuteMsg::SettleLottery { limit: None }
 }
 
 /// 创建测试用的投注信息
@@ -171,7 +195,7 @@ pub fn create_query_msgs() -> Vec<dd_3d_lottery::msg::QueryMsg> {
         dd_3d_lottery::msg::QueryMsg::GetCurrentPhase {},
         dd_3d_lottery::msg::QueryMsg::GetConfig {},
         dd_3d_lottery::msg::QueryMsg::GetStats {},
-        dd_3d_lottery::msg::QueryMsg::GetParticipants {},
+        dd_3d_lottery::msg::QueryMsg::GetParticipants { start_after: None, limit: None, order: None },
     ]
 }
 
@@ -275,6 +299,7 @@ pub mod security {
 pub mod data_generator {
     use cosmwasm_std::{Addr, Uint128, Timestamp};
     use dd_3d_lottery::state::Participant;
+    use dd_3d_lottery::lottery_logic::LotteryLogic;
     
     /// 生成测试参与者
     /// 
@@ -292,12 +317,14 @@ pub mod data_generator {
             participants.push(Participant {
                 address: Addr::unchecked(format!("cosmwasm1user{:04}", i)),
                 bet_amount: Uint128::from(bet_amount),
-                lucky_numbers: lucky_numbers.clone(),
+                lucky_numbers: LotteryLogic::to_number_counts(&lucky_numbers),
                 random_seed: Some(format!("user{}_seed", i)),
                 revealed: true,
                 commitment_hash: Some(format!("hash{}", i)),
                 bet_time: Timestamp::from_seconds(1000),
                 reveal_time: Some(Timestamp::from_seconds(7000)),
+                deposit: Uint128::zero(),
+                tickets: vec![],
             });
         }
         